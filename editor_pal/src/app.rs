@@ -1,19 +1,34 @@
-use std::fs::File;
-use std::io::Read;
+use std::path::{Path, PathBuf};
 
 use eframe::egui::{self, Layout};
 use eframe::Frame;
 use egui::{Color32, Pos2, Rect, Vec2};
+use lib_pxc::compression::palette::{
+    color_histogram, palette_compression, PaletteCompressionError,
+};
 
-use crate::canvas::{self, Canvas, CELL_SIZE};
+use crate::canvas::{self, Canvas};
 use crate::chunk::CHUNK_SIZE;
-use crate::filemanager;
-use crate::image_source::ImageSource;
+use crate::document::DocumentSettings;
+use crate::filemanager::{self, ImageHandlingError, OpenedImage};
+use crate::guide::{snap_to_guides, Guide, GuideOrientation, GUIDE_SNAP_DISTANCE};
+use crate::minimap::{self, Minimap, MINIMAP_SIZE};
 use crate::palette::Palette;
-use crate::viewport::{update_canvas_viewport, ViewportInfo, ViewportOptions};
+use crate::palette_file::{self, PaletteFileError};
+use crate::recent_files::RecentFiles;
+use crate::selection::Selection;
+use crate::shapes;
+use crate::size_dialog::{SizeDialog, SizeDialogKind, SizeDialogResult};
+use crate::toast::{ToastLevel, Toasts};
+use crate::tool::Tool;
+use crate::undo::{PixelChange, UndoStack, UndoStep};
+use crate::viewport::{
+    checkerboard_cells, update_canvas_viewport, zoom_to_fit, PointerMode, ViewportInfo,
+    ViewportOptions, MAX_ZOOM, MIN_ZOOM,
+};
 
 pub fn grid_to_screen(viewport_info: &ViewportInfo, grid_pos: (u32, u32)) -> (f32, f32) {
-    let cell_size = viewport_info.zoom() * CELL_SIZE as f32;
+    let cell_size = viewport_info.cell_size();
     let x = grid_pos.0 as f32 * cell_size;
     let y = grid_pos.1 as f32 * cell_size;
 
@@ -23,12 +38,32 @@ pub fn grid_to_screen(viewport_info: &ViewportInfo, grid_pos: (u32, u32)) -> (f3
     (adjusted_pos.x, adjusted_pos.y)
 }
 
-fn screen_to_grid(viewport_info: &ViewportInfo, canvas_dims: &(u32, u32), pos: Pos2) -> (u32, u32) {
+fn screen_to_grid_unclamped(viewport_info: &ViewportInfo, pos: Pos2) -> (u32, u32) {
     let viewport = viewport_info.get_parent_rect().unwrap();
     let pos = pos.to_vec2() - viewport.min.to_vec2();
 
-    let cell_size = viewport_info.zoom() * CELL_SIZE as f32;
-    let pos = Vec2::new((pos.x / cell_size).floor(), (pos.y / cell_size).floor());
+    let cell_size = viewport_info.cell_size();
+    let pos = Vec2::new((pos.x / cell_size).floor(), (pos.y / cell_size).floor()).max(Vec2::ZERO);
+
+    (pos.x as u32, pos.y as u32)
+}
+
+/// Like `screen_to_grid_unclamped`, but keeps positions left of or above the
+/// canvas negative
+fn screen_to_grid_signed(viewport_info: &ViewportInfo, pos: Pos2) -> (i32, i32) {
+    let viewport = viewport_info.get_parent_rect().unwrap();
+    let pos = pos - viewport.min;
+
+    let cell_size = viewport_info.cell_size();
+    (
+        (pos.x / cell_size).floor() as i32,
+        (pos.y / cell_size).floor() as i32,
+    )
+}
+
+fn screen_to_grid(viewport_info: &ViewportInfo, canvas_dims: &(u32, u32), pos: Pos2) -> (u32, u32) {
+    let pos = screen_to_grid_unclamped(viewport_info, pos);
+    let pos = Vec2::new(pos.0 as f32, pos.1 as f32);
 
     let pos = pos.clamp(
         Vec2::ZERO,
@@ -38,24 +73,309 @@ fn screen_to_grid(viewport_info: &ViewportInfo, canvas_dims: &(u32, u32), pos: P
     (pos.x as u32, pos.y as u32)
 }
 
+pub const DEFAULT_CANVAS_SIZE: (u32, u32) = (32, 32);
+
+const GUIDE_COLOR: Color32 = Color32::from_rgb(0, 200, 255);
+const SELECTION_COLOR: Color32 = Color32::from_rgb(255, 255, 0);
+const MAX_BRUSH_SIZE: u32 = 16;
+const TILE_COLOR: Color32 = Color32::from_rgb(255, 170, 0);
+const CHECKER_LIGHT: Color32 = Color32::from_gray(204);
+const CHECKER_DARK: Color32 = Color32::from_gray(153);
+/// How close in screen pixels the pointer must be to grab a guide
+const GUIDE_GRAB_DISTANCE: f32 = 4.0;
+
+/// Actions from a palette swatch's context menu, applied once the palette
+/// grid has been drawn
+enum SwatchAction {
+    Duplicate(usize),
+    Delete(usize),
+    SetTransparent(usize),
+    ReplacePixels(usize),
+}
+
+enum LayerAction {
+    Add,
+    Remove(usize),
+    Select(usize),
+    SetVisible(usize, bool),
+    SetOpacity(usize, f32),
+    Move { from: usize, to: usize },
+}
+
+/// Configures the initial state of a [`PixelEditor`]
+#[derive(Default)]
+pub struct PixelEditorBuilder {
+    dimensions: Option<(u32, u32)>,
+    palette: Option<Palette>,
+    file: Option<PathBuf>,
+    cell_size: Option<f32>,
+}
+
+impl PixelEditorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dimensions(mut self, width: u32, height: u32) -> Self {
+        self.dimensions = Some((width, height));
+        self
+    }
+
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = Some(palette);
+        self
+    }
+
+    /// Image to open on startup; its dimensions override `dimensions`
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// On-screen side of a canvas pixel at 100% zoom, in points. Defaults to
+    /// `canvas::CELL_SIZE`.
+    pub fn cell_size(mut self, size: f32) -> Self {
+        self.cell_size = Some(size);
+        self
+    }
+
+    pub fn build(self) -> Result<PixelEditor, ImageHandlingError> {
+        let (width, height) = self.dimensions.unwrap_or(DEFAULT_CANVAS_SIZE);
+
+        let mut editor = PixelEditor {
+            canvas: Canvas::new(width, height),
+            palette: self.palette.unwrap_or_default(),
+            ..PixelEditor::new()
+        };
+        if let Some(size) = self.cell_size {
+            editor.viewport_info.set_base_cell_size(size);
+        }
+
+        if let Some(path) = self.file {
+            let opened = filemanager::open_image_path(&path)?;
+            editor.handle_image_load(opened);
+        }
+
+        Ok(editor)
+    }
+}
+
 #[derive(Default)]
 pub struct PixelEditor {
     canvas: Canvas,
     viewport_info: ViewportInfo,
     viewport_options: ViewportOptions,
     palette: Palette,
+    document: DocumentSettings,
+    tool: Tool,
+    // Tool to return to when the eyedropper shortcut is toggled off
+    previous_tool: Tool,
+    // Side length of the square stamped by the pencil and eraser
+    brush_size: u32,
+    // Start and current end of the shape or selection being dragged out
+    shape_drag: Option<((i32, i32), (i32, i32))>,
+    selection: Option<Selection>,
+    // Copied pixels, relative to the top-left of the copied selection
+    clipboard: Vec<((u32, u32), Color32)>,
+    // Cell under the pointer, where pastes land
+    hovered_cell: Option<(u32, u32)>,
+    size_dialog: Option<SizeDialog>,
+    // Whether "Clear Canvas" is waiting for confirmation
+    confirm_clear: bool,
+    toasts: Toasts,
+    recent_files: RecentFiles,
+    minimap: Minimap,
+    guides: Vec<Guide>,
+    snap_to_guides: bool,
+    dragged_guide: Option<usize>,
+    undo_stack: UndoStack,
+    // Pixel changes of the stroke being drawn, recorded as one undo step
+    // once the pointer is released
+    current_stroke: Vec<PixelChange>,
+    // Last cell painted by the current stroke, to join up fast drags
+    last_stroke_cell: Option<(u32, u32)>,
+    // egui time of the current frame, used to time toasts
+    time: f64,
 }
 
 impl PixelEditor {
     pub fn new() -> Self {
+        let (width, height) = DEFAULT_CANVAS_SIZE;
         Self {
-            canvas: Canvas::new(32, 32),
+            canvas: Canvas::new(width, height),
             viewport_info: ViewportInfo::new(),
             viewport_options: ViewportOptions::new(),
             palette: Palette::new(),
+            document: DocumentSettings::new(),
+            tool: Tool::default(),
+            previous_tool: Tool::default(),
+            brush_size: 1,
+            shape_drag: None,
+            selection: None,
+            clipboard: Vec::new(),
+            hovered_cell: None,
+            size_dialog: None,
+            confirm_clear: false,
+            toasts: Toasts::new(),
+            recent_files: RecentFiles::new(),
+            minimap: Minimap::new(),
+            guides: Vec::new(),
+            snap_to_guides: false,
+            dragged_guide: None,
+            undo_stack: UndoStack::new(),
+            current_stroke: Vec::new(),
+            last_stroke_cell: None,
+            time: 0.0,
+        }
+    }
+
+    /// Shows a message to the user as a toast
+    pub fn notify(&mut self, text: impl Into<String>) {
+        self.toasts.push(text, ToastLevel::Info, self.time);
+    }
+
+    /// Shows a failure to the user as an error toast
+    pub fn notify_error(&mut self, text: impl Into<String>) {
+        self.toasts.push(text, ToastLevel::Error, self.time);
+    }
+
+    pub fn toasts(&self) -> &Toasts {
+        &self.toasts
+    }
+
+    fn open_image(&mut self) {
+        match filemanager::pick_open_path() {
+            Ok(path) => self.open_image_at(&path),
+            Err(ImageHandlingError::DialogCanceled) => {}
+            Err(err) => self.notify_error(format!("Load failed: {}", err.user_message())),
+        }
+    }
+
+    pub fn open_image_at(&mut self, path: &Path) {
+        match filemanager::open_image_path(path) {
+            Ok(opened) => {
+                self.handle_image_load(opened);
+                self.recent_files.push(path);
+            }
+            Err(err) => self.notify_error(format!("Load failed: {}", err.user_message())),
+        }
+    }
+
+    pub fn recent_files(&self) -> &RecentFiles {
+        &self.recent_files
+    }
+
+    /// Opens the first of the files dropped onto the window
+    pub fn open_dropped_files(&mut self, files: &[egui::DroppedFile]) {
+        let Some(first) = files.first() else {
+            return;
+        };
+
+        match &first.path {
+            Some(path) => self.open_image_at(path),
+            None => self.notify_error(format!("Load failed: {} has no file path", first.name)),
+        }
+        if files.len() > 1 {
+            self.notify(format!(
+                "Only one file can be opened at a time, ignored {} more",
+                files.len() - 1
+            ));
         }
     }
 
+    fn save_image(&mut self) {
+        match filemanager::pick_save_path() {
+            Ok(path) => self.save_image_to(&path),
+            Err(ImageHandlingError::DialogCanceled) => {}
+            Err(err) => self.notify_error(format!("Save failed: {}", err.user_message())),
+        }
+    }
+
+    pub fn save_image_to(&mut self, path: &Path) {
+        let (dimensions, rgba_data) = self.canvas.get_export_data();
+        let metadata = self.document.to_metadata();
+        match filemanager::save_image(path, dimensions, rgba_data, &metadata) {
+            Ok(()) => self.notify(format!("File saved to {}", path.display())),
+            Err(err) => self.notify_error(format!("Save failed: {}", err.user_message())),
+        }
+    }
+
+    fn export_palette(&mut self) {
+        match palette_file::pick_palette_save_path() {
+            Ok(path) => self.export_palette_to(&path),
+            Err(PaletteFileError::DialogCanceled) => {}
+            Err(err) => self.notify_error(format!("Palette export failed: {}", err)),
+        }
+    }
+
+    /// Writes the palette as `.gpl` or `.json`, picked from the extension
+    pub fn export_palette_to(&mut self, path: &Path) {
+        match palette_file::save_palette(path, self.palette.colors()) {
+            Ok(()) => self.notify(format!("Palette saved to {}", path.display())),
+            Err(err) => self.notify_error(format!("Palette export failed: {}", err)),
+        }
+    }
+
+    fn import_palette(&mut self) {
+        match palette_file::pick_palette_open_path() {
+            Ok(path) => self.import_palette_from(&path),
+            Err(PaletteFileError::DialogCanceled) => {}
+            Err(err) => self.notify_error(format!("Palette import failed: {}", err)),
+        }
+    }
+
+    /// Replaces the palette with the colors of a `.gpl` or `.json` file,
+    /// keeping the first 256
+    pub fn import_palette_from(&mut self, path: &Path) {
+        match palette_file::load_palette(path) {
+            Ok(colors) => self.palette = Palette::from_colors(colors),
+            Err(err) => self.notify_error(format!("Palette import failed: {}", err)),
+        }
+    }
+
+    /// Replaces the palette with the colors used on the canvas, in order of
+    /// first appearance. Artwork with more than 256 colors keeps the 256 most
+    /// used ones.
+    pub fn use_image_palette(&mut self) {
+        let (_, data) = self.canvas.get_export_data();
+        let colors = match palette_compression(&data) {
+            Ok(compressed) => compressed.palette,
+            Err(PaletteCompressionError::PaletteOverflow(_)) => {
+                let histogram = color_histogram(&data).unwrap_or_default();
+                self.notify(format!(
+                    "The image has {} colors, kept the 256 most used",
+                    histogram.len()
+                ));
+                histogram.into_iter().map(|(color, _)| color).collect()
+            }
+            Err(err) => {
+                self.notify_error(format!("Could not read the image palette: {}", err));
+                return;
+            }
+        };
+        if colors.is_empty() {
+            self.notify("The image has no colors");
+            return;
+        }
+        self.palette = Palette::from_rgba(&colors);
+    }
+
+    pub fn canvas(&self) -> &Canvas {
+        &self.canvas
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn document(&self) -> &DocumentSettings {
+        &self.document
+    }
+
+    pub fn tool(&self) -> Tool {
+        self.tool
+    }
+
     fn get_visible_chunk_indexes(&self, rect: &Rect) -> Vec<(u32, u32)> {
         let canvas_dims = self.canvas.dimensions();
         let top_left = screen_to_grid(&self.viewport_info, &canvas_dims, rect.min);
@@ -77,12 +397,267 @@ impl PixelEditor {
         chunks
     }
 
-    fn handle_image_load<T: ImageSource>(&mut self, source: &T) {
-        self.canvas.load_image(source);
+    /// Paints a pixel as part of the current stroke
+    fn paint_pixel(&mut self, x: u32, y: u32, color: Color32) {
+        let before = self
+            .canvas
+            .get_layer_pixel(self.canvas.active_layer(), x, y)
+            .unwrap_or(Color32::TRANSPARENT);
+        if before == color || self.canvas.set_pixel(x, y, color).is_err() {
+            return;
+        }
+        self.current_stroke.push(PixelChange {
+            x,
+            y,
+            before,
+            after: color,
+        });
+    }
+
+    /// Paints the brush-sized square centered on (x, y). Cells left of or
+    /// above the canvas are skipped, the rest are bounded by `set_pixel`.
+    fn stamp_brush(&mut self, x: u32, y: u32, color: Color32) {
+        let size = self.brush_size.max(1);
+        let (min_x, min_y) = (
+            x.saturating_sub((size - 1) / 2),
+            y.saturating_sub((size - 1) / 2),
+        );
+        let (max_x, max_y) = (x + size / 2, y + size / 2);
+        for cell_y in min_y..=max_y {
+            for cell_x in min_x..=max_x {
+                self.paint_pixel(cell_x, cell_y, color);
+            }
+        }
+    }
+
+    /// Stamps the brush along the line from the previous cell of the stroke
+    /// to `cell`, so fast drags don't leave gaps between frames
+    fn stroke_to(&mut self, cell: (u32, u32), color: Color32) {
+        let from = self.last_stroke_cell.replace(cell).unwrap_or(cell);
+        let points = shapes::bresenham(
+            (from.0 as i32, from.1 as i32),
+            (cell.0 as i32, cell.1 as i32),
+        );
+        for (x, y) in points {
+            self.stamp_brush(x as u32, y as u32, color);
+        }
+    }
+
+    /// Applies the selected tool at a canvas position
+    fn use_tool(&mut self, (x, y): (u32, u32)) {
+        let color = self.palette.get_current_color();
+        match self.tool {
+            Tool::Pencil => self.stroke_to((x, y), color),
+            Tool::Eraser => self.stroke_to((x, y), Color32::TRANSPARENT),
+            Tool::Fill => self.with_snapshot(|canvas| {
+                canvas.flood_fill(x, y, color);
+            }),
+            Tool::Eyedropper => {
+                self.sample_color(x, y);
+            }
+            // Shapes span a drag and are drawn by `handle_shape_drag`
+            Tool::Line | Tool::Rectangle | Tool::Ellipse | Tool::Select => {}
+        }
+    }
+
+    /// Sets the current palette color to the pixel at (x, y). Transparent and
+    /// out of bounds pixels are ignored. Returns whether a color was picked.
+    fn sample_color(&mut self, x: u32, y: u32) -> bool {
+        match self.canvas.get_pixel(x, y) {
+            Ok(color) if color != Color32::TRANSPARENT => {
+                self.palette.set_current_color(color);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Switches to the eyedropper, or back to the previous tool if it is
+    /// already selected
+    fn toggle_eyedropper(&mut self) {
+        if self.tool == Tool::Eyedropper {
+            self.tool = self.previous_tool;
+        } else {
+            self.previous_tool = self.tool;
+            self.tool = Tool::Eyedropper;
+        }
+    }
+
+    /// Records the current stroke as a single undo step
+    fn end_stroke(&mut self) {
+        self.last_stroke_cell = None;
+        if !self.current_stroke.is_empty() {
+            let changes = std::mem::take(&mut self.current_stroke);
+            self.undo_stack.push(UndoStep::Pixels {
+                layer: self.canvas.active_layer(),
+                changes,
+            });
+        }
+    }
+
+    /// Runs a bulk operation on the active layer, recording the whole layer
+    /// before and after it as one undo step
+    fn with_snapshot(&mut self, operation: impl FnOnce(&mut Canvas)) {
+        self.end_stroke();
+        let layer = self.canvas.active_layer();
+        let dimensions = self.canvas.dimensions();
+        let before = self.canvas.get_layer_data(layer);
+        operation(&mut self.canvas);
+        let after = self.canvas.get_layer_data(layer);
+        if before != after {
+            self.undo_stack.push(UndoStep::Snapshot {
+                layer,
+                origin: (0, 0),
+                dimensions,
+                before,
+                after,
+            });
+        }
+    }
+
+    /// Reverts the latest undo step. Returns whether there was one.
+    pub fn apply_undo(&mut self) -> bool {
+        self.end_stroke();
+        match self.undo_stack.undo() {
+            Some(step) => {
+                apply_step(&mut self.canvas, step, false);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Re-applies the latest undone step. Returns whether there was one.
+    pub fn apply_redo(&mut self) -> bool {
+        self.end_stroke();
+        match self.undo_stack.redo() {
+            Some(step) => {
+                apply_step(&mut self.canvas, step, true);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn handle_undo_shortcuts(&mut self, ctx: &egui::Context) {
+        let redo = egui::KeyboardShortcut::new(
+            egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+            egui::Key::Z,
+        );
+        let undo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+
+        // Check the more specific shortcut first, as Ctrl+Z also matches
+        // while Shift is held
+        if ctx.input_mut(|i| i.consume_shortcut(&redo)) {
+            self.apply_redo();
+        } else if ctx.input_mut(|i| i.consume_shortcut(&undo)) {
+            self.apply_undo();
+        }
+    }
+
+    fn handle_image_load(&mut self, opened: OpenedImage) {
+        self.current_stroke.clear();
+        self.undo_stack.clear();
+        self.canvas.load_image(&*opened.image);
+        if let Some(colors) = opened.palette {
+            self.palette = Palette::from_colors(colors);
+        }
+        self.document = DocumentSettings::from_metadata(&opened.metadata);
 
         // Center the view on the loaded image and adjust zoom
-        // self.center_view(view_size);
-        // self.zoom = self.calculate_zoom_to_fit(view_size);
+        if let Some(viewport) = self.viewport_info.get_viewport_rect() {
+            let zoom = self.calculate_zoom_to_fit(viewport.size());
+            self.viewport_info.set_zoom(zoom, &self.viewport_options);
+            self.center_view();
+        }
+    }
+
+    /// Replaces the document with a blank canvas of the given size
+    pub fn new_canvas(&mut self, width: u32, height: u32) {
+        let unbounded = self.canvas.is_unbounded();
+        self.canvas = Canvas::new(width, height);
+        self.canvas.set_unbounded(unbounded);
+        self.reset_history();
+    }
+
+    /// Resizes the canvas, cropping or padding it at the right and bottom.
+    /// Undo steps can't span a size change, so the history is cleared.
+    pub fn resize_canvas(&mut self, width: u32, height: u32) {
+        self.canvas.resize(width, height);
+        self.reset_history();
+    }
+
+    fn reset_history(&mut self) {
+        self.current_stroke.clear();
+        self.last_stroke_cell = None;
+        self.undo_stack.clear();
+        self.selection = None;
+        self.shape_drag = None;
+    }
+
+    fn show_size_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.size_dialog else {
+            return;
+        };
+        let kind = dialog.kind;
+        match dialog.show(ctx) {
+            SizeDialogResult::Open => {}
+            SizeDialogResult::Cancel => self.size_dialog = None,
+            SizeDialogResult::Confirm(width, height) => {
+                self.size_dialog = None;
+                match kind {
+                    SizeDialogKind::New => self.new_canvas(width, height),
+                    SizeDialogKind::Resize => self.resize_canvas(width, height),
+                }
+            }
+        }
+    }
+
+    /// Clears the active layer as one undoable step
+    pub fn clear_canvas(&mut self) {
+        self.with_snapshot(Canvas::clear);
+    }
+
+    fn show_clear_confirmation(&mut self, ctx: &egui::Context) {
+        if !self.confirm_clear {
+            return;
+        }
+
+        let mut confirmed = None;
+        egui::Window::new("Clear Canvas")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label("Clear every pixel of the active layer? Undo brings them back.");
+                ui.horizontal(|ui| {
+                    if ui.button("Clear").clicked() {
+                        confirmed = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        confirmed = Some(false);
+                    }
+                });
+            });
+
+        if let Some(confirmed) = confirmed {
+            self.confirm_clear = false;
+            if confirmed {
+                self.clear_canvas();
+            }
+        }
+    }
+
+    fn calculate_zoom_to_fit(&self, view_size: Vec2) -> f32 {
+        zoom_to_fit(
+            self.canvas.dimensions(),
+            view_size,
+            self.viewport_info.base_cell_size(),
+        )
+    }
+
+    fn center_view(&mut self) {
+        self.viewport_info.center();
     }
 
     fn draw_central_panel(&mut self, ctx: &egui::Context) {
@@ -94,10 +669,8 @@ impl PixelEditor {
 
             let canvas_dims = self.canvas.dimensions();
 
-            let image_size = Vec2::new(
-                canvas_dims.0 as f32 * self.viewport_info.zoom() * 20.0,
-                canvas_dims.1 as f32 * self.viewport_info.zoom() * 20.0,
-            );
+            let image_size = Vec2::new(canvas_dims.0 as f32, canvas_dims.1 as f32)
+                * self.viewport_info.cell_size();
             let image_rect = egui::Rect::from_center_size(
                 ui.max_rect().center() + self.viewport_info.pan_offset(),
                 image_size,
@@ -108,6 +681,10 @@ impl PixelEditor {
 
             let painter = ui.painter();
 
+            let space_down = !ctx.wants_keyboard_input() && input.key_down(egui::Key::Space);
+            self.viewport_info
+                .update_pointer_mode(space_down, input.pointer.primary_down());
+
             update_canvas_viewport(
                 &input,
                 &mut self.viewport_info,
@@ -115,10 +692,20 @@ impl PixelEditor {
                 &canvas_dims,
             );
 
+            // Checkerboard behind the canvas so transparent pixels read as empty
+            if self.viewport_options.draw_checkerboard {
+                painter.rect_filled(image_rect.intersect(visible_rect), 0.0, CHECKER_LIGHT);
+                for cell in
+                    checkerboard_cells(image_rect, visible_rect, self.viewport_info.cell_size())
+                {
+                    painter.rect_filled(cell, 0.0, CHECKER_DARK);
+                }
+            }
+
             let visible_chunks = self.get_visible_chunk_indexes(&visible_rect);
 
             // Update texture cache
-            let cached_chunks = self.canvas.update_cache(&visible_chunks, &ctx);
+            let cached_chunks = self.canvas.update_cache(&visible_chunks, ctx);
 
             // Draw the chunks as before, using the updated pan_offset
             for &chunk_pos in &visible_chunks {
@@ -153,10 +740,17 @@ impl PixelEditor {
                 let bottom_right =
                     screen_to_grid(&self.viewport_info, &canvas_dims, visible_rect.max);
 
-                for x in top_left.0..=bottom_right.0 {
-                    for y in top_left.1..=bottom_right.1 {
+                let spacing = self.document.grid_spacing.max(1);
+                let start = (
+                    top_left.0 / spacing * spacing,
+                    top_left.1 / spacing * spacing,
+                );
+
+                for x in (start.0..=bottom_right.0).step_by(spacing as usize) {
+                    for y in (start.1..=bottom_right.1).step_by(spacing as usize) {
                         let cell_min = grid_to_screen(&self.viewport_info, (x, y));
-                        let cell_max = grid_to_screen(&self.viewport_info, (x + 1, y + 1));
+                        let cell_max =
+                            grid_to_screen(&self.viewport_info, (x + spacing, y + spacing));
 
                         let cell_min = Pos2::new(cell_min.0, cell_min.1);
                         let cell_max = Pos2::new(cell_max.0, cell_max.1);
@@ -170,61 +764,538 @@ impl PixelEditor {
                 }
             }
 
+            // Outline tiles across the whole canvas
+            if let Some(tile_size) = self.document.tile_size.filter(|&size| size > 0) {
+                let stroke = egui::Stroke::new(1.0, TILE_COLOR);
+                for x in (0..=canvas_dims.0).step_by(tile_size as usize) {
+                    let (x, _) = grid_to_screen(&self.viewport_info, (x, 0));
+                    painter.line_segment(
+                        [
+                            Pos2::new(x, image_rect.top()),
+                            Pos2::new(x, image_rect.bottom()),
+                        ],
+                        stroke,
+                    );
+                }
+                for y in (0..=canvas_dims.1).step_by(tile_size as usize) {
+                    let (_, y) = grid_to_screen(&self.viewport_info, (0, y));
+                    painter.line_segment(
+                        [
+                            Pos2::new(image_rect.left(), y),
+                            Pos2::new(image_rect.right(), y),
+                        ],
+                        stroke,
+                    );
+                }
+            }
+
+            // Draw guides across the whole canvas
+            for guide in &self.guides {
+                let (x, y) = grid_to_screen(&self.viewport_info, (guide.position, guide.position));
+                let (from, to) = match guide.orientation {
+                    GuideOrientation::Horizontal => (
+                        Pos2::new(image_rect.left(), y),
+                        Pos2::new(image_rect.right(), y),
+                    ),
+                    GuideOrientation::Vertical => (
+                        Pos2::new(x, image_rect.top()),
+                        Pos2::new(x, image_rect.bottom()),
+                    ),
+                };
+                painter.line_segment([from, to], egui::Stroke::new(1.5, GUIDE_COLOR));
+            }
+
+            if self.viewport_info.pointer_mode() == PointerMode::Pan {
+                self.end_stroke();
+                self.shape_drag = None;
+                return;
+            }
+
+            if self.handle_guide_drag(&input, image_rect) {
+                return;
+            }
+
+            self.hovered_cell = input
+                .pointer
+                .hover_pos()
+                .filter(|&pos| image_rect.contains(pos))
+                .map(|pos| screen_to_grid(&self.viewport_info, &canvas_dims, pos));
+
+            if let Some(selection) = self.selection {
+                let min = grid_to_screen(&self.viewport_info, selection.min);
+                let max = grid_to_screen(
+                    &self.viewport_info,
+                    (selection.max.0 + 1, selection.max.1 + 1),
+                );
+                painter.rect_stroke(
+                    Rect::from_min_max(Pos2::new(min.0, min.1), Pos2::new(max.0, max.1)),
+                    0.0,
+                    egui::Stroke::new(1.5, SELECTION_COLOR),
+                );
+            }
+
+            if self.tool.is_shape() || self.tool == Tool::Select {
+                self.handle_shape_drag(&input, response.hovered(), painter);
+                return;
+            }
+
             // Handle drawing on the canvas
-            if response.clicked() || (response.dragged() && input.pointer.primary_down()) {
+            let pressed = match self.tool {
+                Tool::Pencil | Tool::Eraser => {
+                    response.clicked() || (response.dragged() && input.pointer.primary_down())
+                }
+                Tool::Fill | Tool::Eyedropper => response.clicked(),
+                Tool::Line | Tool::Rectangle | Tool::Ellipse | Tool::Select => false,
+            };
+            if pressed {
                 if let Some(pos) = input.pointer.hover_pos() {
-                    let grid_pos = screen_to_grid(&self.viewport_info, &canvas_dims, pos);
-                    let _ = self.canvas.set_pixel(
-                        grid_pos.0,
-                        grid_pos.1,
-                        self.palette.get_current_color(),
-                    );
+                    let mut grid_pos = if self.canvas.is_unbounded() {
+                        screen_to_grid_unclamped(&self.viewport_info, pos)
+                    } else {
+                        screen_to_grid(&self.viewport_info, &canvas_dims, pos)
+                    };
+                    if self.snap_to_guides {
+                        grid_pos = snap_to_guides(&self.guides, grid_pos, GUIDE_SNAP_DISTANCE);
+                    }
+                    self.use_tool(grid_pos);
                 }
             }
+            if !input.pointer.primary_down() {
+                self.end_stroke();
+            }
         });
     }
 
+    /// Tracks a shape tool drag, previewing the shape until the primary
+    /// button is released and then drawing it onto the canvas
+    fn handle_shape_drag(
+        &mut self,
+        input: &egui::InputState,
+        hovered: bool,
+        painter: &egui::Painter,
+    ) {
+        if let Some(pos) = input.pointer.hover_pos() {
+            let mut grid_pos = screen_to_grid_signed(&self.viewport_info, pos);
+            if self.snap_to_guides {
+                if let (Ok(x), Ok(y)) = (grid_pos.0.try_into(), grid_pos.1.try_into()) {
+                    let (x, y) = snap_to_guides(&self.guides, (x, y), GUIDE_SNAP_DISTANCE);
+                    grid_pos = (x as i32, y as i32);
+                }
+            }
+
+            if input.pointer.primary_pressed() && hovered {
+                self.shape_drag = Some((grid_pos, grid_pos));
+            } else if let Some((_, end)) = &mut self.shape_drag {
+                *end = grid_pos;
+            }
+        }
+
+        let Some((start, end)) = self.shape_drag else {
+            return;
+        };
+        if self.tool == Tool::Select {
+            self.selection = Selection::from_corners(start, end, self.canvas.dimensions());
+            if !input.pointer.primary_down() {
+                self.shape_drag = None;
+            }
+            return;
+        }
+        let points = self.tool.shape(start, end).unwrap_or_default();
+
+        if input.pointer.primary_down() {
+            let cell_size = self.viewport_info.cell_size();
+            let origin = self.viewport_info.get_parent_rect().unwrap().min;
+            let color = self.palette.get_current_color();
+            for (x, y) in points {
+                let min = origin + Vec2::new(x as f32, y as f32) * cell_size;
+                painter.rect_filled(Rect::from_min_size(min, Vec2::splat(cell_size)), 0.0, color);
+            }
+        } else {
+            self.draw_shape(&points);
+            self.shape_drag = None;
+        }
+    }
+
+    /// Paints the cells of a shape as one undo step, skipping any that fall
+    /// outside the canvas
+    fn draw_shape(&mut self, points: &[(i32, i32)]) {
+        let color = self.palette.get_current_color();
+        for &(x, y) in points {
+            if let (Ok(x), Ok(y)) = (x.try_into(), y.try_into()) {
+                self.paint_pixel(x, y, color);
+            }
+        }
+        self.end_stroke();
+    }
+
+    /// Copies the selected pixels of the active layer into the clipboard.
+    /// Returns whether there was a selection.
+    pub fn copy_selection(&mut self) -> bool {
+        let Some(selection) = self.selection else {
+            return false;
+        };
+        self.clipboard = selection
+            .cells()
+            .map(|(x, y)| {
+                let color = self
+                    .canvas
+                    .get_layer_pixel(self.canvas.active_layer(), x, y)
+                    .unwrap_or(Color32::TRANSPARENT);
+                ((x - selection.min.0, y - selection.min.1), color)
+            })
+            .collect();
+        true
+    }
+
+    /// Copies the selected pixels and clears them as one undo step
+    pub fn cut_selection(&mut self) -> bool {
+        if !self.copy_selection() {
+            return false;
+        }
+        if let Some(selection) = self.selection {
+            self.end_stroke();
+            for (x, y) in selection.cells() {
+                self.paint_pixel(x, y, Color32::TRANSPARENT);
+            }
+            self.end_stroke();
+        }
+        true
+    }
+
+    /// Stamps the clipboard with its top-left corner at `origin` as one undo
+    /// step. Pixels that would land outside the canvas are dropped.
+    pub fn paste_at(&mut self, origin: (u32, u32)) {
+        self.end_stroke();
+        for ((dx, dy), color) in self.clipboard.clone() {
+            self.paint_pixel(origin.0 + dx, origin.1 + dy, color);
+        }
+        self.end_stroke();
+    }
+
+    fn handle_clipboard_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+
+        let events = ctx.input(|i| i.events.clone());
+        for event in events {
+            let copied = match event {
+                egui::Event::Copy => self.copy_selection(),
+                egui::Event::Cut => self.cut_selection(),
+                egui::Event::Paste(_) => {
+                    if let Some(cell) = self.hovered_cell {
+                        self.paste_at(cell);
+                    }
+                    false
+                }
+                _ => false,
+            };
+            // egui only reports Ctrl+V while the system clipboard holds text
+            if copied {
+                if let Some(selection) = self.selection {
+                    ctx.copy_text(format!(
+                        "{}x{} pixels",
+                        selection.width(),
+                        selection.height()
+                    ));
+                }
+            }
+        }
+    }
+
+    /// Moves a guide grabbed with the primary button, removing it when it is
+    /// dropped outside the canvas. Returns whether a guide is being dragged.
+    fn handle_guide_drag(&mut self, input: &egui::InputState, image_rect: Rect) -> bool {
+        let Some(pos) = input.pointer.hover_pos() else {
+            return self.dragged_guide.is_some();
+        };
+
+        if input.pointer.primary_pressed() {
+            self.dragged_guide = self.guides.iter().position(|guide| {
+                let (x, y) = grid_to_screen(&self.viewport_info, (guide.position, guide.position));
+                match guide.orientation {
+                    GuideOrientation::Horizontal => (pos.y - y).abs() <= GUIDE_GRAB_DISTANCE,
+                    GuideOrientation::Vertical => (pos.x - x).abs() <= GUIDE_GRAB_DISTANCE,
+                }
+            });
+        }
+
+        let Some(index) = self.dragged_guide else {
+            return false;
+        };
+
+        if input.pointer.primary_down() {
+            let cell_size = self.viewport_info.cell_size();
+            let offset = (pos - image_rect.min) / cell_size;
+            let guide = &mut self.guides[index];
+            guide.position = match guide.orientation {
+                GuideOrientation::Horizontal => offset.y.round().max(0.0) as u32,
+                GuideOrientation::Vertical => offset.x.round().max(0.0) as u32,
+            };
+        } else {
+            if !image_rect.contains(pos) {
+                self.guides.remove(index);
+            }
+            self.dragged_guide = None;
+        }
+        true
+    }
+
+    fn apply_swatch_action(&mut self, action: SwatchAction) {
+        match action {
+            SwatchAction::Duplicate(index) => {
+                self.palette.duplicate_color(index);
+            }
+            SwatchAction::Delete(index) => {
+                self.palette.delete_color(index);
+            }
+            SwatchAction::SetTransparent(index) => {
+                self.palette.set_color(index, Color32::TRANSPARENT);
+            }
+            SwatchAction::ReplacePixels(index) => {
+                if let Some(color) = self.palette.get_color(index) {
+                    let current = self.palette.get_current_color();
+                    self.with_snapshot(|canvas| {
+                        canvas.replace_color(color, current);
+                    });
+                }
+            }
+        }
+    }
+
+    fn apply_layer_action(&mut self, action: LayerAction) {
+        self.end_stroke();
+        match action {
+            LayerAction::Add => {
+                let name = format!("Layer {}", self.canvas.layers().len() + 1);
+                self.canvas.add_layer(name);
+            }
+            LayerAction::Remove(index) => {
+                // Undo steps refer to layers by index
+                if self.canvas.remove_layer(index) {
+                    self.undo_stack.clear();
+                }
+            }
+            LayerAction::Select(index) => self.canvas.set_active_layer(index),
+            LayerAction::SetVisible(index, visible) => {
+                self.canvas.set_layer_visible(index, visible);
+            }
+            LayerAction::SetOpacity(index, opacity) => {
+                self.canvas.set_layer_opacity(index, opacity);
+            }
+            LayerAction::Move { from, to } => {
+                self.canvas.move_layer(from, to);
+                self.undo_stack.clear();
+            }
+        }
+    }
+
+    fn draw_layers(&mut self, ui: &mut egui::Ui) {
+        let mut layer_action = None;
+        let layer_count = self.canvas.layers().len();
+        let active = self.canvas.active_layer();
+
+        // Listed top to bottom, like the stack is drawn
+        for (index, layer) in self.canvas.layers().iter().enumerate().rev() {
+            ui.horizontal(|ui| {
+                let mut visible = layer.visible;
+                if ui.checkbox(&mut visible, "").changed() {
+                    layer_action = Some(LayerAction::SetVisible(index, visible));
+                }
+                if ui.selectable_label(index == active, &layer.name).clicked() {
+                    layer_action = Some(LayerAction::Select(index));
+                }
+                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui
+                        .add_enabled(index > 0, egui::Button::new("⬇").small())
+                        .clicked()
+                    {
+                        layer_action = Some(LayerAction::Move {
+                            from: index,
+                            to: index - 1,
+                        });
+                    }
+                    if ui
+                        .add_enabled(index + 1 < layer_count, egui::Button::new("⬆").small())
+                        .clicked()
+                    {
+                        layer_action = Some(LayerAction::Move {
+                            from: index,
+                            to: index + 1,
+                        });
+                    }
+                });
+            });
+        }
+
+        let mut opacity = self.canvas.layers()[active].opacity;
+        if ui
+            .add(egui::Slider::new(&mut opacity, 0.0..=1.0).text("Opacity"))
+            .changed()
+        {
+            layer_action = Some(LayerAction::SetOpacity(active, opacity));
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Add Layer").clicked() {
+                layer_action = Some(LayerAction::Add);
+            }
+            if ui
+                .add_enabled(layer_count > 1, egui::Button::new("Remove Layer"))
+                .clicked()
+            {
+                layer_action = Some(LayerAction::Remove(active));
+            }
+        });
+
+        if let Some(action) = layer_action {
+            self.apply_layer_action(action);
+        }
+    }
+
+    /// Shows the whole canvas with the visible region outlined. Clicking or
+    /// dragging on it centers the view there.
+    fn draw_minimap(&mut self, ui: &mut egui::Ui) {
+        let canvas_dims = self.canvas.dimensions();
+        let size = minimap::minimap_size(canvas_dims, MINIMAP_SIZE);
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::click_and_drag());
+        let rect = response.rect;
+
+        let texture = self.minimap.texture(&self.canvas, ui.ctx(), self.time);
+        painter.image(
+            texture.id(),
+            rect,
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0)),
+            Color32::WHITE,
+        );
+        painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, Color32::GRAY));
+
+        if let Some(viewport) = self.viewport_info.get_viewport_rect() {
+            let visible = minimap::viewport_rect(
+                canvas_dims,
+                self.viewport_info.cell_size(),
+                self.viewport_info.pan_offset(),
+                viewport.size(),
+                rect,
+            );
+            painter.rect_stroke(visible, 0.0, egui::Stroke::new(1.5, Color32::YELLOW));
+        }
+
+        if let Some(pos) = response.interact_pointer_pos() {
+            let pan_offset =
+                minimap::pan_offset_for(canvas_dims, self.viewport_info.cell_size(), rect, pos);
+            self.viewport_info.set_pan_offset(pan_offset);
+        }
+    }
+
     fn draw_toolbar(&mut self, ctx: &egui::Context) {
         egui::TopBottomPanel::top("toolbar").show(ctx, |ui| {
             ui.horizontal(|ui| {
+                if ui.button("New").clicked() {
+                    let size = self.canvas.dimensions();
+                    self.size_dialog = Some(SizeDialog::new(SizeDialogKind::New, size));
+                }
+
                 // Load image button
                 if ui.button("📂 Load Image").clicked() {
-                    let image = filemanager::open_image()?;
-                    self.handle_image_load(&*image?);
+                    self.open_image();
+                }
+
+                let mut reopen = None;
+                ui.add_enabled_ui(!self.recent_files.is_empty(), |ui| {
+                    ui.menu_button("Recent", |ui| {
+                        for path in self.recent_files.existing() {
+                            let name = path.file_name().unwrap_or(path.as_os_str());
+                            if ui
+                                .button(name.to_string_lossy())
+                                .on_hover_text(path.display().to_string())
+                                .clicked()
+                            {
+                                reopen = Some(path.to_path_buf());
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+                if let Some(path) = reopen {
+                    self.open_image_at(&path);
                 }
 
                 if ui.button("Save Image").clicked() {
-                    let (width, height) = self.canvas.dimensions();
-                    let rgba_data = self.canvas.get_data();
-                    filemanager::save_image((width, height), rgba_data);
+                    self.save_image();
                 }
 
-                // ui.separator();
+                if ui.button("Resize Canvas").clicked() {
+                    let size = self.canvas.dimensions();
+                    self.size_dialog = Some(SizeDialog::new(SizeDialogKind::Resize, size));
+                }
 
-                // // Zoom controls
-                // if ui.button("🔍 Fit to View").clicked() {
-                //     self.zoom = self.calculate_zoom_to_fit(view_size);
-                //     self.center_view(view_size);
-                // }
+                ui.separator();
 
-                // if ui.button("⚖️ 100%").clicked() {
-                //     self.zoom = 1.0;
-                //     self.center_view(view_size);
-                // }
+                if ui.button("🎨 Import Palette").clicked() {
+                    self.import_palette();
+                }
+                if ui.button("Export Palette").clicked() {
+                    self.export_palette();
+                }
+
+                ui.separator();
+
+                // Zoom controls
+                if ui.button("🔍 Fit to View").clicked() {
+                    if let Some(viewport) = self.viewport_info.get_viewport_rect() {
+                        let zoom = self.calculate_zoom_to_fit(viewport.size());
+                        self.viewport_info.set_zoom(zoom, &self.viewport_options);
+                        self.center_view();
+                    }
+                }
+
+                if ui.button("⚖️ 100%").clicked() {
+                    self.viewport_info.set_zoom(1.0, &self.viewport_options);
+                    self.center_view();
+                }
 
                 // Zoom percentage display and manual input
-                // let mut zoom_text = format!("{:.0}%", self.zoom * 100.0);
-                // if ui.text_edit_singleline(&mut zoom_text).lost_focus() {
-                //     if let Ok(percentage) = zoom_text.trim().trim_end_matches('%').parse::<f32>() {
-                //         self.zoom = (percentage / 100.0).clamp(0.1, 10.0);
-                //         self.center_view(view_size);
-                //     }
-                // }
+                let mut percentage = self.viewport_info.zoom() * 100.0;
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut percentage)
+                            .range(
+                                self.viewport_options.min_zoom * 100.0
+                                    ..=self.viewport_options.max_zoom * 100.0,
+                            )
+                            .suffix("%")
+                            .max_decimals(0),
+                    )
+                    .changed()
+                {
+                    self.viewport_info
+                        .set_zoom(percentage / 100.0, &self.viewport_options);
+                }
+
+                ui.separator();
+
+                for tool in Tool::ALL {
+                    ui.selectable_value(&mut self.tool, tool, tool.label());
+                }
+
+                ui.add(
+                    egui::Slider::new(&mut self.brush_size, 1..=MAX_BRUSH_SIZE).text("Brush size"),
+                );
 
                 ui.separator();
 
                 // Grid toggle
                 ui.checkbox(&mut self.viewport_options.draw_grid, "🔲 Show Grid");
+                ui.checkbox(
+                    &mut self.viewport_options.draw_checkerboard,
+                    "🏁 Show Transparency",
+                );
+
+                let mut unbounded = self.canvas.is_unbounded();
+                if ui.checkbox(&mut unbounded, "∞ Infinite Canvas").changed() {
+                    self.canvas.set_unbounded(unbounded);
+                }
             });
         });
     }
@@ -264,6 +1335,7 @@ impl PixelEditor {
                 let color_size = Vec2::new(30.0, 30.0);
                 let colors_total = self.palette.get_palette_length();
                 let current_index = self.palette.get_current_color_index();
+                let mut swatch_action = None;
 
                 for row in 0..(colors_total / 4) + 1 {
                     ui.horizontal(|ui| {
@@ -292,46 +1364,624 @@ impl PixelEditor {
 
                             let color = self.palette.get_color(index).unwrap();
 
-                            if ui
-                                .add(
-                                    egui::Button::new("")
-                                        .fill(color)
-                                        .stroke(stroke)
-                                        .min_size(color_size),
-                                )
-                                .clicked()
-                            {
+                            let response = ui.add(
+                                egui::Button::new("")
+                                    .fill(color)
+                                    .stroke(stroke)
+                                    .min_size(color_size),
+                            );
+                            if response.clicked() {
                                 self.palette.switch_color(index);
                             }
+
+                            response.context_menu(|ui| {
+                                ui.horizontal(|ui| {
+                                    let mut edited = color;
+                                    if ui.color_edit_button_srgba(&mut edited).changed() {
+                                        self.palette.set_color(index, edited);
+                                    }
+                                    ui.label("Edit");
+                                });
+                                if ui.button("Duplicate").clicked() {
+                                    swatch_action = Some(SwatchAction::Duplicate(index));
+                                }
+                                if ui.button("Delete this color").clicked() {
+                                    swatch_action = Some(SwatchAction::Delete(index));
+                                }
+                                if ui.button("Set as transparent").clicked() {
+                                    swatch_action = Some(SwatchAction::SetTransparent(index));
+                                }
+                                if ui.button("Replace all pixels with current").clicked() {
+                                    swatch_action = Some(SwatchAction::ReplacePixels(index));
+                                }
+                                if swatch_action.is_some() {
+                                    ui.close_menu();
+                                }
+                            });
                         }
                     });
                 }
 
+                if let Some(action) = swatch_action {
+                    self.apply_swatch_action(action);
+                }
+
                 ui.add_space(8.0);
 
-                if ui.button("Remove").clicked() {
-                    self.palette.remove_color()
-                }
+                ui.horizontal(|ui| {
+                    if ui.button("Remove").clicked() {
+                        self.palette.remove_color()
+                    }
+                    if ui
+                        .button("Use Image Palette")
+                        .on_hover_text("Replace the palette with the colors on the canvas")
+                        .clicked()
+                    {
+                        self.use_image_palette();
+                    }
+                });
 
                 ui.add_space(8.0);
-                if ui.button("Clear Canvas").clicked() {
-                    self.canvas.clear();
-                }
+                ui.horizontal(|ui| {
+                    if ui.button("Clear Canvas").clicked() {
+                        self.confirm_clear = true;
+                    }
+                    if ui.button("Fill with Color").clicked() {
+                        let color = self.palette.get_current_color();
+                        self.with_snapshot(|canvas| canvas.clear_to(color));
+                    }
+                });
+
+                ui.add_space(8.0);
+                egui::CollapsingHeader::new("Layers")
+                    .default_open(true)
+                    .show(ui, |ui| self.draw_layers(ui));
+
+                ui.add_space(8.0);
+                ui.collapsing("Overview", |ui| self.draw_minimap(ui));
+
+                ui.add_space(8.0);
+                ui.collapsing("Document", |ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.document.grid_spacing)
+                            .range(1..=64)
+                            .prefix("Grid spacing: "),
+                    );
+
+                    let mut show_tiles = self.document.tile_size.is_some();
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut show_tiles, "Tiles");
+                        if show_tiles {
+                            let tile_size = self.document.tile_size.get_or_insert(16);
+                            ui.add(egui::DragValue::new(tile_size).range(1..=256));
+                        }
+                    });
+                    if !show_tiles {
+                        self.document.tile_size = None;
+                    }
+                    ui.label("Saved with the .pxc file.");
+                });
+
+                ui.add_space(8.0);
+                ui.collapsing("Guides", |ui| {
+                    let (width, height) = self.canvas.dimensions();
+                    ui.horizontal(|ui| {
+                        if ui.button("Add Horizontal").clicked() {
+                            self.guides.push(Guide::horizontal(height / 2));
+                        }
+                        if ui.button("Add Vertical").clicked() {
+                            self.guides.push(Guide::vertical(width / 2));
+                        }
+                    });
+                    ui.checkbox(&mut self.snap_to_guides, "Snap to guides");
+                    ui.label("Drag a guide off the canvas to remove it.");
+                });
+
+                ui.add_space(8.0);
+                ui.collapsing("View Settings", |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut self.viewport_options.zoom_step, 0.01..=0.5)
+                            .text("Zoom step"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut self.viewport_options.zoom_speed, 0.01..=1.0)
+                            .text("Zoom smoothing"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut self.viewport_options.min_zoom, MIN_ZOOM..=1.0)
+                            .logarithmic(true)
+                            .text("Minimum zoom"),
+                    );
+                    ui.add(
+                        egui::Slider::new(&mut self.viewport_options.max_zoom, 1.0..=MAX_ZOOM)
+                            .text("Maximum zoom"),
+                    );
+                    ui.checkbox(&mut self.viewport_options.invert_scroll, "Invert scroll");
+                });
 
                 // Instructions
                 ui.add_space(16.0);
                 ui.label("Controls:");
-                ui.label("• Left click to draw");
-                ui.label("• Middle click to pan");
+                ui.label("• Left click to use the selected tool");
+                ui.label("• Middle click or Space + drag to pan");
                 ui.label("• Ctrl + Scroll to zoom");
+                ui.label("• Ctrl + Z to undo, Ctrl + Shift + Z to redo");
+                ui.label("• Ctrl + C / X / V to copy, cut and paste a selection");
             });
     }
 }
 
+/// Writes the before (`redo == false`) or after state of an undo step back
+/// onto the canvas
+fn apply_step(canvas: &mut Canvas, step: &UndoStep, redo: bool) {
+    match step {
+        UndoStep::Pixels { layer, changes } => {
+            // Undo in reverse so pixels touched twice end at their first state
+            let mut apply = |change: &PixelChange| {
+                let color = if redo { change.after } else { change.before };
+                let _ = canvas.set_layer_pixel(*layer, change.x, change.y, color);
+            };
+            if redo {
+                changes.iter().for_each(&mut apply);
+            } else {
+                changes.iter().rev().for_each(&mut apply);
+            }
+        }
+        UndoStep::Snapshot {
+            layer,
+            origin,
+            dimensions,
+            before,
+            after,
+        } => {
+            let data = if redo { after } else { before };
+            let (width, height) = *dimensions;
+            for (i, pixel) in data.chunks_exact(4).enumerate() {
+                let x = origin.0 + i as u32 % width;
+                let y = origin.1 + i as u32 / width;
+                if y >= origin.1 + height {
+                    break;
+                }
+                let color = Color32::from_rgba_unmultiplied(pixel[0], pixel[1], pixel[2], pixel[3]);
+                let _ = canvas.set_layer_pixel(*layer, x, y, color);
+            }
+        }
+    }
+}
+
 impl eframe::App for PixelEditor {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut Frame) {
+        self.time = ctx.input(|i| i.time);
+        self.handle_undo_shortcuts(ctx);
+        self.handle_clipboard_shortcuts(ctx);
+        let dropped_files = ctx.input(|i| i.raw.dropped_files.clone());
+        self.open_dropped_files(&dropped_files);
+        if !ctx.wants_keyboard_input() && ctx.input(|i| i.key_pressed(egui::Key::I)) {
+            self.toggle_eyedropper();
+        }
+
         self.draw_central_panel(ctx);
         self.draw_side_panel(ctx);
         self.draw_toolbar(ctx);
+        self.show_size_dialog(ctx);
+        self.show_clear_confirmation(ctx);
+        self.toasts.show(ctx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::CELL_SIZE;
+
+    #[test]
+    fn test_builder_defaults() {
+        let editor = PixelEditorBuilder::new().build().unwrap();
+        assert_eq!(editor.canvas().dimensions(), DEFAULT_CANVAS_SIZE);
+        assert_eq!(editor.palette().get_palette_length(), 1);
+    }
+
+    #[test]
+    fn test_builder_dimensions_and_palette() {
+        let palette = Palette::from_colors(vec![Color32::RED, Color32::BLUE]);
+        let editor = PixelEditorBuilder::new()
+            .dimensions(128, 64)
+            .palette(palette)
+            .build()
+            .unwrap();
+
+        assert_eq!(editor.canvas().dimensions(), (128, 64));
+        assert_eq!(editor.palette().get_palette_length(), 2);
+        assert_eq!(editor.palette().get_current_color(), Color32::RED);
+    }
+
+    #[test]
+    fn test_builder_missing_file() {
+        let result = PixelEditorBuilder::new().file("does_not_exist.png").build();
+        assert!(matches!(result, Err(ImageHandlingError::ImageError(_))));
+    }
+
+    #[test]
+    fn test_clear_canvas_is_undoable() {
+        let mut editor = PixelEditorBuilder::new().dimensions(4, 4).build().unwrap();
+        editor.canvas.set_pixel(2, 3, Color32::RED).unwrap();
+
+        editor.clear_canvas();
+        assert!(editor.canvas().is_empty());
+
+        assert!(editor.apply_undo());
+        assert_eq!(editor.canvas().get_pixel(2, 3).unwrap(), Color32::RED);
+    }
+
+    #[test]
+    fn test_failed_save_enqueues_toast() {
+        let mut editor = PixelEditor::new();
+        editor.save_image_to(Path::new("missing_directory/image.pxc"));
+
+        let toasts: Vec<&str> = editor.toasts().iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(toasts.len(), 1);
+        assert_eq!(toasts[0], "Save failed: The file or folder does not exist");
+        assert!(editor.toasts().iter().all(|t| t.level == ToastLevel::Error));
+    }
+
+    #[test]
+    fn test_import_palette_caps_colors() {
+        let path =
+            std::env::temp_dir().join(format!("pxc-editor-import-{}.json", std::process::id()));
+        let colors: Vec<Color32> = (0..300).map(|i| Color32::from_gray(i as u8)).collect();
+        palette_file::save_palette(&path, &colors).unwrap();
+
+        let mut editor = PixelEditor::new();
+        editor.import_palette_from(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(editor.palette().get_palette_length(), 256);
+        assert_eq!(editor.palette().colors(), &colors[..256]);
+
+        editor.import_palette_from(Path::new("missing_directory/palette.gpl"));
+        let toasts: Vec<&str> = editor.toasts().iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(toasts.len(), 1);
+        assert!(toasts[0].starts_with("Palette import failed"));
+        assert_eq!(editor.palette().get_palette_length(), 256);
+    }
+
+    #[test]
+    fn test_use_image_palette() {
+        let mut editor = PixelEditorBuilder::new().dimensions(4, 4).build().unwrap();
+        editor.canvas.set_pixel(0, 0, Color32::RED).unwrap();
+        editor.canvas.set_pixel(1, 0, Color32::BLUE).unwrap();
+
+        editor.use_image_palette();
+        assert_eq!(
+            editor.palette().colors(),
+            &[Color32::RED, Color32::BLUE, Color32::TRANSPARENT]
+        );
+
+        // Over 256 colors, the most used ones are kept
+        let mut editor = PixelEditorBuilder::new()
+            .dimensions(20, 20)
+            .build()
+            .unwrap();
+        for i in 0..400 {
+            editor
+                .canvas
+                .set_pixel(
+                    i % 20,
+                    i / 20,
+                    Color32::from_rgb(i as u8, (i >> 8) as u8, 0),
+                )
+                .unwrap();
+        }
+        editor.canvas.set_pixel(19, 19, Color32::GREEN).unwrap();
+        editor.canvas.set_pixel(18, 19, Color32::GREEN).unwrap();
+        editor.use_image_palette();
+        assert_eq!(editor.palette().get_palette_length(), 256);
+        assert_eq!(editor.palette().get_color(0), Some(Color32::GREEN));
+        assert_eq!(editor.toasts().iter().count(), 1);
+    }
+
+    #[test]
+    fn test_save_empty_unbounded_canvas() {
+        // An empty unbounded canvas has no content bounds to export
+        let mut editor = PixelEditor::new();
+        editor.canvas.set_unbounded(true);
+        assert_eq!(editor.canvas().content_bounds(), None);
+        assert_eq!(editor.canvas().get_export_data(), ((0, 0), Vec::new()));
+
+        // A .pxc file holds the empty image, PNG has no empty images
+        let dir = std::env::temp_dir();
+        let pxc = dir.join(format!("pxc-editor-empty-{}.pxc", std::process::id()));
+        editor.save_image_to(&pxc);
+        let saved = std::fs::read(&pxc);
+        std::fs::remove_file(&pxc).unwrap();
+        let decoded = lib_pxc::decode(&saved.unwrap()).unwrap();
+        assert_eq!((decoded.width, decoded.height), (0, 0));
+
+        let png = dir.join(format!("pxc-editor-empty-{}.png", std::process::id()));
+        editor.save_image_to(&png);
+        assert!(!png.exists());
+        let toasts: Vec<&str> = editor.toasts().iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(toasts.len(), 2);
+        assert!(toasts[1].starts_with("Save failed"));
+    }
+
+    #[test]
+    fn test_undo_redo_stroke() {
+        let mut editor = PixelEditorBuilder::new().dimensions(8, 8).build().unwrap();
+
+        // One drag across three pixels, then a separate click
+        for x in 0..3 {
+            editor.paint_pixel(x, 0, Color32::RED);
+        }
+        editor.end_stroke();
+        editor.paint_pixel(0, 0, Color32::BLUE);
+        editor.end_stroke();
+
+        assert!(editor.apply_undo());
+        assert_eq!(editor.canvas().get_pixel(0, 0).unwrap(), Color32::RED);
+
+        // The whole drag is undone at once
+        assert!(editor.apply_undo());
+        for x in 0..3 {
+            assert_eq!(
+                editor.canvas().get_pixel(x, 0).unwrap(),
+                Color32::TRANSPARENT
+            );
+        }
+        assert!(!editor.apply_undo());
+
+        assert!(editor.apply_redo());
+        assert!(editor.apply_redo());
+        assert_eq!(editor.canvas().get_pixel(0, 0).unwrap(), Color32::BLUE);
+        assert_eq!(editor.canvas().get_pixel(2, 0).unwrap(), Color32::RED);
+        assert!(!editor.apply_redo());
+    }
+
+    #[test]
+    fn test_undo_clear_canvas() {
+        let mut editor = PixelEditorBuilder::new().dimensions(4, 4).build().unwrap();
+        editor.paint_pixel(1, 2, Color32::GREEN);
+        editor.with_snapshot(Canvas::clear);
+        assert!(editor.canvas().is_empty());
+
+        assert!(editor.apply_undo());
+        assert_eq!(editor.canvas().get_pixel(1, 2).unwrap(), Color32::GREEN);
+
+        assert!(editor.apply_redo());
+        assert!(editor.canvas().is_empty());
+    }
+
+    #[test]
+    fn test_eyedropper_samples_pixel() {
+        let mut editor = PixelEditorBuilder::new().dimensions(4, 4).build().unwrap();
+        editor.canvas.set_pixel(2, 1, Color32::GOLD).unwrap();
+
+        editor.toggle_eyedropper();
+        assert_eq!(editor.tool(), Tool::Eyedropper);
+
+        // Transparent and out of bounds pixels leave the color alone
+        assert!(!editor.sample_color(0, 0));
+        assert!(!editor.sample_color(9, 9));
+        assert_eq!(editor.palette().get_current_color(), Color32::WHITE);
+
+        editor.use_tool((2, 1));
+        assert_eq!(editor.palette().get_current_color(), Color32::GOLD);
+
+        editor.toggle_eyedropper();
+        assert_eq!(editor.tool(), Tool::Pencil);
+    }
+
+    #[test]
+    fn test_draw_shape_skips_off_canvas_cells() {
+        let mut editor = PixelEditorBuilder::new().dimensions(4, 4).build().unwrap();
+
+        let line = Tool::Line.shape((-2, 1), (5, 1)).unwrap();
+        editor.draw_shape(&line);
+        for x in 0..4 {
+            assert_eq!(editor.canvas().get_pixel(x, 1).unwrap(), Color32::WHITE);
+        }
+        assert_eq!(
+            editor.canvas().get_pixel(0, 0).unwrap(),
+            Color32::TRANSPARENT
+        );
+
+        // The whole shape is a single undo step
+        assert!(editor.apply_undo());
+        assert!(editor.canvas().is_empty());
+    }
+
+    #[test]
+    fn test_brush_size_stamps_square() {
+        let mut editor = PixelEditorBuilder::new().dimensions(8, 8).build().unwrap();
+        editor.brush_size = 3;
+        editor.palette.set_current_color(Color32::RED);
+
+        editor.use_tool((4, 4));
+        editor.end_stroke();
+        for y in 0..8 {
+            for x in 0..8 {
+                let expected = if (3..=5).contains(&x) && (3..=5).contains(&y) {
+                    Color32::RED
+                } else {
+                    Color32::TRANSPARENT
+                };
+                assert_eq!(editor.canvas().get_pixel(x, y).unwrap(), expected);
+            }
+        }
+
+        // Stamps at the edges are clipped to the canvas
+        editor.use_tool((0, 7));
+        editor.end_stroke();
+        assert_eq!(editor.canvas().get_pixel(0, 6).unwrap(), Color32::RED);
+        assert_eq!(editor.canvas().get_pixel(1, 7).unwrap(), Color32::RED);
+
+        // The whole stamp is one undo step
+        assert!(editor.apply_undo());
+        assert_eq!(
+            editor.canvas().get_pixel(0, 6).unwrap(),
+            Color32::TRANSPARENT
+        );
+        assert_eq!(editor.canvas().get_pixel(4, 4).unwrap(), Color32::RED);
+    }
+
+    #[test]
+    fn test_fast_drag_is_interpolated() {
+        let mut editor = PixelEditorBuilder::new().dimensions(8, 8).build().unwrap();
+        editor.palette.set_current_color(Color32::RED);
+
+        editor.use_tool((0, 0));
+        editor.use_tool((5, 3));
+        let painted: Vec<_> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&(x, y)| editor.canvas().get_pixel(x, y).unwrap() == Color32::RED)
+            .collect();
+        assert_eq!(
+            painted,
+            vec![(0, 0), (1, 1), (2, 1), (3, 2), (4, 2), (5, 3)]
+        );
+
+        // A new stroke doesn't join up with the previous one
+        editor.end_stroke();
+        editor.use_tool((7, 7));
+        assert_eq!(
+            editor.canvas().get_pixel(6, 5).unwrap(),
+            Color32::TRANSPARENT
+        );
+        assert_eq!(editor.canvas().get_pixel(7, 7).unwrap(), Color32::RED);
+    }
+
+    #[test]
+    fn test_copy_paste_selection() {
+        let mut editor = PixelEditorBuilder::new().dimensions(8, 8).build().unwrap();
+        let color_at = |x: u32, y: u32| Color32::from_rgb(x as u8 * 40, y as u8 * 40, 200);
+        for y in 1..4 {
+            for x in 1..4 {
+                editor.canvas.set_pixel(x, y, color_at(x, y)).unwrap();
+            }
+        }
+
+        editor.selection = Selection::from_corners((1, 1), (3, 3), (8, 8));
+        assert!(editor.copy_selection());
+        editor.paste_at((4, 5));
+
+        for y in 0..3 {
+            for x in 0..3 {
+                assert_eq!(
+                    editor.canvas().get_pixel(4 + x, 5 + y).unwrap(),
+                    color_at(1 + x, 1 + y)
+                );
+            }
+        }
+        // Cells that fall off the canvas are dropped
+        editor.paste_at((6, 6));
+        assert_eq!(editor.canvas().get_pixel(7, 7).unwrap(), color_at(2, 2));
+
+        assert!(editor.cut_selection());
+        assert_eq!(
+            editor.canvas().get_pixel(2, 2).unwrap(),
+            Color32::TRANSPARENT
+        );
+        assert!(editor.apply_undo());
+        assert_eq!(editor.canvas().get_pixel(2, 2).unwrap(), color_at(2, 2));
+    }
+
+    #[test]
+    fn test_open_dropped_files_loads_first() {
+        let path =
+            std::env::temp_dir().join(format!("pxc-editor-dropped-{}.png", std::process::id()));
+        let data = [255, 0, 0, 255].repeat(6);
+        filemanager::save_image(&path, (3, 2), data, &[]).unwrap();
+
+        let dropped = |path: &str| egui::DroppedFile {
+            path: Some(PathBuf::from(path)),
+            ..Default::default()
+        };
+        let mut editor = PixelEditor::new();
+        editor.open_dropped_files(&[
+            dropped(path.to_str().unwrap()),
+            dropped("second.png"),
+            dropped("third.pxc"),
+        ]);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(editor.canvas().dimensions(), (3, 2));
+        assert_eq!(editor.canvas().get_pixel(2, 1).unwrap(), Color32::RED);
+        assert_eq!(
+            editor.recent_files().iter().collect::<Vec<_>>(),
+            [path.as_path()]
+        );
+        let toasts: Vec<&str> = editor.toasts().iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(
+            toasts,
+            ["Only one file can be opened at a time, ignored 2 more"]
+        );
+
+        editor.open_dropped_files(&[dropped("notes.txt")]);
+        assert!(editor
+            .toasts()
+            .iter()
+            .any(|t| t.text.starts_with("Load failed: Unsupported file type")
+                && t.level == ToastLevel::Error));
+    }
+
+    #[test]
+    fn test_grid_screen_roundtrip() {
+        let canvas_dims = (64, 64);
+        for base in [CELL_SIZE, 7.0, 32.0] {
+            let mut editor = PixelEditorBuilder::new()
+                .dimensions(canvas_dims.0, canvas_dims.1)
+                .cell_size(base)
+                .build()
+                .unwrap();
+            for zoom in [0.3, 1.0, 2.5, MAX_ZOOM] {
+                editor
+                    .viewport_info
+                    .set_zoom(zoom, &editor.viewport_options);
+                let cell_size = editor.viewport_info.cell_size();
+                assert_eq!(cell_size, zoom * base);
+
+                let canvas_rect =
+                    Rect::from_min_size(Pos2::new(13.5, -47.25), Vec2::new(64.0, 64.0) * cell_size);
+                editor.viewport_info.update(canvas_rect, canvas_rect, None);
+                for cell in (0..64)
+                    .step_by(3)
+                    .flat_map(|x| [(x, 0), (x, 63 - x), (63, x)])
+                {
+                    let (x, y) = grid_to_screen(&editor.viewport_info, cell);
+                    // Anywhere inside the cell picks it. Its exact corner is
+                    // shared with the neighbours and may round either way.
+                    for offset in [0.01, 0.5, 0.99] {
+                        let pos = Pos2::new(x, y) + Vec2::splat(offset * cell_size);
+                        assert_eq!(
+                            screen_to_grid(&editor.viewport_info, &canvas_dims, pos),
+                            cell,
+                            "base {base}, zoom {zoom}, offset {offset}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_document_settings_survive_save() {
+        let path =
+            std::env::temp_dir().join(format!("pxc-editor-document-{}.pxc", std::process::id()));
+
+        let mut editor = PixelEditorBuilder::new().dimensions(4, 4).build().unwrap();
+        editor.canvas.set_pixel(1, 1, Color32::RED).unwrap();
+        editor.document.grid_spacing = 4;
+        editor.save_image_to(&path);
+        assert!(editor
+            .toasts()
+            .iter()
+            .all(|t| t.text.starts_with("File saved")));
+
+        let reopened = PixelEditorBuilder::new().file(&path).build();
+        std::fs::remove_file(&path).unwrap();
+        let reopened = reopened.unwrap();
+
+        assert_eq!(reopened.document().grid_spacing, 4);
+        assert_eq!(reopened.document().tile_size, None);
+        assert_eq!(reopened.canvas().get_pixel(1, 1).unwrap(), Color32::RED);
     }
 }
@@ -1,28 +1,94 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use crate::{
     chunk::{Chunk, ChunkError, CHUNK_SIZE},
     image_source::ImageSource,
+    layer::{blend, Layer},
 };
 use eframe::egui::{self, Color32, TextureHandle};
+use rayon::prelude::*;
 
-pub const CELL_SIZE: i32 = 20; // in pixels
+/// Default on-screen side of a canvas pixel at zoom 1, in points. The
+/// editor's base cell size can be changed with `ViewportInfo::set_base_cell_size`.
+pub const CELL_SIZE: f32 = 20.0;
+
+/// Most chunks rendered in one cache update. The rest wait for the next frame
+/// so zooming far out doesn't stall a single frame.
+pub const MAX_CHUNK_RENDERS_PER_FRAME: usize = 256;
+
+/// Changed area of a cached chunk texture, in inclusive local coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct DirtyRect {
+    min: (u8, u8),
+    max: (u8, u8),
+}
+
+impl DirtyRect {
+    fn new(x: u8, y: u8) -> Self {
+        Self {
+            min: (x, y),
+            max: (x, y),
+        }
+    }
+
+    fn include(&mut self, x: u8, y: u8) {
+        self.min = (self.min.0.min(x), self.min.1.min(y));
+        self.max = (self.max.0.max(x), self.max.1.max(y));
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (
+            (self.max.0 - self.min.0) as usize + 1,
+            (self.max.1 - self.min.1) as usize + 1,
+        )
+    }
+}
 
-#[derive(Default)]
 pub struct Canvas {
     cached_visible_chunks: HashMap<(u32, u32), TextureHandle>,
-    chunks: HashMap<(u32, u32), Chunk>,
+    // Parts of cached textures that changed since they were uploaded
+    dirty_rects: HashMap<(u32, u32), DirtyRect>,
+    // Texture bytes sent to egui, for measuring the cache
+    uploaded_bytes: usize,
+    // Ordered bottom to top
+    layers: Vec<Layer>,
+    active_layer: usize,
     width: u32,
     height: u32,
+    unbounded: bool,
+    // Inclusive (min_x, min_y, max_x, max_y) of every non-transparent pixel drawn
+    content_bounds: Option<(u32, u32, u32, u32)>,
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self::new(0, 0)
+    }
 }
 
 impl Canvas {
     pub fn new(width: u32, height: u32) -> Self {
         Self {
             cached_visible_chunks: HashMap::new(),
-            chunks: HashMap::new(),
+            dirty_rects: HashMap::new(),
+            uploaded_bytes: 0,
+            layers: vec![Layer::new("Background")],
+            active_layer: 0,
             width,
             height,
+            unbounded: false,
+            content_bounds: None,
+        }
+    }
+
+    /// Creates a canvas that grows whenever a pixel is drawn outside its current size.
+    /// It only grows right and down: coordinates are unsigned, so nothing can be
+    /// drawn above or left of the origin.
+    pub fn new_unbounded(width: u32, height: u32) -> Self {
+        Self {
+            unbounded: true,
+            ..Self::new(width, height)
         }
     }
 
@@ -30,6 +96,97 @@ impl Canvas {
         (self.width, self.height)
     }
 
+    pub fn is_unbounded(&self) -> bool {
+        self.unbounded
+    }
+
+    pub fn set_unbounded(&mut self, unbounded: bool) {
+        self.unbounded = unbounded;
+    }
+
+    pub fn content_bounds(&self) -> Option<(u32, u32, u32, u32)> {
+        self.content_bounds
+    }
+
+    fn expand_content_bounds(&mut self, x: u32, y: u32) {
+        self.content_bounds = Some(match self.content_bounds {
+            Some((min_x, min_y, max_x, max_y)) => {
+                (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+            }
+            None => (x, y, x, y),
+        });
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    pub fn active_layer(&self) -> usize {
+        self.active_layer
+    }
+
+    pub fn set_active_layer(&mut self, index: usize) {
+        if index < self.layers.len() {
+            self.active_layer = index;
+        }
+    }
+
+    /// Adds an empty layer on top of the stack and makes it active, returning
+    /// its index
+    pub fn add_layer(&mut self, name: impl Into<String>) -> usize {
+        self.layers.push(Layer::new(name));
+        self.active_layer = self.layers.len() - 1;
+        self.active_layer
+    }
+
+    /// Removes a layer. The last remaining layer can't be removed.
+    pub fn remove_layer(&mut self, index: usize) -> bool {
+        if self.layers.len() <= 1 || index >= self.layers.len() {
+            return false;
+        }
+        self.layers.remove(index);
+        if self.active_layer >= index && self.active_layer > 0 {
+            self.active_layer -= 1;
+        }
+        self.cached_visible_chunks.clear();
+        true
+    }
+
+    /// Moves a layer to another position in the stack, keeping the same layer
+    /// active
+    pub fn move_layer(&mut self, from: usize, to: usize) {
+        if from >= self.layers.len() || to >= self.layers.len() || from == to {
+            return;
+        }
+        let active = self.active_layer;
+        let layer = self.layers.remove(from);
+        self.layers.insert(to, layer);
+        self.active_layer = if active == from {
+            to
+        } else if from < active && active <= to {
+            active - 1
+        } else if to <= active && active < from {
+            active + 1
+        } else {
+            active
+        };
+        self.cached_visible_chunks.clear();
+    }
+
+    pub fn set_layer_visible(&mut self, index: usize, visible: bool) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.visible = visible;
+            self.cached_visible_chunks.clear();
+        }
+    }
+
+    pub fn set_layer_opacity(&mut self, index: usize, opacity: f32) {
+        if let Some(layer) = self.layers.get_mut(index) {
+            layer.opacity = opacity.clamp(0.0, 1.0);
+            self.cached_visible_chunks.clear();
+        }
+    }
+
     pub fn get_chunk_coords(x: u32, y: u32) -> (u32, u32) {
         let chunk_x = x / CHUNK_SIZE as u32;
         let chunk_y = y / CHUNK_SIZE as u32;
@@ -48,7 +205,7 @@ impl Canvas {
         (x, y)
     }
 
-    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color32) -> Result<(), ChunkError> {
+    fn check_bounds(&self, x: u32, y: u32) -> Result<(), ChunkError> {
         if x >= self.width || y >= self.height {
             return Err(ChunkError::OutOfBounds {
                 x: x as u8,
@@ -56,74 +213,359 @@ impl Canvas {
                 chunk_size: CHUNK_SIZE,
             });
         }
+        Ok(())
+    }
+
+    /// Writes a pixel to the active layer
+    pub fn set_pixel(&mut self, x: u32, y: u32, color: Color32) -> Result<(), ChunkError> {
+        self.set_layer_pixel(self.active_layer, x, y, color)
+    }
+
+    pub fn set_layer_pixel(
+        &mut self,
+        layer: usize,
+        x: u32,
+        y: u32,
+        color: Color32,
+    ) -> Result<(), ChunkError> {
+        // A canvas including coordinate u32::MAX would need a size past
+        // u32::MAX, so that row and column stay out of bounds
+        if self.unbounded && x < u32::MAX && y < u32::MAX {
+            self.width = self.width.max(x + 1);
+            self.height = self.height.max(y + 1);
+        }
+
+        self.check_bounds(x, y)?;
+        let Some(layer) = self.layers.get_mut(layer) else {
+            return Ok(());
+        };
 
         let chunk_coords = Self::get_chunk_coords(x, y);
         let local_coords = Self::get_local_coords(x, y);
 
-        println!(
-            "Setting pixel at ({}, {}), chunk coords: ({}, {}), local coords: ({}, {})",
-            x, y, chunk_coords.0, chunk_coords.1, local_coords.0, local_coords.1
-        );
+        let chunk = layer.chunks.entry(chunk_coords).or_default();
+        chunk.set_pixel(local_coords.0, local_coords.1, color)?;
+        // Erasing the last pixel drops the chunk, so saving and rendering
+        // skip it like a chunk that was never painted
+        if chunk.is_empty {
+            layer.chunks.remove(&chunk_coords);
+        }
 
-        let chunk = self.chunks.entry(chunk_coords).or_insert_with(Chunk::new);
-        self.cached_visible_chunks.remove(&chunk_coords);
+        // Cached textures are patched in place on the next cache update
+        if self.cached_visible_chunks.contains_key(&chunk_coords) {
+            self.dirty_rects
+                .entry(chunk_coords)
+                .and_modify(|rect| rect.include(local_coords.0, local_coords.1))
+                .or_insert(DirtyRect::new(local_coords.0, local_coords.1));
+        }
 
-        chunk.set_pixel(local_coords.0, local_coords.1, color)?;
+        if color != Color32::TRANSPARENT {
+            self.expand_content_bounds(x, y);
+        }
 
         Ok(())
     }
 
+    /// Returns the visible layers composited bottom to top
     pub fn get_pixel(&self, x: u32, y: u32) -> Result<Color32, ChunkError> {
-        if x >= self.width || y >= self.height {
-            return Err(ChunkError::OutOfBounds {
-                x: x as u8,
-                y: y as u8,
-                chunk_size: CHUNK_SIZE,
-            });
-        }
+        self.check_bounds(x, y)?;
+        Ok(self.composite_pixel(x, y))
+    }
 
-        let chunk_coords = Self::get_chunk_coords(x, y);
-        let local_coords = Self::get_local_coords(x, y);
+    /// Returns the pixel of a single layer, ignoring visibility and opacity
+    pub fn get_layer_pixel(&self, layer: usize, x: u32, y: u32) -> Result<Color32, ChunkError> {
+        self.check_bounds(x, y)?;
+        Ok(self
+            .layers
+            .get(layer)
+            .map_or(Color32::TRANSPARENT, |layer| layer.get_pixel(x, y)))
+    }
 
-        self.chunks
-            .get(&chunk_coords)
-            .map_or(Ok(Color32::TRANSPARENT), |chunk| {
-                chunk.get_pixel(local_coords.0, local_coords.1)
+    fn composite_pixel(&self, x: u32, y: u32) -> Color32 {
+        self.layers
+            .iter()
+            .filter(|layer| layer.visible)
+            .fold(Color32::TRANSPARENT, |below, layer| {
+                blend(below, layer.get_pixel(x, y), layer.opacity)
             })
     }
 
+    /// Returns row-major RGBA data for the whole composited canvas
     pub fn get_data(&self) -> Vec<u8> {
-        let mut data = Vec::new();
-        for chunk in self.chunks.values() {
-            data.extend_from_slice(
-                &chunk
-                    .pixels
-                    .iter()
-                    .flat_map(|p| vec![p.r(), p.g(), p.b(), p.a()])
-                    .collect::<Vec<u8>>(),
-            );
+        self.get_region_data(0, 0, self.width, self.height)
+    }
+
+    /// Returns row-major RGBA data for the given region of the composited canvas
+    pub fn get_region_data(&self, x: u32, y: u32, width: u32, height: u32) -> Vec<u8> {
+        self.region_data(x, y, width, height, |x, y| self.get_pixel(x, y))
+    }
+
+    /// Returns row-major RGBA data for the whole of a single layer
+    pub fn get_layer_data(&self, layer: usize) -> Vec<u8> {
+        self.region_data(0, 0, self.width, self.height, |x, y| {
+            self.get_layer_pixel(layer, x, y)
+        })
+    }
+
+    fn region_data(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        pixel: impl Fn(u32, u32) -> Result<Color32, ChunkError>,
+    ) -> Vec<u8> {
+        let mut data = Vec::with_capacity(width as usize * height as usize * 4);
+        for pixel_y in y..y + height {
+            for pixel_x in x..x + width {
+                let color = pixel(pixel_x, pixel_y).unwrap_or(Color32::TRANSPARENT);
+                data.extend_from_slice(&color.to_srgba_unmultiplied());
+            }
         }
         data
     }
 
+    /// Returns the dimensions and RGBA data that should be written on save,
+    /// with all visible layers flattened. Unbounded canvases only export their
+    /// content bounds.
+    pub fn get_export_data(&self) -> ((u32, u32), Vec<u8>) {
+        if !self.unbounded {
+            return ((self.width, self.height), self.get_data());
+        }
+
+        match self.content_bounds {
+            Some((min_x, min_y, max_x, max_y)) => {
+                let width = max_x - min_x + 1;
+                let height = max_y - min_y + 1;
+                (
+                    (width, height),
+                    self.get_region_data(min_x, min_y, width, height),
+                )
+            }
+            None => ((0, 0), Vec::new()),
+        }
+    }
+
+    /// Clears the active layer
     pub fn clear(&mut self) {
-        self.chunks.clear();
+        self.layers[self.active_layer].chunks.clear();
         self.cached_visible_chunks.clear();
+        self.dirty_rects.clear();
+        if self.is_empty() {
+            self.content_bounds = None;
+        }
+    }
+
+    /// Fills the whole active layer with `color`. A transparent color is the
+    /// same as [`Canvas::clear`] and leaves no chunks allocated.
+    pub fn clear_to(&mut self, color: Color32) {
+        self.clear();
+        if color == Color32::TRANSPARENT || self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let (max_chunk_x, max_chunk_y) = Self::get_chunk_coords(self.width - 1, self.height - 1);
+        let layer = &mut self.layers[self.active_layer];
+        for chunk_y in 0..=max_chunk_y {
+            for chunk_x in 0..=max_chunk_x {
+                let mut chunk = Chunk::new();
+                let (end_x, end_y) =
+                    Self::get_absolute_coords(chunk_x, chunk_y, CHUNK_SIZE, CHUNK_SIZE);
+                if end_x <= self.width && end_y <= self.height {
+                    chunk.fill(color);
+                } else {
+                    // Chunks on the right and bottom edges stick out of the canvas
+                    for local_y in 0..CHUNK_SIZE {
+                        for local_x in 0..CHUNK_SIZE {
+                            let (x, y) =
+                                Self::get_absolute_coords(chunk_x, chunk_y, local_x, local_y);
+                            if x < self.width && y < self.height {
+                                chunk.pixels
+                                    [local_y as usize * CHUNK_SIZE as usize + local_x as usize] =
+                                    color;
+                            }
+                        }
+                    }
+                    chunk.is_empty = false;
+                }
+                layer.chunks.insert((chunk_x, chunk_y), chunk);
+            }
+        }
+        self.content_bounds = Some((0, 0, self.width - 1, self.height - 1));
+    }
+
+    /// Replaces every pixel of color `from` with `to` on the active layer,
+    /// returning how many pixels changed
+    pub fn replace_color(&mut self, from: Color32, to: Color32) -> usize {
+        if from == to || self.width == 0 || self.height == 0 {
+            return 0;
+        }
+
+        let layer = &mut self.layers[self.active_layer];
+
+        // Transparent pixels also live in chunks that were never allocated
+        if from == Color32::TRANSPARENT {
+            let (max_chunk_x, max_chunk_y) =
+                Self::get_chunk_coords(self.width - 1, self.height - 1);
+            for chunk_y in 0..=max_chunk_y {
+                for chunk_x in 0..=max_chunk_x {
+                    layer.chunks.entry((chunk_x, chunk_y)).or_default();
+                }
+            }
+        }
+
+        let mut replaced = 0;
+        let mut changed = Vec::new();
+        for (&(chunk_x, chunk_y), chunk) in layer.chunks.iter_mut() {
+            let mut chunk_changed = false;
+            for local_y in 0..CHUNK_SIZE {
+                for local_x in 0..CHUNK_SIZE {
+                    let (x, y) = Self::get_absolute_coords(chunk_x, chunk_y, local_x, local_y);
+                    let index = local_y as usize * CHUNK_SIZE as usize + local_x as usize;
+                    if x < self.width && y < self.height && chunk.pixels[index] == from {
+                        chunk.pixels[index] = to;
+                        chunk_changed = true;
+                        replaced += 1;
+                        if to != Color32::TRANSPARENT {
+                            changed.push((x, y));
+                        }
+                    }
+                }
+            }
+            if chunk_changed {
+                chunk.is_empty = chunk
+                    .pixels
+                    .iter()
+                    .all(|&pixel| pixel == Color32::TRANSPARENT);
+                self.cached_visible_chunks.remove(&(chunk_x, chunk_y));
+            }
+        }
+
+        for (x, y) in changed {
+            self.expand_content_bounds(x, y);
+        }
+        replaced
+    }
+
+    /// Replaces the 4-connected region of same-colored pixels around (x, y)
+    /// on the active layer with `color`, returning how many pixels changed
+    pub fn flood_fill(&mut self, x: u32, y: u32, color: Color32) -> usize {
+        let active = self.active_layer;
+        let Ok(target) = self.get_layer_pixel(active, x, y) else {
+            return 0;
+        };
+        if target == color {
+            return 0;
+        }
+
+        let mut filled = HashSet::from([(x, y)]);
+        let mut queue = VecDeque::from([(x, y)]);
+        while let Some((x, y)) = queue.pop_front() {
+            let neighbours = [
+                x.checked_sub(1).map(|x| (x, y)),
+                Some((x + 1, y)),
+                y.checked_sub(1).map(|y| (x, y)),
+                Some((x, y + 1)),
+            ];
+            for (nx, ny) in neighbours.into_iter().flatten() {
+                if self
+                    .get_layer_pixel(active, nx, ny)
+                    .is_ok_and(|pixel| pixel == target)
+                    && filled.insert((nx, ny))
+                {
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+
+        let layer = &mut self.layers[active];
+        let mut touched = HashSet::new();
+        for &(x, y) in &filled {
+            let chunk_coords = Self::get_chunk_coords(x, y);
+            let (local_x, local_y) = Self::get_local_coords(x, y);
+            let chunk = layer.chunks.entry(chunk_coords).or_default();
+            chunk.pixels[local_y as usize * CHUNK_SIZE as usize + local_x as usize] = color;
+            touched.insert(chunk_coords);
+        }
+        for chunk_coords in touched {
+            if let Some(chunk) = layer.chunks.get_mut(&chunk_coords) {
+                chunk.is_empty = chunk
+                    .pixels
+                    .iter()
+                    .all(|&pixel| pixel == Color32::TRANSPARENT);
+            }
+            self.cached_visible_chunks.remove(&chunk_coords);
+        }
+
+        if color != Color32::TRANSPARENT {
+            for &(x, y) in &filled {
+                self.expand_content_bounds(x, y);
+            }
+        }
+        filled.len()
+    }
+
+    /// Whether every pixel of every layer is transparent
+    pub fn is_empty(&self) -> bool {
+        self.layers.iter().all(Layer::is_empty)
     }
 
+    /// Changes the canvas size, discarding every pixel outside the new bounds
     pub fn resize(&mut self, new_width: u32, new_height: u32) {
         self.width = new_width;
         self.height = new_height;
 
-        // Remove chunks outside the new canvas bounds
-        self.chunks.retain(|&(chunk_x, chunk_y), _| {
-            let chunk_min_x = chunk_x * CHUNK_SIZE as u32;
-            let chunk_min_y = chunk_y * CHUNK_SIZE as u32;
-            chunk_min_x < new_width && chunk_min_y < new_height
-        });
+        for layer in &mut self.layers {
+            // Remove chunks outside the new canvas bounds
+            layer.chunks.retain(|&(chunk_x, chunk_y), _| {
+                let chunk_min_x = chunk_x * CHUNK_SIZE as u32;
+                let chunk_min_y = chunk_y * CHUNK_SIZE as u32;
+                chunk_min_x < new_width && chunk_min_y < new_height
+            });
+
+            // Clear the part of edge chunks that now hangs over the edge, so
+            // growing the canvas again doesn't bring old pixels back
+            for (&(chunk_x, chunk_y), chunk) in layer.chunks.iter_mut() {
+                let mut chunk_changed = false;
+                for local_y in 0..CHUNK_SIZE {
+                    for local_x in 0..CHUNK_SIZE {
+                        let (x, y) = Self::get_absolute_coords(chunk_x, chunk_y, local_x, local_y);
+                        let index = local_y as usize * CHUNK_SIZE as usize + local_x as usize;
+                        if (x >= new_width || y >= new_height)
+                            && chunk.pixels[index] != Color32::TRANSPARENT
+                        {
+                            chunk.pixels[index] = Color32::TRANSPARENT;
+                            chunk_changed = true;
+                        }
+                    }
+                }
+                if chunk_changed {
+                    chunk.is_empty = chunk
+                        .pixels
+                        .iter()
+                        .all(|&pixel| pixel == Color32::TRANSPARENT);
+                }
+            }
+        }
+        self.cached_visible_chunks.clear();
+
+        self.content_bounds = self
+            .content_bounds
+            .filter(|&(min_x, min_y, _, _)| min_x < new_width && min_y < new_height)
+            .map(|(min_x, min_y, max_x, max_y)| {
+                (
+                    min_x,
+                    min_y,
+                    max_x.min(new_width - 1),
+                    max_y.min(new_height - 1),
+                )
+            });
     }
 
-    pub fn load_image(&mut self, image: &impl ImageSource) {
+    /// Replaces every layer with a single layer holding the image
+    pub fn load_image<T: ImageSource + ?Sized>(&mut self, image: &T) {
+        self.layers = vec![Layer::new("Background")];
+        self.active_layer = 0;
         self.clear();
 
         let (width, height) = image.dimensions();
@@ -135,59 +577,600 @@ impl Canvas {
 
         for chunk_y in 0..chunks_height {
             for chunk_x in 0..chunks_width {
-                if let Some(chunk) = image.load_chunk(chunk_x as i32, chunk_y as i32) {
-                    self.chunks.insert((chunk_x, chunk_y), chunk);
+                // Transparent chunks are left out, as if never painted
+                if let Some(chunk) = image
+                    .load_chunk(chunk_x as i32, chunk_y as i32)
+                    .filter(|chunk| !chunk.is_empty)
+                {
+                    self.layers[0].chunks.insert((chunk_x, chunk_y), chunk);
                 }
             }
         }
+
+        if width > 0 && height > 0 {
+            self.content_bounds = Some((0, 0, width - 1, height - 1));
+        }
+    }
+
+    /// Total bytes of texture data uploaded by [`Canvas::update_cache`]
+    pub fn uploaded_bytes(&self) -> usize {
+        self.uploaded_bytes
     }
 
     pub fn update_cache(
         &mut self,
-        visible_chunks: &Vec<(u32, u32)>,
+        visible_chunks: &[(u32, u32)],
         ctx: &egui::Context,
     ) -> &HashMap<(u32, u32), TextureHandle> {
         // Retain only the visible chunks in the cache
         self.cached_visible_chunks
             .retain(|chunk_pos, _| visible_chunks.contains(chunk_pos));
 
-        for &chunk_pos in visible_chunks {
-            // Check if the chunk is not already cached
+        // Patch the changed parts of cached textures. Rects covering most of
+        // a chunk are cheaper to send as one full upload.
+        let chunk_area = CHUNK_SIZE as usize * CHUNK_SIZE as usize;
+        for (chunk_pos, rect) in std::mem::take(&mut self.dirty_rects) {
             if !self.cached_visible_chunks.contains_key(&chunk_pos) {
-                // Only render the chunk if it exists
-                if let Some(chunk) = self.chunks.get(&chunk_pos) {
-                    if let Some(texture) = Self::render_chunk(chunk, chunk_pos, ctx) {
-                        self.cached_visible_chunks.insert(chunk_pos, texture);
-                    }
-                }
+                continue;
+            }
+            let (width, height) = rect.size();
+            if !self.has_visible_content(chunk_pos) || width * height * 2 > chunk_area {
+                self.cached_visible_chunks.remove(&chunk_pos);
+                continue;
             }
+
+            let pixels: Vec<Color32> = (rect.min.1..=rect.max.1)
+                .flat_map(|local_y| {
+                    (rect.min.0..=rect.max.0).map(move |local_x| (local_x, local_y))
+                })
+                .map(|(local_x, local_y)| {
+                    let (x, y) =
+                        Self::get_absolute_coords(chunk_pos.0, chunk_pos.1, local_x, local_y);
+                    self.composite_pixel(x, y)
+                })
+                .collect();
+            if let Some(texture) = self.cached_visible_chunks.get_mut(&chunk_pos) {
+                texture.set_partial(
+                    [rect.min.0 as usize, rect.min.1 as usize],
+                    Self::color_image([width, height], &pixels),
+                    egui::TextureOptions::NEAREST,
+                );
+                self.uploaded_bytes += pixels.len() * 4;
+            }
+        }
+
+        // Only render chunks that aren't cached and have content on some
+        // visible layer
+        let uncached: Vec<(u32, u32)> = visible_chunks
+            .iter()
+            .copied()
+            .filter(|chunk_pos| {
+                !self.cached_visible_chunks.contains_key(chunk_pos)
+                    && self.has_visible_content(*chunk_pos)
+            })
+            .collect();
+        if uncached.len() > MAX_CHUNK_RENDERS_PER_FRAME {
+            ctx.request_repaint();
+        }
+        let uncached = &uncached[..uncached.len().min(MAX_CHUNK_RENDERS_PER_FRAME)];
+
+        // Compositing runs in parallel, but textures have to be created on
+        // this thread
+        let rendered = composite_chunks(&self.layers, uncached);
+        for (chunk_pos, pixels) in rendered {
+            let texture = Self::render_chunk(&pixels, chunk_pos, ctx);
+            self.uploaded_bytes += pixels.len() * 4;
+            self.cached_visible_chunks.insert(chunk_pos, texture);
         }
 
         &self.cached_visible_chunks
     }
 
+    fn has_visible_content(&self, chunk_pos: (u32, u32)) -> bool {
+        self.layers.iter().any(|layer| {
+            layer.visible
+                && layer
+                    .chunks
+                    .get(&chunk_pos)
+                    .is_some_and(|chunk| !chunk.is_empty)
+        })
+    }
+
     fn render_chunk(
-        chunk: &Chunk,
+        pixels: &[Color32],
         chunk_pos: (u32, u32),
         ctx: &egui::Context,
-    ) -> Option<egui::TextureHandle> {
-        if chunk.is_empty {
-            return None;
+    ) -> egui::TextureHandle {
+        ctx.load_texture(
+            format!("chunk_{}_{}", chunk_pos.0, chunk_pos.1),
+            Self::color_image([CHUNK_SIZE as usize, CHUNK_SIZE as usize], pixels),
+            egui::TextureOptions::NEAREST,
+        )
+    }
+
+    /// `Color32` stores premultiplied RGBA, so its bytes go in as they are
+    fn color_image(size: [usize; 2], pixels: &[Color32]) -> egui::ColorImage {
+        egui::ColorImage::from_rgba_premultiplied(size, bytemuck::cast_slice(pixels))
+    }
+}
+
+/// Composites the visible layers' chunks at `chunk_pos`, or `None` if they are
+/// all empty
+fn composite_chunk(layers: &[Layer], chunk_pos: (u32, u32)) -> Option<Cow<'_, [Color32]>> {
+    let chunks: Vec<(&Chunk, f32)> = layers
+        .iter()
+        .filter(|layer| layer.visible)
+        .filter_map(|layer| {
+            let chunk = layer.chunks.get(&chunk_pos)?;
+            (!chunk.is_empty).then_some((chunk, layer.opacity))
+        })
+        .collect();
+
+    match chunks.as_slice() {
+        [] => None,
+        [(chunk, opacity)] if *opacity >= 1.0 => Some(Cow::Borrowed(&chunk.pixels[..])),
+        _ => Some(Cow::Owned(
+            (0..CHUNK_SIZE as usize * CHUNK_SIZE as usize)
+                .map(|index| {
+                    chunks
+                        .iter()
+                        .fold(Color32::TRANSPARENT, |below, (chunk, opacity)| {
+                            blend(below, chunk.pixels[index], *opacity)
+                        })
+                })
+                .collect(),
+        )),
+    }
+}
+
+/// A chunk position and its composited pixels
+type CompositedChunk<'a> = ((u32, u32), Cow<'a, [Color32]>);
+
+/// Composites many chunks on the rayon thread pool, skipping empty ones.
+/// Results keep the order of `chunk_positions`.
+fn composite_chunks<'a>(
+    layers: &'a [Layer],
+    chunk_positions: &[(u32, u32)],
+) -> Vec<CompositedChunk<'a>> {
+    chunk_positions
+        .par_iter()
+        .filter_map(|&chunk_pos| Some((chunk_pos, composite_chunk(layers, chunk_pos)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib_pxc::{decode, encode};
+
+    #[test]
+    fn test_flood_fill_bounded_region() {
+        // A red ring around a 2x2 transparent hole on a 6x6 canvas
+        let mut canvas = Canvas::new(6, 6);
+        for i in 1..5 {
+            for (x, y) in [(i, 1), (i, 4), (1, i), (4, i)] {
+                canvas.set_pixel(x, y, Color32::RED).unwrap();
+            }
+        }
+
+        assert_eq!(canvas.flood_fill(2, 2, Color32::BLUE), 4);
+        for y in 0..6 {
+            for x in 0..6 {
+                let expected = match (x, y) {
+                    (2..=3, 2..=3) => Color32::BLUE,
+                    (1..=4, 1..=4) => Color32::RED,
+                    _ => Color32::TRANSPARENT,
+                };
+                assert_eq!(canvas.get_pixel(x, y).unwrap(), expected, "({x}, {y})");
+            }
+        }
+
+        // Filling with the target color changes nothing
+        assert_eq!(canvas.flood_fill(2, 2, Color32::BLUE), 0);
+        // The outside stops at the canvas edges: 36 - 16 pixels
+        assert_eq!(canvas.flood_fill(0, 0, Color32::GREEN), 20);
+        assert_eq!(canvas.flood_fill(6, 0, Color32::GREEN), 0);
+    }
+
+    #[test]
+    fn test_layers_composite_with_alpha() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_pixel(0, 0, Color32::RED).unwrap();
+        canvas.set_pixel(1, 0, Color32::RED).unwrap();
+
+        let top = canvas.add_layer("Top");
+        assert_eq!(canvas.active_layer(), top);
+        let half_blue = Color32::from_rgba_unmultiplied(0, 0, 255, 128);
+        canvas.set_pixel(0, 0, half_blue).unwrap();
+
+        // Writes only touch the active layer
+        assert_eq!(canvas.get_layer_pixel(0, 0, 0).unwrap(), Color32::RED);
+        assert_eq!(canvas.get_layer_pixel(top, 0, 0).unwrap(), half_blue);
+
+        let blended = canvas.get_pixel(0, 0).unwrap().to_srgba_unmultiplied();
+        for (channel, expected) in blended.iter().zip([127u8, 0, 128, 255]) {
+            assert!(channel.abs_diff(expected) <= 1, "{blended:?}");
         }
+        assert_eq!(canvas.get_pixel(1, 0).unwrap(), Color32::RED);
 
-        let color_image = egui::ColorImage::from_rgba_unmultiplied(
-            [CHUNK_SIZE as usize, CHUNK_SIZE as usize],
-            &chunk
-                .pixels
-                .iter()
-                .flat_map(|p| vec![p.r(), p.g(), p.b(), p.a()])
-                .collect::<Vec<u8>>(),
+        canvas.set_layer_visible(top, false);
+        assert_eq!(canvas.get_pixel(0, 0).unwrap(), Color32::RED);
+        canvas.set_layer_visible(top, true);
+        canvas.move_layer(top, 0);
+        assert_eq!(canvas.active_layer(), 0);
+        assert_eq!(canvas.get_pixel(0, 0).unwrap(), Color32::RED);
+    }
+
+    #[test]
+    fn test_diagonal_stroke_invalidates_every_crossed_chunk() {
+        let size = CHUNK_SIZE as u32 * 3;
+        let mut canvas = Canvas::new(size, size);
+        canvas.clear_to(Color32::WHITE);
+
+        let ctx = egui::Context::default();
+        let all_chunks: Vec<(u32, u32)> =
+            (0..3).flat_map(|y| (0..3).map(move |x| (x, y))).collect();
+        assert_eq!(canvas.update_cache(&all_chunks, &ctx).len(), 9);
+
+        // A diagonal crosses the three chunks on the main diagonal and, from
+        // one pixel off it, the chunks beside them at each corner
+        for i in 0..size - 1 {
+            canvas.set_pixel(i, i, Color32::RED).unwrap();
+            canvas.set_pixel(i + 1, i, Color32::RED).unwrap();
+        }
+
+        let mut invalidated: Vec<_> = all_chunks
+            .iter()
+            .filter(|pos| {
+                !canvas.cached_visible_chunks.contains_key(pos)
+                    || canvas.dirty_rects.contains_key(pos)
+            })
+            .copied()
+            .collect();
+        invalidated.sort();
+        assert_eq!(invalidated, vec![(0, 0), (1, 0), (1, 1), (2, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn test_resize_smaller_discards_pixels() {
+        let size = CHUNK_SIZE as u32 * 2;
+        let mut canvas = Canvas::new(size, size);
+        canvas.set_pixel(1, 1, Color32::RED).unwrap();
+        canvas.set_pixel(10, 1, Color32::GREEN).unwrap();
+        canvas.set_pixel(size - 1, size - 1, Color32::BLUE).unwrap();
+
+        canvas.resize(8, 8);
+        assert_eq!(canvas.dimensions(), (8, 8));
+        assert_eq!(canvas.layers[0].chunks.len(), 1);
+        assert_eq!(canvas.get_pixel(1, 1).unwrap(), Color32::RED);
+        assert!(canvas.get_pixel(10, 1).is_err());
+
+        // Growing again doesn't bring the cropped pixels back
+        canvas.resize(size, size);
+        assert_eq!(canvas.get_pixel(1, 1).unwrap(), Color32::RED);
+        assert_eq!(canvas.get_pixel(10, 1).unwrap(), Color32::TRANSPARENT);
+        assert_eq!(
+            canvas.get_pixel(size - 1, size - 1).unwrap(),
+            Color32::TRANSPARENT
         );
+    }
 
-        Some(ctx.load_texture(
-            format!("chunk_{}_{}", chunk_pos.0, chunk_pos.1),
-            color_image,
-            egui::TextureOptions::NEAREST,
-        ))
+    #[test]
+    fn test_resize_larger_keeps_content() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.set_pixel(3, 3, Color32::RED).unwrap();
+
+        canvas.resize(100, 90);
+        assert_eq!(canvas.dimensions(), (100, 90));
+        assert_eq!(canvas.get_pixel(3, 3).unwrap(), Color32::RED);
+        assert_eq!(canvas.content_bounds(), Some((3, 3, 3, 3)));
+        canvas.set_pixel(99, 89, Color32::BLUE).unwrap();
+        assert_eq!(canvas.get_pixel(99, 89).unwrap(), Color32::BLUE);
+    }
+
+    #[test]
+    fn test_erasing_last_pixel_drops_chunk() {
+        let mut canvas = Canvas::new(CHUNK_SIZE as u32 * 2, CHUNK_SIZE as u32);
+        let far = CHUNK_SIZE as u32 + 5;
+        canvas.set_pixel(1, 1, Color32::RED).unwrap();
+        canvas.set_pixel(2, 1, Color32::RED).unwrap();
+        canvas.set_pixel(far, 3, Color32::BLUE).unwrap();
+        assert_eq!(canvas.layers[0].chunks.len(), 2);
+
+        canvas.set_pixel(1, 1, Color32::TRANSPARENT).unwrap();
+        assert!(canvas.layers[0].chunks.contains_key(&(0, 0)));
+        canvas.set_pixel(2, 1, Color32::TRANSPARENT).unwrap();
+        assert!(!canvas.layers[0].chunks.contains_key(&(0, 0)));
+        assert!(canvas.layers[0].chunks.contains_key(&(1, 0)));
+
+        // Erasing where nothing was painted doesn't allocate a chunk either
+        canvas.set_pixel(3, 3, Color32::TRANSPARENT).unwrap();
+        assert_eq!(canvas.layers[0].chunks.len(), 1);
+        assert_eq!(canvas.get_pixel(far, 3).unwrap(), Color32::BLUE);
+    }
+
+    #[test]
+    fn test_clear_empties_pixels_and_cache() {
+        let size = CHUNK_SIZE as u32 + 4;
+        let mut canvas = Canvas::new(size, size);
+        canvas.clear_to(Color32::WHITE);
+
+        let ctx = egui::Context::default();
+        let visible = vec![(0, 0), (1, 0), (0, 1), (1, 1)];
+        canvas.update_cache(&visible, &ctx);
+        canvas.set_pixel(1, 1, Color32::RED).unwrap();
+        assert!(!canvas.cached_visible_chunks.is_empty());
+        assert!(!canvas.dirty_rects.is_empty());
+
+        canvas.clear();
+        assert!(canvas.cached_visible_chunks.is_empty());
+        assert!(canvas.dirty_rects.is_empty());
+        assert!(canvas.is_empty());
+        for y in 0..size {
+            for x in 0..size {
+                assert_eq!(canvas.get_pixel(x, y).unwrap(), Color32::TRANSPARENT);
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_pixel_edit_uploads_dirty_rect() {
+        let size = CHUNK_SIZE as u32 * 2;
+        let mut canvas = Canvas::new(size, size);
+        canvas.clear_to(Color32::WHITE);
+
+        let ctx = egui::Context::default();
+        let visible = vec![(0, 0), (1, 0), (0, 1), (1, 1)];
+        canvas.update_cache(&visible, &ctx);
+        let full_chunk = CHUNK_SIZE as usize * CHUNK_SIZE as usize * 4;
+        assert_eq!(canvas.uploaded_bytes(), 4 * full_chunk);
+
+        // One pixel patches one pixel of one texture
+        canvas.set_pixel(70, 3, Color32::RED).unwrap();
+        let before = canvas.uploaded_bytes();
+        canvas.update_cache(&visible, &ctx);
+        assert_eq!(canvas.uploaded_bytes() - before, 4);
+        assert!(canvas.dirty_rects.is_empty());
+
+        // A small stroke sends its bounding box
+        canvas.set_pixel(2, 2, Color32::RED).unwrap();
+        canvas.set_pixel(4, 3, Color32::RED).unwrap();
+        let before = canvas.uploaded_bytes();
+        canvas.update_cache(&visible, &ctx);
+        assert_eq!(canvas.uploaded_bytes() - before, 3 * 2 * 4);
+
+        // Covering most of a chunk falls back to a full upload
+        for y in 0..CHUNK_SIZE as u32 {
+            canvas.set_pixel(y, y, Color32::BLUE).unwrap();
+        }
+        let before = canvas.uploaded_bytes();
+        canvas.update_cache(&visible, &ctx);
+        assert_eq!(canvas.uploaded_bytes() - before, full_chunk);
+    }
+
+    #[test]
+    fn test_composite_chunks_matches_sequential() {
+        let size = CHUNK_SIZE as u32 * 4;
+        let mut canvas = Canvas::new(size, size);
+        for i in 0..size {
+            canvas.set_pixel(i, i, Color32::RED).unwrap();
+        }
+        canvas.add_layer("Top");
+        canvas.set_layer_opacity(1, 0.5);
+        for i in 0..size {
+            canvas.set_pixel(size - 1 - i, i, Color32::BLUE).unwrap();
+        }
+
+        let positions: Vec<(u32, u32)> = (0..4).flat_map(|y| (0..4).map(move |x| (x, y))).collect();
+        let rendered = composite_chunks(&canvas.layers, &positions);
+
+        // Only the chunks on either diagonal have content
+        let rendered_positions: Vec<_> = rendered.iter().map(|(pos, _)| *pos).collect();
+        let expected: Vec<_> = positions
+            .iter()
+            .copied()
+            .filter(|&(x, y)| x == y || x + y == 3)
+            .collect();
+        assert_eq!(rendered_positions, expected);
+
+        for (chunk_pos, pixels) in &rendered {
+            assert_eq!(
+                Some(pixels),
+                composite_chunk(&canvas.layers, *chunk_pos).as_ref()
+            );
+            for local_y in 0..CHUNK_SIZE {
+                for local_x in 0..CHUNK_SIZE {
+                    let (x, y) =
+                        Canvas::get_absolute_coords(chunk_pos.0, chunk_pos.1, local_x, local_y);
+                    let index = local_y as usize * CHUNK_SIZE as usize + local_x as usize;
+                    assert_eq!(pixels[index], canvas.get_pixel(x, y).unwrap());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_canvas_bounded_rejects_out_of_bounds() {
+        let mut canvas = Canvas::new(32, 32);
+        assert!(canvas.set_pixel(200, 150, Color32::RED).is_err());
+        assert_eq!(canvas.dimensions(), (32, 32));
+        assert_eq!(canvas.content_bounds(), None);
+    }
+
+    #[test]
+    fn test_canvas_unbounded_grows() {
+        let mut canvas = Canvas::new_unbounded(32, 32);
+        canvas.set_pixel(200, 150, Color32::RED).unwrap();
+
+        assert_eq!(canvas.dimensions(), (201, 151));
+        assert_eq!(canvas.content_bounds(), Some((200, 150, 200, 150)));
+        assert_eq!(canvas.get_pixel(200, 150).unwrap(), Color32::RED);
+
+        canvas.set_pixel(10, 5, Color32::BLUE).unwrap();
+        assert_eq!(canvas.dimensions(), (201, 151));
+        assert_eq!(canvas.content_bounds(), Some((10, 5, 200, 150)));
+
+        assert!(matches!(
+            canvas.set_pixel(u32::MAX, 0, Color32::RED),
+            Err(ChunkError::OutOfBounds { .. })
+        ));
+        assert_eq!(canvas.dimensions(), (201, 151));
+    }
+
+    #[test]
+    fn test_canvas_unbounded_save_roundtrip() {
+        let mut canvas = Canvas::new_unbounded(32, 32);
+        let color = Color32::from_rgba_unmultiplied(250, 75, 98, 255);
+        canvas.set_pixel(10, 5, Color32::BLUE).unwrap();
+        canvas.set_pixel(200, 150, color).unwrap();
+
+        let ((width, height), data) = canvas.get_export_data();
+        assert_eq!((width, height), (191, 146));
+
+        let encoded = encode(width, height, &data).unwrap();
+        let decoded = decode(&encoded).unwrap();
+        assert_eq!(decoded.rgba_data, data);
+
+        let last = decoded.rgba_data.len() - 4;
+        assert_eq!(
+            &decoded.rgba_data[..4],
+            &Color32::BLUE.to_srgba_unmultiplied()
+        );
+        assert_eq!(&decoded.rgba_data[last..], &color.to_srgba_unmultiplied());
+    }
+
+    #[test]
+    fn test_canvas_clear_to_transparent() {
+        let mut canvas = Canvas::new(8, 8);
+        canvas.set_pixel(3, 4, Color32::RED).unwrap();
+        assert!(!canvas.is_empty());
+
+        canvas.clear();
+        assert!(canvas.is_empty());
+        assert_eq!(canvas.get_pixel(3, 4).unwrap(), Color32::TRANSPARENT);
+
+        canvas.clear_to(Color32::TRANSPARENT);
+        assert!(canvas.is_empty());
+        assert!(canvas.layers[0].chunks.is_empty());
+        assert_eq!(canvas.content_bounds(), None);
+    }
+
+    #[test]
+    fn test_canvas_clear_to_color() {
+        let mut canvas = Canvas::new(70, 3);
+        canvas.set_pixel(1, 1, Color32::RED).unwrap();
+
+        canvas.clear_to(Color32::BLUE);
+        assert!(!canvas.is_empty());
+        assert_eq!(canvas.get_pixel(0, 0).unwrap(), Color32::BLUE);
+        assert_eq!(canvas.get_pixel(1, 1).unwrap(), Color32::BLUE);
+        assert_eq!(canvas.get_pixel(69, 2).unwrap(), Color32::BLUE);
+        assert_eq!(canvas.content_bounds(), Some((0, 0, 69, 2)));
+
+        let data = canvas.get_region_data(0, 0, 70, 3);
+        assert!(data.chunks(4).all(|pixel| pixel == [0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_canvas_clear_to_fills_whole_chunks() {
+        let mut canvas = Canvas::new(130, 70);
+        canvas.clear_to(Color32::BLUE);
+
+        // Chunks inside the canvas are filled whole, edge chunks only up to
+        // the canvas border
+        let chunks = &canvas.layers[0].chunks;
+        assert_eq!(chunks[&(0, 0)].is_uniform(), Some(Color32::BLUE));
+        assert_eq!(chunks[&(1, 0)].is_uniform(), Some(Color32::BLUE));
+        assert_eq!(chunks[&(2, 0)].is_uniform(), None);
+        assert_eq!(chunks[&(0, 1)].is_uniform(), None);
+        assert!(chunks.values().all(|chunk| !chunk.is_empty));
+        assert_eq!(canvas.get_pixel(129, 69).unwrap(), Color32::BLUE);
+    }
+
+    #[test]
+    fn test_canvas_replace_color() {
+        let mut canvas = Canvas::new(4, 4);
+        canvas.set_pixel(0, 0, Color32::RED).unwrap();
+        canvas.set_pixel(3, 3, Color32::RED).unwrap();
+        canvas.set_pixel(1, 1, Color32::GREEN).unwrap();
+
+        assert_eq!(canvas.replace_color(Color32::RED, Color32::BLUE), 2);
+        assert_eq!(canvas.get_pixel(0, 0).unwrap(), Color32::BLUE);
+        assert_eq!(canvas.get_pixel(3, 3).unwrap(), Color32::BLUE);
+        assert_eq!(canvas.get_pixel(1, 1).unwrap(), Color32::GREEN);
+
+        // Only pixels inside the canvas count as transparent
+        assert_eq!(
+            canvas.replace_color(Color32::TRANSPARENT, Color32::WHITE),
+            13
+        );
+        assert_eq!(canvas.get_pixel(2, 0).unwrap(), Color32::WHITE);
+    }
+
+    #[test]
+    fn test_canvas_get_data_row_major() {
+        let mut canvas = Canvas::new(70, 66);
+        canvas.set_pixel(0, 0, Color32::RED).unwrap();
+        canvas.set_pixel(63, 0, Color32::GREEN).unwrap();
+        canvas.set_pixel(64, 0, Color32::BLUE).unwrap();
+        canvas.set_pixel(69, 65, Color32::WHITE).unwrap();
+        canvas.set_pixel(2, 64, Color32::RED).unwrap();
+
+        let data = canvas.get_data();
+        assert_eq!(data.len(), 70 * 66 * 4);
+
+        let pixel = |x: usize, y: usize| {
+            let index = (y * 70 + x) * 4;
+            &data[index..index + 4]
+        };
+        assert_eq!(pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(63, 0), [0, 255, 0, 255]);
+        assert_eq!(pixel(64, 0), [0, 0, 255, 255]);
+        assert_eq!(pixel(69, 65), [255, 255, 255, 255]);
+        assert_eq!(pixel(2, 64), [255, 0, 0, 255]);
+        assert_eq!(pixel(1, 0), [0, 0, 0, 0]);
+
+        let set = data.chunks(4).filter(|pixel| pixel[3] != 0).count();
+        assert_eq!(set, 5);
+    }
+
+    #[test]
+    fn test_canvas_export_roundtrip_across_chunks() {
+        let size = CHUNK_SIZE as u32 + 8;
+        let mut canvas = Canvas::new(size, size);
+
+        // An L-shape whose corner sits in the first chunk and whose arms reach
+        // into the neighbouring chunks
+        for i in 60..size {
+            canvas.set_pixel(60, i, Color32::RED).unwrap();
+            canvas.set_pixel(i, size - 1, Color32::BLUE).unwrap();
+        }
+
+        let ((width, height), data) = canvas.get_export_data();
+        let encoded = encode(width, height, &data).unwrap();
+        let decoded = decode(&encoded).unwrap();
+
+        assert_eq!((decoded.width as u32, decoded.height as u32), (size, size));
+        assert_eq!(decoded.rgba_data, data);
+
+        let mut reloaded = Canvas::new(1, 1);
+        reloaded.load_image(&decoded);
+        for y in 0..size {
+            for x in 0..size {
+                assert_eq!(
+                    reloaded.get_pixel(x, y).unwrap(),
+                    canvas.get_pixel(x, y).unwrap()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_chunk_texture_keeps_premultiplied_pixels() {
+        let half_red = Color32::from_rgba_unmultiplied(255, 0, 0, 128);
+
+        // Uploading must not premultiply the already premultiplied bytes again
+        let image = Canvas::color_image([1, 1], &[half_red]);
+        assert_eq!(image.pixels, vec![half_red]);
     }
 }
@@ -14,6 +14,12 @@ pub struct Chunk {
     pub is_empty: bool,
 }
 
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Chunk {
     pub fn new() -> Self {
         let default_pixels =
@@ -47,6 +53,29 @@ impl Chunk {
         Ok(index)
     }
 
+    /// Sets every pixel to `color`. The chunk is empty afterwards only if
+    /// `color` is transparent.
+    pub fn fill(&mut self, color: Color32) {
+        self.pixels.fill(color);
+        self.is_empty = color == Color32::TRANSPARENT;
+    }
+
+    /// The color of every pixel, if they all share one
+    pub fn is_uniform(&self) -> Option<Color32> {
+        let first = self.pixels[0];
+        self.pixels
+            .iter()
+            .all(|&pixel| pixel == first)
+            .then_some(first)
+    }
+
+    /// The pixels as row-major RGBA bytes, borrowed straight from the pixel
+    /// storage so they are always in sync with it. `Color32` keeps its
+    /// channels premultiplied.
+    pub fn rgba_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.pixels[..])
+    }
+
     pub fn get_pixel(&self, x: u8, y: u8) -> Result<Color32, ChunkError> {
         if x >= CHUNK_SIZE || y >= CHUNK_SIZE {
             return Err(ChunkError::OutOfBounds {
@@ -56,7 +85,83 @@ impl Chunk {
             });
         }
 
-        let index = (y * CHUNK_SIZE + x) as usize;
+        let index = y as usize * CHUNK_SIZE as usize + x as usize;
         Ok(self.pixels[index])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rgba_bytes_track_set_pixel() {
+        let mut chunk = Chunk::new();
+        assert!(chunk.rgba_bytes().iter().all(|&byte| byte == 0));
+        assert_eq!(
+            chunk.rgba_bytes().len(),
+            CHUNK_SIZE as usize * CHUNK_SIZE as usize * 4
+        );
+
+        let half_red = Color32::from_rgba_unmultiplied(255, 0, 0, 128);
+        chunk.set_pixel(0, 0, Color32::RED).unwrap();
+        chunk.set_pixel(5, 1, Color32::BLUE).unwrap();
+        chunk.set_pixel(63, 63, half_red).unwrap();
+        chunk.set_pixel(0, 0, Color32::GREEN).unwrap();
+
+        let bytes = chunk.rgba_bytes();
+        let pixel = |x: usize, y: usize| {
+            let index = (y * CHUNK_SIZE as usize + x) * 4;
+            &bytes[index..index + 4]
+        };
+        assert_eq!(pixel(0, 0), Color32::GREEN.to_array());
+        assert_eq!(pixel(5, 1), Color32::BLUE.to_array());
+        assert_eq!(pixel(63, 63), half_red.to_array());
+        assert_eq!(pixel(1, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_fill_sets_is_empty() {
+        let mut chunk = Chunk::new();
+        chunk.fill(Color32::RED);
+        assert!(!chunk.is_empty);
+        assert_eq!(chunk.get_pixel(63, 63).unwrap(), Color32::RED);
+
+        chunk.fill(Color32::TRANSPARENT);
+        assert!(chunk.is_empty);
+        assert_eq!(chunk.get_pixel(0, 0).unwrap(), Color32::TRANSPARENT);
+    }
+
+    #[test]
+    fn test_is_uniform() {
+        let mut chunk = Chunk::new();
+        assert_eq!(chunk.is_uniform(), Some(Color32::TRANSPARENT));
+
+        chunk.fill(Color32::BLUE);
+        assert_eq!(chunk.is_uniform(), Some(Color32::BLUE));
+
+        chunk.set_pixel(63, 63, Color32::RED).unwrap();
+        assert_eq!(chunk.is_uniform(), None);
+        chunk.set_pixel(63, 63, Color32::BLUE).unwrap();
+        assert_eq!(chunk.is_uniform(), Some(Color32::BLUE));
+    }
+
+    #[test]
+    fn test_erasing_every_pixel_empties_chunk() {
+        let mut chunk = Chunk::new();
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                chunk.set_pixel(x, y, Color32::RED).unwrap();
+            }
+        }
+        assert!(!chunk.is_empty);
+
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                assert!(!chunk.is_empty);
+                chunk.set_pixel(x, y, Color32::TRANSPARENT).unwrap();
+            }
+        }
+        assert!(chunk.is_empty);
+    }
+}
@@ -0,0 +1,87 @@
+const GRID_SPACING_KEY: &str = "editor.grid_spacing";
+const TILE_SIZE_KEY: &str = "editor.tile_size";
+
+/// Editor settings that belong to the document rather than the view and are
+/// saved as `.pxc` metadata
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DocumentSettings {
+    /// Number of cells between grid lines
+    pub grid_spacing: u32,
+    /// Size of the tiles outlined on the canvas, if any
+    pub tile_size: Option<u32>,
+}
+
+impl Default for DocumentSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DocumentSettings {
+    pub fn new() -> Self {
+        Self {
+            grid_spacing: 1,
+            tile_size: None,
+        }
+    }
+
+    pub fn to_metadata(&self) -> Vec<(String, String)> {
+        let mut metadata = vec![(GRID_SPACING_KEY.to_string(), self.grid_spacing.to_string())];
+        if let Some(tile_size) = self.tile_size {
+            metadata.push((TILE_SIZE_KEY.to_string(), tile_size.to_string()));
+        }
+        metadata
+    }
+
+    /// Restores settings from file metadata. Missing, unknown or malformed
+    /// entries fall back to the defaults.
+    pub fn from_metadata(metadata: &[(String, String)]) -> Self {
+        let read = |key: &str| {
+            metadata
+                .iter()
+                .find(|(entry_key, _)| entry_key == key)
+                .and_then(|(_, value)| value.parse::<u32>().ok())
+                .filter(|&value| value > 0)
+        };
+
+        let defaults = Self::new();
+        Self {
+            grid_spacing: read(GRID_SPACING_KEY).unwrap_or(defaults.grid_spacing),
+            tile_size: read(TILE_SIZE_KEY),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_roundtrip() {
+        let settings = DocumentSettings {
+            grid_spacing: 8,
+            tile_size: Some(16),
+        };
+        assert_eq!(
+            DocumentSettings::from_metadata(&settings.to_metadata()),
+            settings
+        );
+    }
+
+    #[test]
+    fn test_missing_or_invalid_metadata() {
+        assert_eq!(
+            DocumentSettings::from_metadata(&[]),
+            DocumentSettings::new()
+        );
+
+        let metadata = vec![
+            (GRID_SPACING_KEY.to_string(), "zero".to_string()),
+            (TILE_SIZE_KEY.to_string(), "0".to_string()),
+        ];
+        assert_eq!(
+            DocumentSettings::from_metadata(&metadata),
+            DocumentSettings::new()
+        );
+    }
+}
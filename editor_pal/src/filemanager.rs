@@ -1,9 +1,11 @@
+use eframe::egui::Color32;
 use eframe::Result;
-use image::ImageError;
-use lib_pxc::{decode, encode};
+use image::{ExtendedColorType, ImageError};
+use lib_pxc::image::encoder::EncodingError;
+use lib_pxc::{decode, decode_metadata, encode_with_metadata};
 use std::fs::File;
 use std::io::{self, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 use crate::image_source::ImageSource;
@@ -25,8 +27,58 @@ pub enum ImageHandlingError {
     #[error("Custom format decode error")]
     DecodeError(#[from] DecodeError),
 
+    #[error("Encode error: {0}")]
+    EncodeError(#[from] EncodingError),
+
     #[error("Unsupported file extension")]
     UnsupportedExtension,
+
+    #[error("Nothing to export, the canvas is empty")]
+    EmptyImage,
+}
+
+impl ImageHandlingError {
+    /// Explanation of the error suitable for a toast
+    pub fn user_message(&self) -> String {
+        match self {
+            ImageHandlingError::DialogCanceled => "No file was selected".to_string(),
+            ImageHandlingError::InvalidPath => "The file path is not valid".to_string(),
+            ImageHandlingError::IoError(err) => match err.kind() {
+                io::ErrorKind::NotFound => "The file or folder does not exist".to_string(),
+                io::ErrorKind::PermissionDenied => "Permission denied".to_string(),
+                _ => format!("Could not access the file ({err})"),
+            },
+            ImageHandlingError::ImageError(err) => format!("The image could not be read ({err})"),
+            ImageHandlingError::DecodeError(_) => {
+                "The file is not a valid .pxc image or is damaged".to_string()
+            }
+            ImageHandlingError::EncodeError(err) => {
+                format!("The image could not be encoded ({err})")
+            }
+            ImageHandlingError::UnsupportedExtension => {
+                "Unsupported file type, use .pxc, .png, .jpg, .bmp or .webp".to_string()
+            }
+            ImageHandlingError::EmptyImage => self.to_string(),
+        }
+    }
+}
+
+/// An opened image along with the document data stored in `.pxc` files
+pub struct OpenedImage {
+    pub image: Box<dyn ImageSource>,
+    /// The file's palette, only present for `.pxc` files
+    pub palette: Option<Vec<Color32>>,
+    pub metadata: Vec<(String, String)>,
+}
+
+impl OpenedImage {
+    fn standard(image: Box<dyn ImageSource>) -> Self {
+        Self {
+            image,
+            palette: None,
+            metadata: Vec::new(),
+        }
+    }
 }
 
 // Assuming you have a custom DecodeError for your format
@@ -36,57 +88,216 @@ pub enum DecodeError {
     DecodeFailed,
 }
 
-pub fn save_image(dimensions: (u32, u32), data: Vec<u8>) -> Result<(), ImageHandlingError> {
-    let path = rfd::FileDialog::new()
+pub fn pick_save_path() -> Result<PathBuf, ImageHandlingError> {
+    rfd::FileDialog::new()
+        .add_filter(
+            lib_pxc::constants::FORMAT_NAME,
+            &[lib_pxc::constants::FILE_EXT],
+        )
+        .add_filter("PNG", &["png"])
         .save_file()
-        .ok_or(ImageHandlingError::DialogCanceled)?;
+        .ok_or(ImageHandlingError::DialogCanceled)
+}
 
+/// Writes row-major RGBA data to `path`. Paths ending in `.png` are exported
+/// as PNG without the document metadata, anything else as `.pxc`.
+pub fn save_image(
+    path: &Path,
+    dimensions: (u32, u32),
+    data: Vec<u8>,
+    metadata: &[(String, String)],
+) -> Result<(), ImageHandlingError> {
     let path_str = path.to_str().ok_or(ImageHandlingError::InvalidPath)?;
 
-    let mut file = File::create(path_str)?;
+    let is_png = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+    if is_png {
+        return save_png(path, dimensions, &data);
+    }
 
     let (width, height) = dimensions;
-    let encoded_data = encode(width.try_into().unwrap(), height.try_into().unwrap(), &data)
-        .map_err(|_| DecodeError::DecodeFailed)?;
+    let encoded_data = encode_with_metadata(width, height, &data, metadata)?;
 
+    let mut file = File::create(path_str)?;
     file.write_all(&encoded_data)?;
-    println!("File saved successfully to {}", path_str);
 
     Ok(())
 }
 
-pub fn open_image() -> Result<Box<dyn ImageSource>, ImageHandlingError> {
-    let path = rfd::FileDialog::new()
+fn save_png(path: &Path, dimensions: (u32, u32), data: &[u8]) -> Result<(), ImageHandlingError> {
+    let (width, height) = dimensions;
+    if width == 0 || height == 0 {
+        return Err(ImageHandlingError::EmptyImage);
+    }
+
+    image::save_buffer(path, data, width, height, ExtendedColorType::Rgba8)?;
+    Ok(())
+}
+
+pub fn open_image() -> Result<OpenedImage, ImageHandlingError> {
+    open_image_path(&pick_open_path()?)
+}
+
+pub fn pick_open_path() -> Result<PathBuf, ImageHandlingError> {
+    rfd::FileDialog::new()
         .add_filter("Image", &["png", "jpg", "jpeg", "bmp", "webp"])
         .add_filter(
             lib_pxc::constants::FORMAT_NAME,
             &[lib_pxc::constants::FILE_EXT],
         )
         .pick_file()
-        .ok_or(ImageHandlingError::DialogCanceled)?;
+        .ok_or(ImageHandlingError::DialogCanceled)
+}
 
-    let ext = path
-        .extension()
-        .and_then(|ext| ext.to_str())
-        .ok_or(ImageHandlingError::UnsupportedExtension)?;
+/// How a file is decoded, picked from its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    /// Our own format, read with `lib_pxc::decode`
+    Pxc,
+    /// Anything the `image` crate opens
+    Raster,
+}
+
+impl ImageKind {
+    pub fn from_path(path: &Path) -> Result<Self, ImageHandlingError> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or(ImageHandlingError::UnsupportedExtension)?
+            .to_ascii_lowercase();
+
+        match ext.as_str() {
+            // The save dialog suggests `FILE_EXT`, older files use `.pxc`
+            "pxc" | lib_pxc::constants::FILE_EXT => Ok(ImageKind::Pxc),
+            "png" | "jpg" | "jpeg" | "bmp" | "webp" => Ok(ImageKind::Raster),
+            _ => Err(ImageHandlingError::UnsupportedExtension),
+        }
+    }
+}
 
-    match ext {
-        "pxc" => open_custom_image(&path),
-        "png" | "jpg" | "jpeg" | "bmp" | "webp" => open_standard_image(&path),
-        _ => Err(ImageHandlingError::UnsupportedExtension),
+pub fn open_image_path(path: &Path) -> Result<OpenedImage, ImageHandlingError> {
+    match ImageKind::from_path(path)? {
+        ImageKind::Pxc => open_custom_image(path),
+        ImageKind::Raster => open_standard_image(path).map(OpenedImage::standard),
     }
 }
 
-fn open_custom_image(path: &PathBuf) -> Result<Box<dyn ImageSource>, ImageHandlingError> {
+fn open_custom_image(path: &Path) -> Result<OpenedImage, ImageHandlingError> {
     let mut file = File::open(path)?;
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
 
     let custom_image = decode(&buffer).map_err(|_| DecodeError::DecodeFailed)?;
+    let metadata = decode_metadata(&buffer).map_err(|_| DecodeError::DecodeFailed)?;
+    let palette = custom_image
+        .palette
+        .iter()
+        .map(|&[r, g, b, a]| Color32::from_rgba_unmultiplied(r, g, b, a))
+        .collect();
 
-    Ok(Box::new(custom_image))
+    Ok(OpenedImage {
+        image: Box::new(custom_image),
+        palette: Some(palette),
+        metadata,
+    })
 }
 
-fn open_standard_image(path: &PathBuf) -> Result<Box<dyn ImageSource>, ImageHandlingError> {
+fn open_standard_image(path: &Path) -> Result<Box<dyn ImageSource>, ImageHandlingError> {
     Ok(Box::new(image::open(path)?))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_image_path_error_mapping() {
+        let dir = std::env::temp_dir();
+
+        assert!(matches!(
+            open_image_path(Path::new("image")),
+            Err(ImageHandlingError::UnsupportedExtension)
+        ));
+        assert!(matches!(
+            open_image_path(&dir.join("image.gif")),
+            Err(ImageHandlingError::UnsupportedExtension)
+        ));
+
+        let missing = dir.join(format!("pxc-editor-missing-{}.pxc", std::process::id()));
+        assert!(matches!(
+            open_image_path(&missing),
+            Err(ImageHandlingError::IoError(_))
+        ));
+
+        let corrupt = dir.join(format!("pxc-editor-corrupt-{}.pxc", std::process::id()));
+        std::fs::write(&corrupt, b"not a pxc file").unwrap();
+        let result = open_image_path(&corrupt);
+        std::fs::remove_file(&corrupt).unwrap();
+        assert!(matches!(
+            result,
+            Err(ImageHandlingError::DecodeError(DecodeError::DecodeFailed))
+        ));
+    }
+
+    #[test]
+    fn test_user_messages() {
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+        assert_eq!(
+            ImageHandlingError::from(missing).user_message(),
+            "The file or folder does not exist"
+        );
+        assert_eq!(
+            ImageHandlingError::DecodeError(DecodeError::DecodeFailed).user_message(),
+            "The file is not a valid .pxc image or is damaged"
+        );
+        assert_eq!(
+            ImageHandlingError::EmptyImage.user_message(),
+            "Nothing to export, the canvas is empty"
+        );
+    }
+
+    #[test]
+    fn test_image_kind_from_extension() {
+        let kind = |path: &str| ImageKind::from_path(Path::new(path));
+
+        assert_eq!(kind("art.pxc").unwrap(), ImageKind::Pxc);
+        assert_eq!(kind("dir/Art.PXC").unwrap(), ImageKind::Pxc);
+        assert_eq!(kind("art.pal").unwrap(), ImageKind::Pxc);
+        for raster in ["a.png", "a.jpg", "a.JPEG", "a.bmp", "a.webp"] {
+            assert_eq!(kind(raster).unwrap(), ImageKind::Raster);
+        }
+        for unsupported in ["a.gif", "a", "a.png.txt"] {
+            assert!(matches!(
+                kind(unsupported),
+                Err(ImageHandlingError::UnsupportedExtension)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_save_png_reloads() {
+        let path =
+            std::env::temp_dir().join(format!("pxc-editor-export-{}.png", std::process::id()));
+
+        let (width, height) = (3, 2);
+        let mut data = vec![0; width as usize * height as usize * 4];
+        data[(width as usize + 2) * 4..][..4].copy_from_slice(&[250, 75, 98, 255]);
+
+        save_image(&path, (width, height), data, &[]).unwrap();
+        let reloaded = image::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        let reloaded = reloaded.unwrap().to_rgba8();
+
+        assert_eq!(reloaded.dimensions(), (width, height));
+        assert_eq!(reloaded.get_pixel(2, 1).0, [250, 75, 98, 255]);
+        assert_eq!(reloaded.get_pixel(0, 0).0, [0, 0, 0, 0]);
+
+        assert!(matches!(
+            save_image(&path, (0, 0), Vec::new(), &[]),
+            Err(ImageHandlingError::EmptyImage)
+        ));
+        assert!(!path.exists());
+    }
+}
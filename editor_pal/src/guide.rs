@@ -0,0 +1,91 @@
+/// Distance in cells within which drawing snaps onto a guide
+pub const GUIDE_SNAP_DISTANCE: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GuideOrientation {
+    Horizontal,
+    Vertical,
+}
+
+/// A layout guide along a grid line. Guides are an editing aid only and are
+/// not saved with the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Guide {
+    pub orientation: GuideOrientation,
+    /// Grid line the guide sits on: a row for horizontal guides and a column
+    /// for vertical ones
+    pub position: u32,
+}
+
+impl Guide {
+    pub fn horizontal(position: u32) -> Self {
+        Self {
+            orientation: GuideOrientation::Horizontal,
+            position,
+        }
+    }
+
+    pub fn vertical(position: u32) -> Self {
+        Self {
+            orientation: GuideOrientation::Vertical,
+            position,
+        }
+    }
+}
+
+/// Moves `pos` onto the nearest guide on each axis if one is within
+/// `max_distance` cells, leaving the coordinate untouched otherwise.
+pub fn snap_to_guides(guides: &[Guide], pos: (u32, u32), max_distance: u32) -> (u32, u32) {
+    let snap_axis = |orientation: GuideOrientation, value: u32| {
+        guides
+            .iter()
+            .filter(|guide| guide.orientation == orientation)
+            .map(|guide| guide.position)
+            .filter(|&position| position.abs_diff(value) <= max_distance)
+            .min_by_key(|&position| position.abs_diff(value))
+            .unwrap_or(value)
+    };
+
+    (
+        snap_axis(GuideOrientation::Vertical, pos.0),
+        snap_axis(GuideOrientation::Horizontal, pos.1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap_to_nearby_guides() {
+        let guides = [Guide::vertical(10), Guide::horizontal(4)];
+
+        // Exactly on the guides
+        assert_eq!(
+            snap_to_guides(&guides, (10, 4), GUIDE_SNAP_DISTANCE),
+            (10, 4)
+        );
+        // Within one cell on either side
+        assert_eq!(
+            snap_to_guides(&guides, (11, 3), GUIDE_SNAP_DISTANCE),
+            (10, 4)
+        );
+        assert_eq!(
+            snap_to_guides(&guides, (9, 5), GUIDE_SNAP_DISTANCE),
+            (10, 4)
+        );
+        // Too far away to snap
+        assert_eq!(
+            snap_to_guides(&guides, (12, 7), GUIDE_SNAP_DISTANCE),
+            (12, 7)
+        );
+    }
+
+    #[test]
+    fn test_snap_picks_closest_guide() {
+        let guides = [Guide::vertical(10), Guide::vertical(12)];
+        assert_eq!(snap_to_guides(&guides, (11, 0), 2), (10, 0));
+        assert_eq!(snap_to_guides(&guides, (13, 0), 2), (12, 0));
+        assert_eq!(snap_to_guides(&[], (13, 0), 2), (13, 0));
+    }
+}
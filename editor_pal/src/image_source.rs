@@ -1,117 +1,304 @@
 use eframe::egui::Color32;
 use image::{DynamicImage, GenericImageView};
 use lib_pxc::Image;
+use thiserror::Error;
 
 use crate::chunk::{Chunk, CHUNK_SIZE};
 
-// First, let's define a trait for image sources
+/// Pixel data the canvas can load.
+///
+/// Sources hold straight (unmultiplied) RGBA, like PNG and `.pxc` files, and
+/// convert with `Color32::from_rgba_unmultiplied`. `Canvas::get_data` converts
+/// back with `Color32::to_srgba_unmultiplied`, so a pixel survives a load and
+/// save up to the precision of `Color32`'s premultiplied storage.
 pub trait ImageSource: Send + Sync {
     /// Get the dimensions of the image (width, height)
     fn dimensions(&self) -> (u32, u32);
 
-    /// Get a pixel at the specified coordinates
-    /// Returns Color32 to maintain compatibility with the editor
+    /// The pixel at (x, y), converted from unmultiplied RGBA
     fn get_pixel(&self, x: u32, y: u32) -> Color32;
 
-    /// Optional method to provide a more efficient way to load chunks directly
-    /// Default implementation uses get_pixel for each pixel in the chunk
-    fn load_chunk(&self, chunk_x: i32, chunk_y: i32) -> Option<Chunk>;
-}
+    /// Row-major pixels of the `chunk_size`×`chunk_size` chunk at
+    /// (chunk_x, chunk_y), transparent past the image edges. Returns `None`
+    /// if the chunk lies entirely outside the image.
+    fn load_chunk_pixels(
+        &self,
+        chunk_x: u32,
+        chunk_y: u32,
+        chunk_size: u32,
+    ) -> Option<Vec<Color32>> {
+        let (img_width, img_height) = self.dimensions();
+        let start_x = chunk_x.checked_mul(chunk_size)?;
+        let start_y = chunk_y.checked_mul(chunk_size)?;
 
-// Implementation for the standard image crate's DynamicImage
-impl ImageSource for DynamicImage {
-    fn dimensions(&self) -> (u32, u32) {
-        return GenericImageView::dimensions(self);
-    }
+        if chunk_size == 0 || start_x >= img_width || start_y >= img_height {
+            return None;
+        }
 
-    fn get_pixel(&self, x: u32, y: u32) -> Color32 {
-        let pixel = GenericImageView::get_pixel(self, x, y);
-        Color32::from_rgba_unmultiplied(pixel[0], pixel[1], pixel[2], pixel[3])
+        let mut pixels = Vec::with_capacity(chunk_size as usize * chunk_size as usize);
+        for y in start_y..start_y + chunk_size {
+            for x in start_x..start_x + chunk_size {
+                pixels.push(if x < img_width && y < img_height {
+                    self.get_pixel(x, y)
+                } else {
+                    Color32::TRANSPARENT
+                });
+            }
+        }
+
+        Some(pixels)
     }
 
+    /// Loads a chunk of the canvas, which is always `CHUNK_SIZE` wide
     fn load_chunk(&self, chunk_x: i32, chunk_y: i32) -> Option<Chunk> {
-        let (img_width, img_height) = ImageSource::dimensions(self);
-        let start_x = (chunk_x * CHUNK_SIZE as i32) as u32;
-        let start_y = (chunk_y * CHUNK_SIZE as i32) as u32;
+        let pixels = self.load_chunk_pixels(
+            u32::try_from(chunk_x).ok()?,
+            u32::try_from(chunk_y).ok()?,
+            CHUNK_SIZE as u32,
+        )?;
 
-        if start_x >= img_width || start_y >= img_height {
-            return None;
+        let mut chunk = Chunk::new();
+        chunk.pixels.copy_from_slice(&pixels);
+        chunk.is_empty = pixels.iter().all(|&pixel| pixel == Color32::TRANSPARENT);
+        Some(chunk)
+    }
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum RawImageError {
+    #[error("{len} bytes do not make a {width}x{height} RGBA image")]
+    InvalidLength { width: u32, height: u32, len: usize },
+}
+
+/// Raw row-major RGBA buffer, e.g. produced by a procedural generator or a
+/// test, loaded without going through the `image` crate
+pub struct RawImageSource {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl RawImageSource {
+    /// Fails unless `data` holds exactly `width * height` RGBA pixels
+    pub fn new(width: u32, height: u32, data: Vec<u8>) -> Result<Self, RawImageError> {
+        let expected = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4));
+        if expected != Some(data.len()) {
+            return Err(RawImageError::InvalidLength {
+                width,
+                height,
+                len: data.len(),
+            });
         }
 
-        let mut chunk = Chunk::new();
-        for y in 0..CHUNK_SIZE {
-            for x in 0..CHUNK_SIZE {
-                let pixel_x = start_x + x as u32;
-                let pixel_y = start_y + y as u32;
-
-                if pixel_x < img_width && pixel_y < img_height {
-                    let pixel = ImageSource::get_pixel(self, pixel_x, pixel_y);
-                    let rgba =
-                        Color32::from_rgba_premultiplied(pixel[0], pixel[1], pixel[2], pixel[3]);
-                    chunk.set_pixel(x, y, rgba).ok();
-                }
-            }
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    /// Returns the RGBA bytes of a pixel, or None if it lies outside the buffer
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
         }
 
-        Some(chunk)
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        self.data
+            .get(index..index + 4)
+            .map(|rgba| [rgba[0], rgba[1], rgba[2], rgba[3]])
+    }
+}
+
+impl ImageSource for RawImageSource {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Color32 {
+        self.pixel(x, y)
+            .map_or(Color32::TRANSPARENT, |[r, g, b, a]| {
+                Color32::from_rgba_unmultiplied(r, g, b, a)
+            })
+    }
+}
+
+// Implementation for the standard image crate's DynamicImage
+impl ImageSource for DynamicImage {
+    fn dimensions(&self) -> (u32, u32) {
+        GenericImageView::dimensions(self)
+    }
+
+    fn get_pixel(&self, x: u32, y: u32) -> Color32 {
+        let pixel = GenericImageView::get_pixel(self, x, y);
+        Color32::from_rgba_unmultiplied(pixel[0], pixel[1], pixel[2], pixel[3])
     }
 }
 
 impl ImageSource for Image {
     fn dimensions(&self) -> (u32, u32) {
-        (self.width.into(), self.height.into())
+        (self.width, self.height)
     }
 
     fn get_pixel(&self, x: u32, y: u32) -> Color32 {
-        if x >= self.width.into() || y >= self.height.into() {
-            return Color32::BLACK;
-        }
-        let x = x as u16;
-        let y = y as u16;
-        let index = (y * self.width + x) as usize;
-
-        println!(
-            "Pixel ({}, {}): rgba = ({}, {}, {}, {})",
-            x,
-            y,
-            self.rgba_data[index],
-            self.rgba_data[index + 1],
-            self.rgba_data[index + 2],
-            self.rgba_data[index + 3]
+        // Missing bytes in a short buffer are empty, not opaque black
+        Image::get_pixel(self, x, y).map_or(Color32::TRANSPARENT, |[r, g, b, a]| {
+            Color32::from_rgba_unmultiplied(r, g, b, a)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::Canvas;
+
+    #[test]
+    fn test_raw_image_source_load_into_canvas() {
+        #[rustfmt::skip]
+        let data = vec![
+            255, 0, 0, 255,   0, 255, 0, 255,   0, 0, 255, 255,
+            0, 0, 0, 0,       255, 255, 255, 255, 0, 0, 0, 0,
+            0, 0, 255, 255,   0, 255, 0, 255,   255, 0, 0, 255,
+        ];
+        let source = RawImageSource::new(3, 3, data).unwrap();
+
+        assert_eq!(source.pixel(1, 1), Some([255, 255, 255, 255]));
+        assert_eq!(source.pixel(3, 0), None);
+        assert_eq!(ImageSource::get_pixel(&source, 0, 3), Color32::TRANSPARENT);
+
+        let mut canvas = Canvas::new(32, 32);
+        canvas.load_image(&source);
+
+        assert_eq!(canvas.dimensions(), (3, 3));
+        assert_eq!(canvas.get_pixel(0, 0).unwrap(), Color32::RED);
+        assert_eq!(canvas.get_pixel(1, 0).unwrap(), Color32::GREEN);
+        assert_eq!(canvas.get_pixel(2, 0).unwrap(), Color32::BLUE);
+        assert_eq!(canvas.get_pixel(0, 1).unwrap(), Color32::TRANSPARENT);
+        assert_eq!(canvas.get_pixel(1, 1).unwrap(), Color32::WHITE);
+        assert_eq!(canvas.get_pixel(2, 2).unwrap(), Color32::RED);
+    }
+
+    #[test]
+    fn test_raw_image_source_checks_length() {
+        assert_eq!(
+            RawImageSource::new(2, 2, vec![0; 15]).err(),
+            Some(RawImageError::InvalidLength {
+                width: 2,
+                height: 2,
+                len: 15
+            })
         );
+        assert!(RawImageSource::new(2, 2, vec![0; 20]).is_err());
+        assert!(RawImageSource::new(u32::MAX, u32::MAX, Vec::new()).is_err());
 
-        Color32::from_rgba_unmultiplied(
-            self.rgba_data[index],
-            self.rgba_data[index + 1],
-            self.rgba_data[index + 2],
-            self.rgba_data[index + 3],
-        )
+        let empty = RawImageSource::new(0, 5, Vec::new()).unwrap();
+        assert_eq!(empty.dimensions(), (0, 5));
     }
 
-    fn load_chunk(&self, chunk_x: i32, chunk_y: i32) -> Option<Chunk> {
-        let start_x = (chunk_x * CHUNK_SIZE as i32) as u32;
-        let start_y = (chunk_y * CHUNK_SIZE as i32) as u32;
+    #[test]
+    fn test_image_get_pixel_uses_rgba_stride() {
+        #[rustfmt::skip]
+        let data = vec![
+            255, 0, 0, 255,   0, 255, 0, 255,
+            0, 0, 255, 255,   255, 255, 255, 255,
+        ];
+        let palette = vec![
+            [255, 0, 0, 255],
+            [0, 255, 0, 255],
+            [0, 0, 255, 255],
+            [255, 255, 255, 255],
+        ];
+        let image = Image::new(2, 2, 4, palette, data);
 
-        if start_x >= self.width.into() || start_y >= self.height.into() {
-            return None;
-        }
+        assert_eq!(ImageSource::get_pixel(&image, 1, 0), Color32::GREEN);
+    }
 
-        println!("Loading image ({:?})", self.rgba_data);
+    #[test]
+    fn test_decoded_image_pixels() {
+        #[rustfmt::skip]
+        let data = [
+            255, 0, 0, 255,   0, 255, 0, 255,
+            0, 0, 255, 255,   0, 0, 0, 0,
+        ];
+        let image = lib_pxc::decode(&lib_pxc::encode(2, 2, &data).unwrap()).unwrap();
 
-        let mut chunk = Chunk::new();
-        for y in 0..CHUNK_SIZE {
-            for x in 0..CHUNK_SIZE {
-                let pixel_x = start_x + x as u32;
-                let pixel_y = start_y + y as u32;
-
-                if pixel_x < self.width.into() && pixel_y < self.height.into() {
-                    let color = self.get_pixel(pixel_x, pixel_y);
-                    // println!("Pixel ({}, {}): {:?}", pixel_x, pixel_y, color);
-                    chunk.set_pixel(x, y, color).ok();
+        assert_eq!(ImageSource::get_pixel(&image, 0, 0), Color32::RED);
+        assert_eq!(ImageSource::get_pixel(&image, 1, 0), Color32::GREEN);
+        assert_eq!(ImageSource::get_pixel(&image, 0, 1), Color32::BLUE);
+        assert_eq!(ImageSource::get_pixel(&image, 1, 1), Color32::TRANSPARENT);
+    }
+
+    #[test]
+    fn test_image_truncated_data_is_transparent() {
+        // 2x2 image that only carries data for its first pixel
+        let image = Image::new(2, 2, 1, vec![[255, 0, 0, 255]], vec![255, 0, 0, 255]);
+
+        assert_eq!(ImageSource::get_pixel(&image, 0, 0), Color32::RED);
+        assert_eq!(ImageSource::get_pixel(&image, 0, 1), Color32::TRANSPARENT);
+        assert_eq!(ImageSource::get_pixel(&image, 1, 1), Color32::TRANSPARENT);
+        assert_eq!(ImageSource::get_pixel(&image, 5, 5), Color32::TRANSPARENT);
+
+        let mut canvas = Canvas::new(32, 32);
+        canvas.load_image(&image);
+
+        assert_eq!(canvas.get_pixel(0, 0).unwrap(), Color32::RED);
+        assert_eq!(canvas.get_pixel(0, 1).unwrap(), Color32::TRANSPARENT);
+        assert_eq!(canvas.get_pixel(1, 1).unwrap(), Color32::TRANSPARENT);
+    }
+
+    #[test]
+    fn test_half_alpha_pixels_survive_load_and_save() {
+        #[rustfmt::skip]
+        let data = vec![
+            255, 0, 0, 128,     250, 75, 98, 128,
+            255, 255, 255, 128, 0, 0, 0, 0,
+        ];
+        let png = DynamicImage::ImageRgba8(image::RgbaImage::from_raw(2, 2, data.clone()).unwrap());
+        let pxc = lib_pxc::decode(&lib_pxc::encode(2, 2, &data).unwrap()).unwrap();
+
+        let mut canvas = Canvas::new(1, 1);
+        canvas.load_image(&png);
+        assert_eq!(canvas.get_data(), data);
+
+        canvas.load_image(&pxc);
+        assert_eq!(canvas.get_data(), data);
+    }
+
+    #[test]
+    fn test_load_chunk_pixels_any_chunk_size() {
+        // 5x3 image whose pixels encode their own coordinates
+        let image = DynamicImage::ImageRgba8(image::RgbaImage::from_fn(5, 3, |x, y| {
+            image::Rgba([x as u8, y as u8, 0, 255])
+        }));
+
+        for chunk_size in [2u32, 4] {
+            let mut covered = 0;
+            for chunk_y in 0..3u32.div_ceil(chunk_size) {
+                for chunk_x in 0..5u32.div_ceil(chunk_size) {
+                    let pixels = image
+                        .load_chunk_pixels(chunk_x, chunk_y, chunk_size)
+                        .unwrap();
+                    assert_eq!(pixels.len(), (chunk_size * chunk_size) as usize);
+
+                    for (i, &pixel) in pixels.iter().enumerate() {
+                        let x = chunk_x * chunk_size + i as u32 % chunk_size;
+                        let y = chunk_y * chunk_size + i as u32 / chunk_size;
+                        if x < 5 && y < 3 {
+                            assert_eq!(pixel, Color32::from_rgb(x as u8, y as u8, 0));
+                            covered += 1;
+                        } else {
+                            assert_eq!(pixel, Color32::TRANSPARENT);
+                        }
+                    }
                 }
             }
-        }
+            assert_eq!(covered, 5 * 3, "chunk size {chunk_size}");
 
-        Some(chunk)
+            let past_edge = 5u32.div_ceil(chunk_size);
+            assert!(image.load_chunk_pixels(past_edge, 0, chunk_size).is_none());
+        }
     }
 }
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+
+use crate::chunk::{Chunk, CHUNK_SIZE};
+use eframe::egui::Color32;
+
+/// A named stack entry of the canvas with its own chunk storage
+pub struct Layer {
+    pub name: String,
+    pub visible: bool,
+    /// Multiplies the alpha of every pixel when compositing, in `0.0..=1.0`
+    pub opacity: f32,
+    pub(crate) chunks: HashMap<(u32, u32), Chunk>,
+}
+
+impl Layer {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+            opacity: 1.0,
+            chunks: HashMap::new(),
+        }
+    }
+
+    /// Pixel at canvas coordinates (x, y), transparent where no chunk exists
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color32 {
+        let chunk_coords = (x / CHUNK_SIZE as u32, y / CHUNK_SIZE as u32);
+        let (local_x, local_y) = (x % CHUNK_SIZE as u32, y % CHUNK_SIZE as u32);
+        self.chunks
+            .get(&chunk_coords)
+            .map_or(Color32::TRANSPARENT, |chunk| {
+                chunk.pixels[local_y as usize * CHUNK_SIZE as usize + local_x as usize]
+            })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.values().all(|chunk| chunk.is_empty)
+    }
+}
+
+/// Composites `top`, with its alpha scaled by `opacity`, over `bottom`
+pub fn blend(bottom: Color32, top: Color32, opacity: f32) -> Color32 {
+    let top_alpha = top.a() as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+    if top_alpha <= 0.0 {
+        return bottom;
+    }
+    if opacity >= 1.0 && (top.a() == 255 || bottom.a() == 0) {
+        return top;
+    }
+
+    let bottom_alpha = bottom.a() as f32 / 255.0;
+    let alpha = top_alpha + bottom_alpha * (1.0 - top_alpha);
+    let top = top.to_srgba_unmultiplied();
+    let bottom = bottom.to_srgba_unmultiplied();
+    let channel = |i: usize| {
+        let value = (top[i] as f32 * top_alpha
+            + bottom[i] as f32 * bottom_alpha * (1.0 - top_alpha))
+            / alpha;
+        value.round() as u8
+    };
+    Color32::from_rgba_unmultiplied(
+        channel(0),
+        channel(1),
+        channel(2),
+        (alpha * 255.0).round() as u8,
+    )
+}
@@ -0,0 +1,19 @@
+pub mod app;
+pub mod canvas;
+pub mod chunk;
+pub mod document;
+pub mod filemanager;
+pub mod guide;
+pub mod image_source;
+pub mod layer;
+pub mod minimap;
+pub mod palette;
+pub mod palette_file;
+pub mod recent_files;
+pub mod selection;
+pub mod shapes;
+pub mod size_dialog;
+pub mod toast;
+pub mod tool;
+pub mod undo;
+pub mod viewport;
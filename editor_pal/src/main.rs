@@ -1,18 +1,18 @@
-mod app;
-mod chunk;
-mod image_source;
-
-mod canvas;
-mod filemanager;
-mod palette;
-mod viewport;
-
-use app::PixelEditor;
+use pxc_editor::app::{PixelEditor, PixelEditorBuilder};
 
 fn main() -> Result<(), eframe::Error> {
     lib_pxc::init_logging();
 
-    let app = PixelEditor::new();
+    let mut builder = PixelEditorBuilder::new();
+    if let Some(path) = std::env::args().nth(1) {
+        builder = builder.file(path);
+    }
+
+    let app = builder.build().unwrap_or_else(|err| {
+        let mut editor = PixelEditor::new();
+        editor.notify_error(format!("Load failed: {}", err.user_message()));
+        editor
+    });
     let native_options = eframe::NativeOptions::default();
 
     eframe::run_native(
@@ -0,0 +1,213 @@
+use eframe::egui::{self, ColorImage, Pos2, Rect, TextureHandle, Vec2};
+
+use crate::canvas::Canvas;
+
+/// Longest side of the overview, in points
+pub const MINIMAP_SIZE: f32 = 180.0;
+/// Seconds between rebuilds of the overview image
+pub const MINIMAP_REFRESH_INTERVAL: f64 = 0.5;
+
+/// Downscaled overview of the whole canvas, rebuilt at most every
+/// `MINIMAP_REFRESH_INTERVAL` seconds
+#[derive(Default)]
+pub struct Minimap {
+    texture: Option<TextureHandle>,
+    /// egui time of the last rebuild
+    last_refresh: Option<f64>,
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn needs_refresh(&self, time: f64) -> bool {
+        self.last_refresh
+            .is_none_or(|last| time - last >= MINIMAP_REFRESH_INTERVAL || time < last)
+    }
+
+    /// Forces a rebuild on the next frame, e.g. after loading an image
+    pub fn invalidate(&mut self) {
+        self.last_refresh = None;
+    }
+
+    /// Returns the overview texture, rebuilding it from `canvas` if it is due
+    pub fn texture(&mut self, canvas: &Canvas, ctx: &egui::Context, time: f64) -> &TextureHandle {
+        if self.texture.is_none() || self.needs_refresh(time) {
+            let image = downscale(canvas, MINIMAP_SIZE as u32);
+            match &mut self.texture {
+                Some(texture) => texture.set(image, egui::TextureOptions::NEAREST),
+                None => {
+                    self.texture =
+                        Some(ctx.load_texture("minimap", image, egui::TextureOptions::NEAREST))
+                }
+            }
+            self.last_refresh = Some(time);
+        }
+        self.texture.as_ref().unwrap()
+    }
+}
+
+/// Composites the canvas with `Canvas::get_data` and shrinks it by nearest
+/// neighbour sampling so its longest side is at most `max_size` pixels
+pub fn downscale(canvas: &Canvas, max_size: u32) -> ColorImage {
+    let (width, height) = canvas.dimensions();
+    if width == 0 || height == 0 {
+        return ColorImage::new([1, 1], egui::Color32::TRANSPARENT);
+    }
+
+    let scale = (width.max(height) as f32 / max_size.max(1) as f32).max(1.0);
+    let out_width = ((width as f32 / scale).round() as usize).max(1);
+    let out_height = ((height as f32 / scale).round() as usize).max(1);
+
+    let data = canvas.get_data();
+    let mut pixels = Vec::with_capacity(out_width * out_height * 4);
+    for y in 0..out_height {
+        let source_y = ((y as f32 * scale) as usize).min(height as usize - 1);
+        for x in 0..out_width {
+            let source_x = ((x as f32 * scale) as usize).min(width as usize - 1);
+            let index = (source_y * width as usize + source_x) * 4;
+            pixels.extend_from_slice(&data[index..index + 4]);
+        }
+    }
+    ColorImage::from_rgba_unmultiplied([out_width, out_height], &pixels)
+}
+
+/// Size of the overview: the canvas aspect ratio fitted into a square of
+/// side `max_size`
+pub fn minimap_size(canvas_dimensions: (u32, u32), max_size: f32) -> Vec2 {
+    let (width, height) = canvas_dimensions;
+    if width == 0 || height == 0 {
+        return Vec2::splat(max_size);
+    }
+    let scale = max_size / width.max(height) as f32;
+    Vec2::new(width as f32 * scale, height as f32 * scale)
+}
+
+/// Part of `minimap_rect` showing what the viewport shows, clipped to the
+/// canvas.
+///
+/// The canvas is drawn centered on the viewport center plus `pan_offset`,
+/// with cells `cell_size` points wide, see `ViewportInfo::cell_size`.
+pub fn viewport_rect(
+    canvas_dimensions: (u32, u32),
+    cell_size: f32,
+    pan_offset: Vec2,
+    viewport_size: Vec2,
+    minimap_rect: Rect,
+) -> Rect {
+    let canvas_size = Vec2::new(canvas_dimensions.0 as f32, canvas_dimensions.1 as f32);
+    // Viewport corners in cells from the canvas top left
+    let to_cells = |corner: Vec2| (corner - pan_offset) / cell_size + canvas_size / 2.0;
+    let min = to_cells(-viewport_size / 2.0);
+    let max = to_cells(viewport_size / 2.0);
+
+    let scale = minimap_rect.size() / canvas_size;
+    Rect::from_min_max(
+        minimap_rect.min + min * scale,
+        minimap_rect.min + max * scale,
+    )
+    .intersect(minimap_rect)
+}
+
+/// Pan offset that centers the viewport on the canvas point shown at `pos`
+/// in `minimap_rect`
+pub fn pan_offset_for(
+    canvas_dimensions: (u32, u32),
+    cell_size: f32,
+    minimap_rect: Rect,
+    pos: Pos2,
+) -> Vec2 {
+    let canvas_size = Vec2::new(canvas_dimensions.0 as f32, canvas_dimensions.1 as f32);
+    let fraction =
+        ((pos - minimap_rect.min) / minimap_rect.size()).clamp(Vec2::ZERO, Vec2::splat(1.0));
+    -(fraction - Vec2::splat(0.5)) * canvas_size * cell_size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::CELL_SIZE;
+    use eframe::egui::Color32;
+
+    fn minimap_rect() -> Rect {
+        Rect::from_min_size(Pos2::new(10.0, 20.0), Vec2::new(100.0, 50.0))
+    }
+
+    #[test]
+    fn test_viewport_rect_maps_visible_region() {
+        // 200x100 cells of 20px at zoom 1 is 4000x2000 points; an 800x600
+        // viewport sees 40x30 cells around the canvas center
+        let rect = viewport_rect(
+            (200, 100),
+            CELL_SIZE,
+            Vec2::ZERO,
+            Vec2::new(800.0, 600.0),
+            minimap_rect(),
+        );
+        assert_eq!(
+            rect,
+            Rect::from_center_size(Pos2::new(60.0, 45.0), Vec2::new(20.0, 15.0))
+        );
+
+        // Panning the canvas right by 20 cells shows cells further left
+        let rect = viewport_rect(
+            (200, 100),
+            CELL_SIZE,
+            Vec2::new(400.0, 0.0),
+            Vec2::new(800.0, 600.0),
+            minimap_rect(),
+        );
+        assert_eq!(rect.center(), Pos2::new(50.0, 45.0));
+
+        // Zoomed out past the whole canvas, the rect covers the minimap
+        let rect = viewport_rect(
+            (200, 100),
+            0.1 * CELL_SIZE,
+            Vec2::ZERO,
+            Vec2::new(800.0, 600.0),
+            minimap_rect(),
+        );
+        assert_eq!(rect, minimap_rect());
+    }
+
+    #[test]
+    fn test_pan_offset_for_recenters_viewport() {
+        let dims = (200, 100);
+        let view = Vec2::new(800.0, 600.0);
+        let click = Pos2::new(85.0, 30.0);
+
+        let pan = pan_offset_for(dims, 2.0 * CELL_SIZE, minimap_rect(), click);
+        let rect = viewport_rect(dims, 2.0 * CELL_SIZE, pan, view, minimap_rect());
+        assert!((rect.center() - click).length() < 1e-3);
+        assert_eq!(
+            pan_offset_for(
+                dims,
+                2.0 * CELL_SIZE,
+                minimap_rect(),
+                minimap_rect().center()
+            ),
+            Vec2::ZERO
+        );
+    }
+
+    #[test]
+    fn test_downscale_and_throttle() {
+        let mut canvas = Canvas::new(400, 200);
+        canvas.set_pixel(0, 0, Color32::RED).unwrap();
+        let image = downscale(&canvas, 100);
+        assert_eq!(image.size, [100, 50]);
+        assert_eq!(image.pixels[0], Color32::RED);
+        assert_eq!(image.pixels[1], Color32::TRANSPARENT);
+
+        assert_eq!(minimap_size((400, 200), 100.0), Vec2::new(100.0, 50.0));
+
+        let mut minimap = Minimap::new();
+        assert!(minimap.needs_refresh(0.0));
+        minimap.last_refresh = Some(1.0);
+        assert!(!minimap.needs_refresh(1.2));
+        assert!(minimap.needs_refresh(1.0 + MINIMAP_REFRESH_INTERVAL));
+        minimap.invalidate();
+        assert!(minimap.needs_refresh(1.2));
+    }
+}
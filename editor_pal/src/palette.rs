@@ -1,11 +1,16 @@
 use eframe::egui::Color32;
 
-#[derive(Default)]
 pub struct Palette {
     colors: Vec<Color32>,
     current_color: usize,
 }
 
+impl Default for Palette {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Palette {
     pub fn new() -> Self {
         Self {
@@ -14,6 +19,34 @@ impl Palette {
         }
     }
 
+    /// Creates a palette from the given colors, keeping at most 256 of them
+    pub fn from_colors(mut colors: Vec<Color32>) -> Self {
+        if colors.is_empty() {
+            return Self::new();
+        }
+        colors.truncate(256);
+
+        Self {
+            colors,
+            current_color: 0,
+        }
+    }
+
+    /// Creates a palette from unmultiplied RGBA colors, like the palette of a
+    /// decoded image, keeping at most 256 of them
+    pub fn from_rgba(colors: &[[u8; 4]]) -> Self {
+        Self::from_colors(
+            colors
+                .iter()
+                .map(|&[r, g, b, a]| Color32::from_rgba_unmultiplied(r, g, b, a))
+                .collect(),
+        )
+    }
+
+    pub fn colors(&self) -> &[Color32] {
+        &self.colors
+    }
+
     pub fn get_palette_length(&self) -> usize {
         self.colors.len()
     }
@@ -25,9 +58,35 @@ impl Palette {
     }
 
     pub fn remove_color(&mut self) {
-        if self.colors.len() > 1 {
-            self.colors.remove(self.current_color);
+        self.delete_color(self.current_color);
+    }
+
+    /// Inserts a copy of the color at `index` right after it, returning the
+    /// index of the copy
+    pub fn duplicate_color(&mut self, index: usize) -> Option<usize> {
+        if index >= self.colors.len() || self.colors.len() >= 256 {
+            return None;
         }
+
+        self.colors.insert(index + 1, self.colors[index]);
+        if self.current_color > index {
+            self.current_color += 1;
+        }
+        Some(index + 1)
+    }
+
+    /// Removes the color at `index`, keeping the current color pointing at the
+    /// same entry where possible. The last remaining color is never removed.
+    pub fn delete_color(&mut self, index: usize) -> Option<Color32> {
+        if index >= self.colors.len() || self.colors.len() <= 1 {
+            return None;
+        }
+
+        let color = self.colors.remove(index);
+        if self.current_color > index || self.current_color == self.colors.len() {
+            self.current_color -= 1;
+        }
+        Some(color)
     }
 
     pub fn get_current_color(&self) -> Color32 {
@@ -65,3 +124,62 @@ impl Palette {
         self.current_color = index;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_color() {
+        let mut palette = Palette::from_colors(vec![Color32::RED, Color32::GREEN, Color32::BLUE]);
+        palette.switch_color(2);
+
+        assert_eq!(palette.duplicate_color(0), Some(1));
+        assert_eq!(palette.get_palette_length(), 4);
+        assert_eq!(palette.get_color(1), Some(Color32::RED));
+        // The selection follows the color it pointed at
+        assert_eq!(palette.get_current_color_index(), 3);
+        assert_eq!(palette.get_current_color(), Color32::BLUE);
+
+        assert_eq!(palette.duplicate_color(4), None);
+    }
+
+    #[test]
+    fn test_from_rgba() {
+        let colors = vec![[255, 0, 0, 255], [0, 0, 255, 255], [0, 255, 0, 128]];
+        let palette = Palette::from_rgba(&colors);
+        assert_eq!(palette.get_palette_length(), 3);
+        assert_eq!(palette.get_color(0), Some(Color32::RED));
+        assert_eq!(palette.get_color(1), Some(Color32::BLUE));
+        assert_eq!(
+            palette.get_color(2),
+            Some(Color32::from_rgba_unmultiplied(0, 255, 0, 128))
+        );
+        assert_eq!(palette.get_current_color_index(), 0);
+
+        // Truncated to 256 colors
+        let many: Vec<[u8; 4]> = (0..300u32)
+            .map(|i| [i as u8, (i >> 8) as u8, 0, 255])
+            .collect();
+        let palette = Palette::from_rgba(&many);
+        assert_eq!(palette.get_palette_length(), 256);
+        assert_eq!(palette.get_color(255), Some(Color32::from_rgb(255, 0, 0)));
+    }
+
+    #[test]
+    fn test_delete_color() {
+        let mut palette = Palette::from_colors(vec![Color32::RED, Color32::GREEN, Color32::BLUE]);
+        palette.switch_color(2);
+
+        assert_eq!(palette.delete_color(0), Some(Color32::RED));
+        assert_eq!(palette.get_current_color_index(), 1);
+        assert_eq!(palette.get_current_color(), Color32::BLUE);
+
+        // Deleting the selected last color moves the selection back
+        assert_eq!(palette.delete_color(1), Some(Color32::BLUE));
+        assert_eq!(palette.get_current_color(), Color32::GREEN);
+
+        assert_eq!(palette.delete_color(0), None);
+        assert_eq!(palette.get_palette_length(), 1);
+    }
+}
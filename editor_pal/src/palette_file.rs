@@ -0,0 +1,280 @@
+use eframe::egui::Color32;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Header line of GIMP palette files
+const GPL_HEADER: &str = "GIMP Palette";
+
+#[derive(Error, Debug)]
+pub enum PaletteFileError {
+    #[error("File dialog was canceled")]
+    DialogCanceled,
+
+    #[error("IO error: {0}")]
+    IoError(#[from] io::Error),
+
+    #[error("Unsupported palette extension, expected .gpl or .json")]
+    UnsupportedExtension,
+
+    #[error("Invalid color on line {0} of the GIMP palette")]
+    InvalidGplLine(usize),
+
+    #[error("Invalid JSON palette, expected an array of hex color strings")]
+    InvalidJson,
+
+    #[error("Invalid hex color {0:?}")]
+    InvalidHexColor(String),
+
+    #[error("The palette file has no colors")]
+    Empty,
+}
+
+/// Palette file formats, picked from the extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// GIMP `.gpl` text palette, RGB only
+    Gpl,
+    /// JSON array of `#rrggbbaa` strings
+    Json,
+}
+
+impl PaletteFormat {
+    pub fn from_path(path: &Path) -> Result<Self, PaletteFileError> {
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or(PaletteFileError::UnsupportedExtension)?
+            .to_ascii_lowercase();
+
+        match ext.as_str() {
+            "gpl" => Ok(PaletteFormat::Gpl),
+            "json" => Ok(PaletteFormat::Json),
+            _ => Err(PaletteFileError::UnsupportedExtension),
+        }
+    }
+}
+
+pub fn pick_palette_save_path() -> Result<PathBuf, PaletteFileError> {
+    rfd::FileDialog::new()
+        .add_filter("GIMP Palette", &["gpl"])
+        .add_filter("JSON", &["json"])
+        .save_file()
+        .ok_or(PaletteFileError::DialogCanceled)
+}
+
+pub fn pick_palette_open_path() -> Result<PathBuf, PaletteFileError> {
+    rfd::FileDialog::new()
+        .add_filter("Palette", &["gpl", "json"])
+        .pick_file()
+        .ok_or(PaletteFileError::DialogCanceled)
+}
+
+/// Writes `colors` to `path` in the format its extension names
+pub fn save_palette(path: &Path, colors: &[Color32]) -> Result<(), PaletteFileError> {
+    let text = match PaletteFormat::from_path(path)? {
+        PaletteFormat::Gpl => {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            to_gpl(colors, &name)
+        }
+        PaletteFormat::Json => to_json(colors),
+    };
+    fs::write(path, text)?;
+    Ok(())
+}
+
+/// Reads the colors of the palette file at `path`
+pub fn load_palette(path: &Path) -> Result<Vec<Color32>, PaletteFileError> {
+    let format = PaletteFormat::from_path(path)?;
+    let text = fs::read_to_string(path)?;
+    let colors = match format {
+        PaletteFormat::Gpl => parse_gpl(&text)?,
+        PaletteFormat::Json => parse_json(&text)?,
+    };
+    if colors.is_empty() {
+        return Err(PaletteFileError::Empty);
+    }
+    Ok(colors)
+}
+
+/// Formats `colors` as a GIMP palette named `name`. Alpha is dropped, each
+/// color is named after its hex code.
+pub fn to_gpl(colors: &[Color32], name: &str) -> String {
+    let mut text = format!("{GPL_HEADER}\nName: {name}\n#\n");
+    for color in colors {
+        let [r, g, b, _] = color.to_srgba_unmultiplied();
+        text.push_str(&format!("{r:3} {g:3} {b:3}\t#{r:02x}{g:02x}{b:02x}\n"));
+    }
+    text
+}
+
+/// Parses the `R G B name` lines of a GIMP palette as opaque colors,
+/// skipping the header, `Name:` and `Columns:` fields, comments and blank
+/// lines
+pub fn parse_gpl(text: &str) -> Result<Vec<Color32>, PaletteFileError> {
+    let mut colors = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let skipped = line.is_empty()
+            || line.starts_with('#')
+            || line == GPL_HEADER
+            || line.starts_with("Name:")
+            || line.starts_with("Columns:");
+        if skipped {
+            continue;
+        }
+
+        let invalid = || PaletteFileError::InvalidGplLine(number + 1);
+        let mut channels = line.split_whitespace().map(|channel| channel.parse::<u8>());
+        let mut channel = || channels.next().and_then(Result::ok).ok_or_else(invalid);
+        let (r, g, b) = (channel()?, channel()?, channel()?);
+        colors.push(Color32::from_rgb(r, g, b));
+    }
+    Ok(colors)
+}
+
+/// Formats `colors` as a JSON array of `#rrggbbaa` strings, unmultiplied
+pub fn to_json(colors: &[Color32]) -> String {
+    let entries: Vec<String> = colors
+        .iter()
+        .map(|color| {
+            let [r, g, b, a] = color.to_srgba_unmultiplied();
+            format!("\"#{r:02x}{g:02x}{b:02x}{a:02x}\"")
+        })
+        .collect();
+    format!("[{}]\n", entries.join(", "))
+}
+
+/// Parses a JSON array of `#rrggbb` or `#rrggbbaa` strings
+pub fn parse_json(text: &str) -> Result<Vec<Color32>, PaletteFileError> {
+    let inner = text
+        .trim()
+        .strip_prefix('[')
+        .and_then(|text| text.strip_suffix(']'))
+        .ok_or(PaletteFileError::InvalidJson)?
+        .trim();
+    if inner.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Hex colors hold no commas or quotes, so splitting on commas is safe
+    inner
+        .split(',')
+        .map(|entry| {
+            let hex = entry
+                .trim()
+                .strip_prefix('"')
+                .and_then(|entry| entry.strip_suffix('"'))
+                .ok_or(PaletteFileError::InvalidJson)?;
+            parse_hex_color(hex)
+        })
+        .collect()
+}
+
+fn parse_hex_color(text: &str) -> Result<Color32, PaletteFileError> {
+    let invalid = || PaletteFileError::InvalidHexColor(text.to_string());
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return Err(invalid());
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    let alpha = if hex.len() == 8 { channel(6)? } else { 255 };
+    Ok(Color32::from_rgba_unmultiplied(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+        alpha,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors() -> Vec<Color32> {
+        vec![
+            Color32::RED,
+            Color32::from_rgb(18, 52, 86),
+            Color32::from_rgba_unmultiplied(0, 255, 0, 128),
+            Color32::TRANSPARENT,
+        ]
+    }
+
+    #[test]
+    fn test_gpl_roundtrip_drops_alpha() {
+        let text = to_gpl(&colors(), "test");
+        assert!(text.starts_with("GIMP Palette\nName: test\n"));
+
+        let parsed = parse_gpl(&text).unwrap();
+        let expected: Vec<Color32> = colors()
+            .iter()
+            .map(|color| {
+                let [r, g, b, _] = color.to_srgba_unmultiplied();
+                Color32::from_rgb(r, g, b)
+            })
+            .collect();
+        assert_eq!(parsed, expected);
+    }
+
+    #[test]
+    fn test_parse_gpl_skips_header_and_comments() {
+        let text = "GIMP Palette\nName: Test\nColumns: 4\n# a comment\n\n\
+                    255 0 0\tRed\n  0 128 255 Sky blue\n";
+        assert_eq!(
+            parse_gpl(text).unwrap(),
+            vec![Color32::RED, Color32::from_rgb(0, 128, 255)]
+        );
+
+        assert!(matches!(
+            parse_gpl("GIMP Palette\n255 0\n"),
+            Err(PaletteFileError::InvalidGplLine(2))
+        ));
+        assert!(matches!(
+            parse_gpl("256 0 0 Too bright\n"),
+            Err(PaletteFileError::InvalidGplLine(1))
+        ));
+    }
+
+    #[test]
+    fn test_json_roundtrip_keeps_alpha() {
+        let text = to_json(&colors());
+        assert!(text.starts_with(r##"["#ff0000ff", "#123456ff", "#00ff0080""##));
+        assert_eq!(parse_json(&text).unwrap(), colors());
+
+        assert_eq!(
+            parse_json(" [ \"00ff00\" ,\"#0000ff80\" ] ").unwrap(),
+            vec![
+                Color32::GREEN,
+                Color32::from_rgba_unmultiplied(0, 0, 255, 128)
+            ]
+        );
+        assert_eq!(parse_json("[]").unwrap(), vec![]);
+        assert!(matches!(
+            parse_json("{\"colors\": []}"),
+            Err(PaletteFileError::InvalidJson)
+        ));
+        assert!(matches!(
+            parse_json("[\"#12345\"]"),
+            Err(PaletteFileError::InvalidHexColor(_))
+        ));
+    }
+
+    #[test]
+    fn test_save_and_load_by_extension() {
+        let dir = std::env::temp_dir();
+        for ext in ["gpl", "json"] {
+            let path = dir.join(format!("pxc-editor-palette-{}.{ext}", std::process::id()));
+            save_palette(&path, &[Color32::RED, Color32::BLUE]).unwrap();
+            let loaded = load_palette(&path);
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(loaded.unwrap(), vec![Color32::RED, Color32::BLUE]);
+        }
+
+        assert!(matches!(
+            save_palette(&dir.join("palette.txt"), &[Color32::RED]),
+            Err(PaletteFileError::UnsupportedExtension)
+        ));
+    }
+}
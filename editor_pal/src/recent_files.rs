@@ -0,0 +1,74 @@
+use std::path::{Path, PathBuf};
+
+pub const MAX_RECENT_FILES: usize = 10;
+
+/// Files opened during this session, most recent first
+#[derive(Default)]
+pub struct RecentFiles {
+    paths: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    pub fn new() -> Self {
+        Self { paths: Vec::new() }
+    }
+
+    /// Moves `path` to the front, dropping the oldest entry once there are
+    /// more than `MAX_RECENT_FILES`
+    pub fn push(&mut self, path: impl Into<PathBuf>) {
+        let path = path.into();
+        self.paths.retain(|existing| *existing != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT_FILES);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Path> {
+        self.paths.iter().map(PathBuf::as_path)
+    }
+
+    /// The entries whose files are still on disk
+    pub fn existing(&self) -> impl Iterator<Item = &Path> {
+        self.iter().filter(|path| path.exists())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_dedups_and_truncates() {
+        let mut recent = RecentFiles::new();
+        recent.push("a.png");
+        recent.push("b.pxc");
+        recent.push("a.png");
+        assert_eq!(
+            recent.iter().collect::<Vec<_>>(),
+            [Path::new("a.png"), Path::new("b.pxc")]
+        );
+
+        for i in 0..MAX_RECENT_FILES {
+            recent.push(format!("{i}.png"));
+        }
+        let paths: Vec<_> = recent.iter().collect();
+        assert_eq!(paths.len(), MAX_RECENT_FILES);
+        assert_eq!(paths[0], Path::new("9.png"));
+        assert_eq!(paths[MAX_RECENT_FILES - 1], Path::new("0.png"));
+    }
+
+    #[test]
+    fn test_existing_skips_missing_files() {
+        let mut recent = RecentFiles::new();
+        recent.push(std::env::temp_dir());
+        recent.push(format!("pxc-editor-missing-{}.png", std::process::id()));
+
+        assert_eq!(
+            recent.existing().collect::<Vec<_>>(),
+            [std::env::temp_dir().as_path()]
+        );
+    }
+}
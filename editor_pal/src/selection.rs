@@ -0,0 +1,55 @@
+/// Rectangular area of the canvas, stored as inclusive corners
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub min: (u32, u32),
+    pub max: (u32, u32),
+}
+
+impl Selection {
+    /// Selection spanning two corners in either order, clamped to a canvas of
+    /// the given dimensions. Returns `None` if it misses the canvas entirely.
+    pub fn from_corners(a: (i32, i32), b: (i32, i32), dimensions: (u32, u32)) -> Option<Self> {
+        let (width, height) = (dimensions.0 as i64, dimensions.1 as i64);
+        let (min_x, max_x) = (a.0.min(b.0) as i64, a.0.max(b.0) as i64);
+        let (min_y, max_y) = (a.1.min(b.1) as i64, a.1.max(b.1) as i64);
+        if max_x < 0 || max_y < 0 || min_x >= width || min_y >= height {
+            return None;
+        }
+
+        Some(Self {
+            min: (min_x.max(0) as u32, min_y.max(0) as u32),
+            max: (max_x.min(width - 1) as u32, max_y.min(height - 1) as u32),
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.max.0 - self.min.0 + 1
+    }
+
+    pub fn height(&self) -> u32 {
+        self.max.1 - self.min.1 + 1
+    }
+
+    /// Every cell of the selection in row-major order
+    pub fn cells(&self) -> impl Iterator<Item = (u32, u32)> {
+        let (min, max) = (self.min, self.max);
+        (min.1..=max.1).flat_map(move |y| (min.0..=max.0).map(move |x| (x, y)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_from_corners_clamps() {
+        let selection = Selection::from_corners((5, -3), (-2, 2), (4, 4)).unwrap();
+        assert_eq!(selection.min, (0, 0));
+        assert_eq!(selection.max, (3, 2));
+        assert_eq!((selection.width(), selection.height()), (4, 3));
+        assert_eq!(selection.cells().count(), 12);
+
+        assert_eq!(Selection::from_corners((-3, 0), (-1, 2), (4, 4)), None);
+        assert_eq!(Selection::from_corners((4, 0), (6, 2), (4, 4)), None);
+    }
+}
@@ -0,0 +1,162 @@
+//! Pixel geometry for the shape tools. Coordinates are signed so shapes may
+//! start or end outside the canvas; callers drop the points that don't fit.
+
+/// Cells on the line from `a` to `b`, both ends included
+pub fn bresenham(a: (i32, i32), b: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = a;
+    let dx = (b.0 - a.0).abs();
+    let dy = -(b.1 - a.1).abs();
+    let step_x = if a.0 < b.0 { 1 } else { -1 };
+    let step_y = if a.1 < b.1 { 1 } else { -1 };
+    let mut err = dx + dy;
+
+    let mut points = Vec::with_capacity(dx.max(-dy) as usize + 1);
+    loop {
+        points.push((x, y));
+        if (x, y) == b {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += step_x;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += step_y;
+        }
+    }
+    points
+}
+
+/// Outline of the rectangle with opposite corners `a` and `b`
+pub fn rectangle(a: (i32, i32), b: (i32, i32)) -> Vec<(i32, i32)> {
+    let (min_x, max_x) = (a.0.min(b.0), a.0.max(b.0));
+    let (min_y, max_y) = (a.1.min(b.1), a.1.max(b.1));
+
+    let mut points = Vec::new();
+    for x in min_x..=max_x {
+        points.push((x, min_y));
+        if max_y != min_y {
+            points.push((x, max_y));
+        }
+    }
+    for y in min_y + 1..max_y {
+        points.push((min_x, y));
+        if max_x != min_x {
+            points.push((max_x, y));
+        }
+    }
+    points
+}
+
+/// Outline of the ellipse inscribed in the rectangle with opposite corners
+/// `a` and `b`, using the midpoint algorithm
+pub fn ellipse(a: (i32, i32), b: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x0, mut y0) = (a.0.min(b.0) as i64, a.1.min(b.1) as i64);
+    let mut x1 = a.0.max(b.0) as i64;
+    let width = x1 - x0;
+    let height = a.1.abs_diff(b.1) as i64;
+
+    let mut points = Vec::new();
+    let mut plot = |x: i64, y: i64| points.push((x as i32, y as i32));
+
+    // Work from the middle rows outwards, tracking the error of the four
+    // symmetric quadrant points
+    let odd_height = height & 1;
+    let mut dx = 4 * (1 - width) * height * height;
+    let mut dy = 4 * (odd_height + 1) * width * width;
+    let mut err = dx + dy + odd_height * width * width;
+    y0 += (height + 1) / 2;
+    let mut y1 = y0 - odd_height;
+    let step_x = 8 * width * width;
+    let step_y = 8 * height * height;
+
+    loop {
+        plot(x1, y0);
+        plot(x0, y0);
+        plot(x0, y1);
+        plot(x1, y1);
+        let e2 = 2 * err;
+        if e2 <= dy {
+            y0 += 1;
+            y1 -= 1;
+            dy += step_x;
+            err += dy;
+        }
+        if e2 >= dx || 2 * err > dy {
+            x0 += 1;
+            x1 -= 1;
+            dx += step_y;
+            err += dx;
+        }
+        if x0 > x1 {
+            break;
+        }
+    }
+
+    // Flat ellipses stop early; finish the tips
+    while y0 - y1 <= height {
+        plot(x0 - 1, y0);
+        plot(x1 + 1, y0);
+        y0 += 1;
+        plot(x0 - 1, y1);
+        plot(x1 + 1, y1);
+        y1 -= 1;
+    }
+
+    points.sort_unstable();
+    points.dedup();
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bresenham_lines() {
+        assert_eq!(
+            bresenham((0, 0), (3, 0)),
+            vec![(0, 0), (1, 0), (2, 0), (3, 0)]
+        );
+        assert_eq!(bresenham((2, 2), (0, 0)), vec![(2, 2), (1, 1), (0, 0)]);
+        assert_eq!(bresenham((-1, 5), (-1, 5)), vec![(-1, 5)]);
+
+        // A steep line gets one cell per row
+        let steep = bresenham((0, 0), (2, 6));
+        assert_eq!(steep.len(), 7);
+        assert_eq!(steep.first(), Some(&(0, 0)));
+        assert_eq!(steep.last(), Some(&(2, 6)));
+    }
+
+    #[test]
+    fn test_rectangle_outline() {
+        let points = rectangle((4, 3), (0, 0));
+        // 2 * 5 + 2 * 4 - 4 corners counted twice
+        assert_eq!(points.len(), 14);
+        assert!(points.contains(&(0, 0)) && points.contains(&(4, 3)));
+        assert!(!points.contains(&(2, 1)));
+
+        assert_eq!(rectangle((1, 1), (3, 1)), vec![(1, 1), (2, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn test_ellipse_outline() {
+        let points = ellipse((0, 0), (8, 4));
+
+        // Touches the middle of each side of the bounding box
+        for point in [(0, 2), (8, 2), (4, 0), (4, 4)] {
+            assert!(points.contains(&point), "missing {point:?}");
+        }
+        // Stays inside it and is mirror symmetric
+        for &(x, y) in &points {
+            assert!((0..=8).contains(&x) && (0..=4).contains(&y));
+            assert!(points.contains(&(8 - x, y)));
+            assert!(points.contains(&(x, 4 - y)));
+        }
+        assert!(!points.contains(&(0, 0)));
+
+        assert_eq!(ellipse((3, 3), (3, 3)), vec![(3, 3)]);
+    }
+}
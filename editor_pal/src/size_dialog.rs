@@ -0,0 +1,141 @@
+use eframe::egui;
+use thiserror::Error;
+
+pub const MIN_CANVAS_SIZE: u32 = 1;
+pub const MAX_CANVAS_SIZE: u32 = 4096;
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum DimensionError {
+    #[error("'{0}' is not a whole number")]
+    NotANumber(String),
+}
+
+/// Parses a width or height typed by the user, clamping it to
+/// `MIN_CANVAS_SIZE..=MAX_CANVAS_SIZE`
+pub fn parse_dimension(text: &str) -> Result<u32, DimensionError> {
+    let text = text.trim();
+    if text.is_empty() || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(DimensionError::NotANumber(text.to_string()));
+    }
+
+    // Digits only, so parsing can only fail by overflowing
+    let value = text.parse::<u32>().unwrap_or(u32::MAX);
+    Ok(value.clamp(MIN_CANVAS_SIZE, MAX_CANVAS_SIZE))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SizeDialogKind {
+    /// Start a blank document
+    New,
+    /// Change the size of the current document
+    Resize,
+}
+
+pub enum SizeDialogResult {
+    Open,
+    Cancel,
+    Confirm(u32, u32),
+}
+
+/// Modal asking for a canvas width and height
+pub struct SizeDialog {
+    pub kind: SizeDialogKind,
+    width: String,
+    height: String,
+    error: Option<String>,
+}
+
+impl SizeDialog {
+    pub fn new(kind: SizeDialogKind, (width, height): (u32, u32)) -> Self {
+        Self {
+            kind,
+            width: width.to_string(),
+            height: height.to_string(),
+            error: None,
+        }
+    }
+
+    fn parse(&self) -> Result<(u32, u32), DimensionError> {
+        Ok((
+            parse_dimension(&self.width)?,
+            parse_dimension(&self.height)?,
+        ))
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) -> SizeDialogResult {
+        let title = match self.kind {
+            SizeDialogKind::New => "New Canvas",
+            SizeDialogKind::Resize => "Resize Canvas",
+        };
+
+        let mut result = SizeDialogResult::Open;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                egui::Grid::new("size_dialog").show(ui, |ui| {
+                    ui.label("Width:");
+                    ui.text_edit_singleline(&mut self.width);
+                    ui.end_row();
+                    ui.label("Height:");
+                    ui.text_edit_singleline(&mut self.height);
+                    ui.end_row();
+                });
+                ui.label(format!(
+                    "From {MIN_CANVAS_SIZE} to {MAX_CANVAS_SIZE} pixels"
+                ));
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("OK").clicked() {
+                        match self.parse() {
+                            Ok((width, height)) => {
+                                result = SizeDialogResult::Confirm(width, height);
+                            }
+                            Err(err) => self.error = Some(err.to_string()),
+                        }
+                    }
+                    if ui.button("Cancel").clicked() {
+                        result = SizeDialogResult::Cancel;
+                    }
+                });
+            });
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dimension() {
+        assert_eq!(parse_dimension("64"), Ok(64));
+        assert_eq!(parse_dimension(" 128 "), Ok(128));
+
+        // Out of range values are clamped
+        assert_eq!(parse_dimension("0"), Ok(MIN_CANVAS_SIZE));
+        assert_eq!(parse_dimension("5000"), Ok(MAX_CANVAS_SIZE));
+        assert_eq!(parse_dimension("99999999999999"), Ok(MAX_CANVAS_SIZE));
+
+        for text in ["", "abc", "-4", "12px", "1.5", "+3"] {
+            assert_eq!(
+                parse_dimension(text),
+                Err(DimensionError::NotANumber(text.trim().to_string())),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_size_dialog_parses_both_fields() {
+        let mut dialog = SizeDialog::new(SizeDialogKind::New, (32, 16));
+        assert_eq!(dialog.parse(), Ok((32, 16)));
+
+        dialog.height = "tall".to_string();
+        assert!(dialog.parse().is_err());
+    }
+}
@@ -0,0 +1,131 @@
+use eframe::egui::{self, Align2, Color32, Vec2};
+
+pub const TOAST_DURATION: f64 = 4.0; // in seconds
+/// Seconds before expiry over which a toast fades out
+pub const TOAST_FADE: f64 = 0.5;
+
+/// Severity of a toast, picks its text color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToastLevel {
+    #[default]
+    Info,
+    Error,
+}
+
+impl ToastLevel {
+    pub fn color(self) -> Color32 {
+        match self {
+            ToastLevel::Info => Color32::WHITE,
+            ToastLevel::Error => Color32::from_rgb(255, 110, 110),
+        }
+    }
+}
+
+pub struct Toast {
+    pub text: String,
+    pub level: ToastLevel,
+    pub expires_at: f64,
+}
+
+impl Toast {
+    /// Opacity at `now`, falling from 1 to 0 over the last `TOAST_FADE`
+    /// seconds
+    pub fn opacity(&self, now: f64) -> f32 {
+        ((self.expires_at - now) / TOAST_FADE).clamp(0.0, 1.0) as f32
+    }
+}
+
+/// Queue of timed messages shown as an overlay, timed against egui's clock
+#[derive(Default)]
+pub struct Toasts {
+    toasts: Vec<Toast>,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Self { toasts: Vec::new() }
+    }
+
+    pub fn push(&mut self, text: impl Into<String>, level: ToastLevel, now: f64) {
+        self.toasts.push(Toast {
+            text: text.into(),
+            level,
+            expires_at: now + TOAST_DURATION,
+        });
+    }
+
+    pub fn remove_expired(&mut self, now: f64) {
+        self.toasts.retain(|toast| toast.expires_at > now);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.toasts.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Toast> {
+        self.toasts.iter()
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let now = ctx.input(|i| i.time);
+        self.remove_expired(now);
+
+        if self.toasts.is_empty() {
+            return;
+        }
+
+        egui::Area::new(egui::Id::new("toasts"))
+            .anchor(Align2::RIGHT_BOTTOM, Vec2::new(-12.0, -12.0))
+            .show(ctx, |ui| {
+                for toast in &self.toasts {
+                    let opacity = toast.opacity(now);
+                    ui.scope(|ui| {
+                        ui.set_opacity(opacity);
+                        egui::Frame::popup(ui.style()).show(ui, |ui| {
+                            ui.colored_label(toast.level.color(), &toast.text);
+                        });
+                    });
+                }
+            });
+
+        // Keep repainting so toasts disappear without further input
+        ctx.request_repaint_after(std::time::Duration::from_millis(250));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toasts_expire() {
+        let mut toasts = Toasts::new();
+        toasts.push("first", ToastLevel::Info, 0.0);
+        toasts.push("second", ToastLevel::Error, 2.0);
+
+        toasts.remove_expired(TOAST_DURATION - 1.0);
+        assert_eq!(toasts.iter().count(), 2);
+
+        toasts.remove_expired(TOAST_DURATION + 1.0);
+        let remaining: Vec<&str> = toasts.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(remaining, vec!["second"]);
+        assert_eq!(toasts.iter().next().unwrap().level, ToastLevel::Error);
+
+        toasts.remove_expired(TOAST_DURATION + 2.0);
+        assert!(toasts.is_empty());
+    }
+
+    #[test]
+    fn test_toasts_fade_before_expiry() {
+        let mut toasts = Toasts::new();
+        toasts.push("saved", ToastLevel::Info, 1.0);
+        let toast = toasts.iter().next().unwrap();
+        let expires_at = 1.0 + TOAST_DURATION;
+
+        assert_eq!(toast.opacity(1.0), 1.0);
+        assert_eq!(toast.opacity(expires_at - TOAST_FADE), 1.0);
+        assert_eq!(toast.opacity(expires_at - TOAST_FADE / 2.0), 0.5);
+        assert_eq!(toast.opacity(expires_at), 0.0);
+        assert_eq!(toast.opacity(expires_at + 1.0), 0.0);
+    }
+}
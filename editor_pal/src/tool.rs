@@ -0,0 +1,64 @@
+use crate::shapes;
+
+/// What a primary click or drag on the canvas does
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Tool {
+    /// Paints single pixels with the current color
+    #[default]
+    Pencil,
+    /// Erases single pixels back to transparent
+    Eraser,
+    /// Fills the clicked region of same-colored pixels with the current color
+    Fill,
+    /// Copies the clicked pixel's color into the current palette slot
+    Eyedropper,
+    /// Draws a line from where the drag starts to where it ends
+    Line,
+    /// Draws a rectangle outline spanning the drag
+    Rectangle,
+    /// Draws an ellipse outline inside the rectangle spanning the drag
+    Ellipse,
+    /// Selects the rectangle spanning the drag for cut, copy and paste
+    Select,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 8] = [
+        Tool::Pencil,
+        Tool::Eraser,
+        Tool::Fill,
+        Tool::Eyedropper,
+        Tool::Line,
+        Tool::Rectangle,
+        Tool::Ellipse,
+        Tool::Select,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Tool::Pencil => "✏ Pencil",
+            Tool::Eraser => "Eraser",
+            Tool::Fill => "🪣 Fill",
+            Tool::Eyedropper => "Eyedropper (I)",
+            Tool::Line => "Line",
+            Tool::Rectangle => "Rectangle",
+            Tool::Ellipse => "Ellipse",
+            Tool::Select => "Select",
+        }
+    }
+
+    /// Cells of the shape dragged from `start` to `end`, or `None` if this
+    /// isn't a shape tool
+    pub fn shape(&self, start: (i32, i32), end: (i32, i32)) -> Option<Vec<(i32, i32)>> {
+        match self {
+            Tool::Line => Some(shapes::bresenham(start, end)),
+            Tool::Rectangle => Some(shapes::rectangle(start, end)),
+            Tool::Ellipse => Some(shapes::ellipse(start, end)),
+            Tool::Pencil | Tool::Eraser | Tool::Fill | Tool::Eyedropper | Tool::Select => None,
+        }
+    }
+
+    pub fn is_shape(&self) -> bool {
+        matches!(self, Tool::Line | Tool::Rectangle | Tool::Ellipse)
+    }
+}
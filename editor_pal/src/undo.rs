@@ -0,0 +1,220 @@
+use std::collections::VecDeque;
+use std::mem::size_of;
+
+use eframe::egui::Color32;
+
+pub const DEFAULT_UNDO_BUDGET: usize = 64 * 1024 * 1024; // in bytes
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelChange {
+    pub x: u32,
+    pub y: u32,
+    pub before: Color32,
+    pub after: Color32,
+}
+
+#[derive(Debug)]
+pub enum UndoStep {
+    /// Individual pixel edits to a layer, e.g. a single brush stroke
+    Pixels {
+        layer: usize,
+        changes: Vec<PixelChange>,
+    },
+    /// Row-major RGBA contents of a layer region before and after a bulk
+    /// operation
+    Snapshot {
+        layer: usize,
+        origin: (u32, u32),
+        dimensions: (u32, u32),
+        before: Vec<u8>,
+        after: Vec<u8>,
+    },
+}
+
+impl UndoStep {
+    /// Approximate heap memory held by this step
+    pub fn memory_size(&self) -> usize {
+        let data = match self {
+            UndoStep::Pixels { changes, .. } => changes.len() * size_of::<PixelChange>(),
+            UndoStep::Snapshot { before, after, .. } => before.len() + after.len(),
+        };
+        size_of::<Self>() + data
+    }
+}
+
+/// Undo/redo history bounded by an approximate memory budget.
+///
+/// When the budget is exceeded the oldest steps are evicted, but the most
+/// recent step is always kept so the last action can be undone.
+pub struct UndoStack {
+    undo: VecDeque<UndoStep>,
+    redo: Vec<UndoStep>,
+    memory_used: usize,
+    budget: usize,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UndoStack {
+    pub fn new() -> Self {
+        Self::with_budget(DEFAULT_UNDO_BUDGET)
+    }
+
+    pub fn with_budget(budget: usize) -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            memory_used: 0,
+            budget,
+        }
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Records a new step, discarding any redo history
+    pub fn push(&mut self, step: UndoStep) {
+        for step in self.redo.drain(..) {
+            self.memory_used -= step.memory_size();
+        }
+
+        self.memory_used += step.memory_size();
+        self.undo.push_back(step);
+        self.evict();
+    }
+
+    /// Moves the latest step to the redo stack and returns it so the caller
+    /// can revert it
+    pub fn undo(&mut self) -> Option<&UndoStep> {
+        let step = self.undo.pop_back()?;
+        self.redo.push(step);
+        self.redo.last()
+    }
+
+    /// Moves the latest undone step back and returns it so the caller can
+    /// re-apply it
+    pub fn redo(&mut self) -> Option<&UndoStep> {
+        let step = self.redo.pop()?;
+        self.undo.push_back(step);
+        self.undo.back()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.memory_used = 0;
+    }
+
+    fn evict(&mut self) {
+        while self.memory_used > self.budget && self.undo.len() > 1 {
+            if let Some(step) = self.undo.pop_front() {
+                self.memory_used -= step.memory_size();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(size: usize, marker: u8) -> UndoStep {
+        UndoStep::Snapshot {
+            layer: 0,
+            origin: (0, 0),
+            dimensions: (size as u32 / 4, 1),
+            before: vec![0; size],
+            after: vec![marker; size],
+        }
+    }
+
+    #[test]
+    fn test_undo_stack_evicts_oldest_over_budget() {
+        let mut stack = UndoStack::with_budget(32 * 1024);
+
+        for marker in 0..100 {
+            stack.push(snapshot(4096, marker));
+            assert!(stack.memory_used() <= stack.budget());
+        }
+
+        assert!(stack.undo_len() < 100);
+        assert!(stack.undo_len() > 1);
+
+        match stack.undo() {
+            Some(UndoStep::Snapshot { after, .. }) => assert_eq!(after[0], 99),
+            _ => panic!("expected the most recent snapshot"),
+        }
+    }
+
+    #[test]
+    fn test_undo_stack_keeps_latest_step_over_budget() {
+        let mut stack = UndoStack::with_budget(1024);
+        stack.push(snapshot(4096, 1));
+        stack.push(snapshot(4096, 2));
+
+        assert_eq!(stack.undo_len(), 1);
+        match stack.undo() {
+            Some(UndoStep::Snapshot { after, .. }) => assert_eq!(after[0], 2),
+            _ => panic!("expected the most recent snapshot"),
+        }
+    }
+
+    #[test]
+    fn test_undo_stack_push_clears_redo() {
+        let mut stack = UndoStack::new();
+        let change = PixelChange {
+            x: 1,
+            y: 2,
+            before: Color32::TRANSPARENT,
+            after: Color32::RED,
+        };
+
+        stack.push(UndoStep::Pixels {
+            layer: 0,
+            changes: vec![change],
+        });
+        stack.push(UndoStep::Pixels {
+            layer: 0,
+            changes: vec![change],
+        });
+        assert!(stack.undo().is_some());
+        assert_eq!(stack.redo_len(), 1);
+
+        stack.push(UndoStep::Pixels {
+            layer: 0,
+            changes: vec![change],
+        });
+        assert_eq!(stack.redo_len(), 0);
+        assert_eq!(stack.undo_len(), 2);
+        assert_eq!(
+            stack.memory_used(),
+            2 * UndoStep::Pixels {
+                layer: 0,
+                changes: vec![change],
+            }
+            .memory_size()
+        );
+    }
+}
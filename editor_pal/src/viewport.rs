@@ -1,6 +1,20 @@
 use eframe::egui::{InputState, Pos2, Rect, Vec2};
 
-#[derive(Default)]
+use crate::canvas::CELL_SIZE;
+
+/// Default lower bound of `ViewportOptions::min_zoom`, also bounding
+/// `zoom_to_fit`
+pub const MIN_ZOOM: f32 = 0.01;
+/// Default upper bound of `ViewportOptions::max_zoom`, also bounding
+/// `zoom_to_fit`
+pub const MAX_ZOOM: f32 = 10.0;
+/// Pixels of the canvas that stay in view however far it is panned, so its
+/// edge can still be grabbed
+pub const PAN_MARGIN: f32 = 32.0;
+/// Smallest side of a transparency checker, in points. Checkers are one
+/// canvas pixel, doubled until they reach this size when zoomed out.
+pub const MIN_CHECKER_SIZE: f32 = 16.0;
+
 pub struct ViewportInfo {
     parent_rect: Option<Rect>,
     viewport: Option<Rect>,
@@ -13,6 +27,32 @@ pub struct ViewportInfo {
 
     zoom: f32,
     target_zoom: f32,
+    /// Side of a canvas pixel at zoom 1, in points
+    base_cell_size: f32,
+
+    pointer_mode: PointerMode,
+}
+
+/// What dragging with the primary button does
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PointerMode {
+    #[default]
+    Draw,
+    /// Space is held, or was held when the current drag started
+    Pan,
+}
+
+impl PointerMode {
+    /// Holding space switches to panning straight away. Releasing it only
+    /// switches back once the primary button is up, so the end of a pan
+    /// never turns into a stroke.
+    pub fn next(self, space_down: bool, primary_down: bool) -> Self {
+        match self {
+            _ if space_down => PointerMode::Pan,
+            PointerMode::Pan if primary_down => PointerMode::Pan,
+            _ => PointerMode::Draw,
+        }
+    }
 }
 
 impl ViewportInfo {
@@ -26,6 +66,8 @@ impl ViewportInfo {
             last_mouse_pos: None,
             target_zoom: 1.0,
             target_pan_offset: Vec2::ZERO,
+            base_cell_size: CELL_SIZE,
+            pointer_mode: PointerMode::Draw,
         }
     }
 
@@ -43,60 +85,180 @@ impl ViewportInfo {
         self.zoom
     }
 
-    pub fn last_mouse_pos(&self) -> Option<Pos2> {
-        self.last_mouse_pos
+    pub fn base_cell_size(&self) -> f32 {
+        self.base_cell_size
     }
 
-    pub fn get_viewport(&self) -> Option<Rect> {
-        self.viewport
+    /// Sets the side of a canvas pixel at zoom 1, at least one point
+    pub fn set_base_cell_size(&mut self, size: f32) {
+        self.base_cell_size = size.max(1.0);
+    }
+
+    /// Side of a canvas pixel on screen at the current zoom, in points. All
+    /// conversions between canvas and screen coordinates go through this.
+    pub fn cell_size(&self) -> f32 {
+        self.zoom * self.base_cell_size
+    }
+
+    /// Sets the zoom immediately, without easing towards it, within the
+    /// bounds of `options`
+    pub fn set_zoom(&mut self, zoom: f32, options: &ViewportOptions) {
+        self.zoom = options.clamp_zoom(zoom);
+        self.target_zoom = self.zoom;
+    }
+
+    /// Pans immediately, without easing towards the offset
+    pub fn set_pan_offset(&mut self, pan_offset: Vec2) {
+        self.pan_offset = pan_offset;
+        self.target_pan_offset = pan_offset;
+    }
+
+    /// Pans the canvas back to the middle of the viewport
+    pub fn center(&mut self) {
+        self.pan_offset = Vec2::ZERO;
+        self.target_pan_offset = Vec2::ZERO;
+    }
+
+    pub fn pointer_mode(&self) -> PointerMode {
+        self.pointer_mode
+    }
+
+    pub fn update_pointer_mode(&mut self, space_down: bool, primary_down: bool) {
+        self.pointer_mode = self.pointer_mode.next(space_down, primary_down);
     }
 
     pub fn get_parent_rect(&self) -> Option<Rect> {
         self.parent_rect
     }
+
+    pub fn get_viewport_rect(&self) -> Option<Rect> {
+        self.viewport
+    }
+}
+
+impl Default for ViewportInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Largest zoom at which a canvas of the given dimensions fits in
+/// `view_size`, with cells `base_cell_size` points wide at zoom 1
+pub fn zoom_to_fit(canvas_dimensions: (u32, u32), view_size: Vec2, base_cell_size: f32) -> f32 {
+    let (width, height) = canvas_dimensions;
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+
+    let zoom_x = view_size.x / (width as f32 * base_cell_size);
+    let zoom_y = view_size.y / (height as f32 * base_cell_size);
+    zoom_x.min(zoom_y).clamp(MIN_ZOOM, MAX_ZOOM)
 }
 
-#[derive(Default)]
 pub struct ViewportOptions {
     pub draw_grid: bool,
+    /// Draw a checkerboard behind the canvas so transparent pixels show
+    pub draw_checkerboard: bool,
+    /// Fraction the zoom changes by per scroll tick
+    pub zoom_step: f32,
+    /// Fraction of the remaining distance to the target zoom covered per
+    /// frame. Values outside `0.0..=1.0` are clamped so the zoom never
+    /// overshoots.
+    pub zoom_speed: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    pub invert_scroll: bool,
 }
 
 impl ViewportOptions {
     pub fn new() -> Self {
-        Self { draw_grid: true }
+        Self {
+            draw_grid: true,
+            draw_checkerboard: true,
+            zoom_step: 0.1,
+            zoom_speed: 0.1,
+            min_zoom: MIN_ZOOM,
+            max_zoom: MAX_ZOOM,
+            invert_scroll: false,
+        }
+    }
+
+    /// Clamps `zoom` to `min_zoom..=max_zoom`. A `max_zoom` below `min_zoom`
+    /// counts as `min_zoom`.
+    pub fn clamp_zoom(&self, zoom: f32) -> f32 {
+        zoom.clamp(self.min_zoom, self.max_zoom.max(self.min_zoom))
+    }
+}
+
+impl Default for ViewportOptions {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-pub fn update_zoom(input: &InputState, viewport_info: &mut ViewportInfo) {
-    let zoom_speed = 0.1;
+pub fn update_zoom(
+    input: &InputState,
+    viewport_info: &mut ViewportInfo,
+    viewport_options: &ViewportOptions,
+) {
+    let zoom_speed = viewport_options.zoom_speed.clamp(0.0, 1.0);
     viewport_info.zoom += (viewport_info.target_zoom - viewport_info.zoom) * zoom_speed;
 
     viewport_info.pan_offset +=
         (viewport_info.target_pan_offset - viewport_info.pan_offset) * zoom_speed;
 
-    let scroll_delta = input.raw_scroll_delta.y;
+    let mut scroll_delta = input.raw_scroll_delta.y;
+    if viewport_options.invert_scroll {
+        scroll_delta = -scroll_delta;
+    }
     if scroll_delta == 0.0 {
         return;
     }
 
     let old_zoom = viewport_info.zoom;
-    let zoom_delta = if scroll_delta > 0.0 { 1.1 } else { 0.9 };
-    viewport_info.target_zoom *= zoom_delta;
-    viewport_info.target_zoom = viewport_info.target_zoom.clamp(0.01, 10.0);
-
-    let viewport = viewport_info.viewport.unwrap();
+    let zoom_delta = if scroll_delta > 0.0 {
+        1.0 + viewport_options.zoom_step
+    } else {
+        1.0 - viewport_options.zoom_step
+    };
+    viewport_info.target_zoom = viewport_options.clamp_zoom(viewport_info.target_zoom * zoom_delta);
 
-    if let Some(mouse_pos) = input.pointer.hover_pos() {
-        let relative_cursor_position = mouse_pos - viewport_info.pan_offset;
+    // Before the first frame there is no viewport to anchor the zoom to
+    if let (Some(mouse_pos), Some(viewport)) = (input.pointer.hover_pos(), viewport_info.viewport) {
         let zoom_ratio = viewport_info.target_zoom / old_zoom;
 
         // Adjust pan_offset to keep the cursor position under the mouse
-        viewport_info.target_pan_offset =
-            mouse_pos - viewport.center() - relative_cursor_position.to_vec2() * zoom_ratio * 0.1;
+        viewport_info.target_pan_offset = zoom_anchored_pan(
+            viewport_info.pan_offset,
+            mouse_pos - viewport.center(),
+            zoom_ratio,
+        );
     }
 }
 
-pub fn update_pan_offset(viewport_info: &mut ViewportInfo) {
+/// Pan offset that keeps the canvas point under `anchor` on screen when the
+/// zoom is multiplied by `zoom_ratio`.
+///
+/// The canvas is drawn centered on the viewport center plus the pan offset,
+/// so a canvas point sits at `pan_offset + p * zoom` relative to the viewport
+/// center. `anchor` is relative to the viewport center too.
+pub fn zoom_anchored_pan(pan_offset: Vec2, anchor: Vec2, zoom_ratio: f32) -> Vec2 {
+    anchor - (anchor - pan_offset) * zoom_ratio
+}
+
+/// Largest distance, per axis, the canvas center may be panned from the
+/// viewport center.
+///
+/// Any part of a canvas larger than the viewport can be brought into view,
+/// while at least `PAN_MARGIN` pixels of it, or all of it if smaller, stay
+/// visible.
+pub fn pan_bounds(canvas_dimensions: (u32, u32), cell_size: f32, viewport_size: Vec2) -> Vec2 {
+    let canvas_size = Vec2::new(canvas_dimensions.0 as f32, canvas_dimensions.1 as f32) * cell_size;
+    let margin = canvas_size.min(Vec2::splat(PAN_MARGIN));
+    ((viewport_size + canvas_size) / 2.0 - margin).max(Vec2::ZERO)
+}
+
+pub fn update_pan_offset(viewport_info: &mut ViewportInfo, canvas_dimensions: (u32, u32)) {
     let mouse_pos = viewport_info.mouse_pos;
 
     if mouse_pos.is_none() {
@@ -112,24 +274,64 @@ pub fn update_pan_offset(viewport_info: &mut ViewportInfo) {
 
     let mouse_pos = mouse_pos.unwrap();
     let last_frame_pos = last_frame_pos.unwrap();
-    let viewport = viewport_info.viewport.unwrap();
+    let Some(viewport) = viewport_info.viewport else {
+        return;
+    };
 
     let delta = mouse_pos - last_frame_pos;
     viewport_info.pan_offset += delta;
 
-    let boundaries = (
-        -viewport.size().x / 2.0,
-        viewport.size().x / 2.0,
-        -viewport.size().y / 2.0,
-        viewport.size().y / 2.0,
+    let bounds = pan_bounds(
+        canvas_dimensions,
+        viewport_info.cell_size(),
+        viewport.size(),
     );
-
-    viewport_info.pan_offset.x = viewport_info.pan_offset.x.clamp(boundaries.0, boundaries.1);
-    viewport_info.pan_offset.y = viewport_info.pan_offset.y.clamp(boundaries.2, boundaries.3);
+    viewport_info.pan_offset = viewport_info.pan_offset.clamp(-bounds, bounds);
 
     viewport_info.last_mouse_pos = Some(mouse_pos);
 }
 
+/// Side of a transparency checker in points, for canvas pixels `cell_size`
+/// points wide
+pub fn checker_size(cell_size: f32) -> f32 {
+    let mut size = cell_size;
+    if size <= 0.0 {
+        return MIN_CHECKER_SIZE;
+    }
+    while size < MIN_CHECKER_SIZE {
+        size *= 2.0;
+    }
+    size
+}
+
+/// Dark squares of the checkerboard behind `canvas_rect`, clipped to
+/// `visible_rect`. The light squares are the background they are drawn on.
+///
+/// Squares are `checker_size(cell_size)` wide and aligned to the canvas top
+/// left, which is light.
+pub fn checkerboard_cells(canvas_rect: Rect, visible_rect: Rect, cell_size: f32) -> Vec<Rect> {
+    let area = canvas_rect.intersect(visible_rect);
+    if !area.is_positive() {
+        return Vec::new();
+    }
+
+    let size = checker_size(cell_size);
+    let start = ((area.min - canvas_rect.min) / size).floor();
+    let end = ((area.max - canvas_rect.min) / size).ceil();
+
+    let mut cells = Vec::new();
+    for y in start.y as i64..end.y as i64 {
+        for x in start.x as i64..end.x as i64 {
+            if (x + y) % 2 == 0 {
+                continue;
+            }
+            let min = canvas_rect.min + Vec2::new(x as f32, y as f32) * size;
+            cells.push(Rect::from_min_size(min, Vec2::splat(size)).intersect(area));
+        }
+    }
+    cells
+}
+
 pub fn update_canvas_viewport(
     input: &InputState,
     viewport_info: &mut ViewportInfo,
@@ -137,15 +339,293 @@ pub fn update_canvas_viewport(
     canvas_dimensions: &(u32, u32),
 ) {
     if input.modifiers.ctrl {
-        update_zoom(input, viewport_info);
+        update_zoom(input, viewport_info, viewport_options);
     } else {
         viewport_info.target_zoom = viewport_info.zoom;
         viewport_info.target_pan_offset = viewport_info.pan_offset;
     }
 
-    if input.pointer.middle_down() {
-        update_pan_offset(viewport_info);
+    let primary_pan =
+        viewport_info.pointer_mode == PointerMode::Pan && input.pointer.primary_down();
+    if input.pointer.middle_down() || primary_pan {
+        update_pan_offset(viewport_info, *canvas_dimensions);
     } else {
         viewport_info.last_mouse_pos = None;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Input with the pointer hovering `pos` and scrolled by `delta`
+    fn hover_scroll_input(pos: Pos2, delta: f32) -> InputState {
+        let raw = eframe::egui::RawInput {
+            events: vec![eframe::egui::Event::PointerMoved(pos)],
+            ..Default::default()
+        };
+        let mut input =
+            InputState::default().begin_pass(raw, false, 1.0, &eframe::egui::Options::default());
+        input.raw_scroll_delta = Vec2::new(0.0, delta);
+        input
+    }
+
+    fn scroll_input(delta: f32) -> InputState {
+        let mut input = InputState::default();
+        input.raw_scroll_delta = Vec2::new(0.0, delta);
+        input
+    }
+
+    fn viewport_info() -> ViewportInfo {
+        let mut viewport_info = ViewportInfo::new();
+        let rect = Rect::from_min_size(Pos2::ZERO, Vec2::new(800.0, 600.0));
+        viewport_info.update(rect, rect, None);
+        viewport_info
+    }
+
+    #[test]
+    fn test_default_options_allow_zoom() {
+        let options = ViewportOptions::default();
+        assert_eq!((options.min_zoom, options.max_zoom), (MIN_ZOOM, MAX_ZOOM));
+        assert!(options.zoom_step > 0.0 && options.zoom_speed > 0.0);
+        assert_eq!(options.clamp_zoom(1.0), 1.0);
+    }
+
+    #[test]
+    fn test_zoom_custom_step() {
+        let options = ViewportOptions {
+            zoom_step: 0.25,
+            ..ViewportOptions::new()
+        };
+
+        let mut info = viewport_info();
+        update_zoom(&scroll_input(1.0), &mut info, &options);
+        assert!((info.target_zoom - 1.25).abs() < 1e-6);
+
+        let mut info = viewport_info();
+        update_zoom(&scroll_input(-1.0), &mut info, &options);
+        assert!((info.target_zoom - 0.75).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_checkerboard_cells() {
+        // 4x2 canvas pixels of 20 points at zoom 1, all visible
+        let canvas = Rect::from_min_size(Pos2::new(10.0, 10.0), Vec2::new(80.0, 40.0));
+        let view = Rect::from_min_size(Pos2::ZERO, Vec2::new(800.0, 600.0));
+        let cells = checkerboard_cells(canvas, view, CELL_SIZE);
+        assert_eq!(cells.len(), 4);
+        assert_eq!(
+            cells[0],
+            Rect::from_min_size(Pos2::new(30.0, 10.0), Vec2::splat(20.0))
+        );
+        assert_eq!(
+            cells[2],
+            Rect::from_min_size(Pos2::new(10.0, 30.0), Vec2::splat(20.0))
+        );
+
+        // Clipped to the visible part of the canvas
+        let view = Rect::from_min_max(Pos2::new(35.0, 0.0), Pos2::new(60.0, 25.0));
+        let cells = checkerboard_cells(canvas, view, CELL_SIZE);
+        assert_eq!(
+            cells,
+            vec![Rect::from_min_max(
+                Pos2::new(35.0, 10.0),
+                Pos2::new(50.0, 25.0)
+            )]
+        );
+
+        // Off screen canvases need no checkers
+        let view = Rect::from_min_size(Pos2::new(500.0, 500.0), Vec2::splat(10.0));
+        assert!(checkerboard_cells(canvas, view, CELL_SIZE).is_empty());
+    }
+
+    #[test]
+    fn test_checker_size_scales_with_zoom() {
+        assert_eq!(checker_size(CELL_SIZE), CELL_SIZE);
+        assert_eq!(checker_size(2.0 * CELL_SIZE), 2.0 * CELL_SIZE);
+        // Zoomed out, checkers span several pixels instead of shrinking
+        assert_eq!(checker_size(2.0), 16.0);
+        assert_eq!(checker_size(10.0), 20.0);
+        let size = checker_size(0.2);
+        assert!((MIN_CHECKER_SIZE..2.0 * MIN_CHECKER_SIZE).contains(&size));
+    }
+
+    #[test]
+    fn test_zoom_and_pan_without_viewport() {
+        // Input can arrive before the first frame sets the viewport
+        let mut info = ViewportInfo::new();
+        let options = ViewportOptions::new();
+        update_zoom(
+            &hover_scroll_input(Pos2::new(10.0, 10.0), 1.0),
+            &mut info,
+            &options,
+        );
+        assert!((info.target_zoom - 1.1).abs() < 1e-6);
+        assert_eq!(info.target_pan_offset, Vec2::ZERO);
+
+        info.mouse_pos = Some(Pos2::new(20.0, 20.0));
+        info.last_mouse_pos = Some(Pos2::new(10.0, 10.0));
+        update_pan_offset(&mut info, (0, 0));
+        assert_eq!(info.pan_offset, Vec2::ZERO);
+    }
+
+    #[test]
+    fn test_zoom_to_fit() {
+        let view = Vec2::new(800.0, 600.0);
+        // 32x16 cells of 20px: limited by the width
+        assert!((zoom_to_fit((32, 16), view, CELL_SIZE) - 1.25).abs() < 1e-6);
+        // Limited by the height
+        assert!((zoom_to_fit((10, 60), view, CELL_SIZE) - 0.5).abs() < 1e-6);
+        // Clamped to the zoom bounds
+        assert_eq!(zoom_to_fit((1, 1), view, CELL_SIZE), MAX_ZOOM);
+        assert_eq!(zoom_to_fit((100_000, 100_000), view, CELL_SIZE), MIN_ZOOM);
+        assert_eq!(zoom_to_fit((0, 0), view, CELL_SIZE), 1.0);
+        // Bigger base cells need less zoom to fill the view
+        assert!((zoom_to_fit((32, 16), view, 2.0 * CELL_SIZE) - 0.625).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pointer_mode_space_toggles_pan() {
+        let mode = PointerMode::Draw;
+        assert_eq!(mode.next(false, true), PointerMode::Draw);
+
+        // Pressing space mid-stroke switches to panning at once
+        let mode = mode.next(true, true);
+        assert_eq!(mode, PointerMode::Pan);
+
+        // Releasing space keeps panning until the drag ends
+        let mode = mode.next(false, true);
+        assert_eq!(mode, PointerMode::Pan);
+        let mode = mode.next(false, false);
+        assert_eq!(mode, PointerMode::Draw);
+
+        // Holding space with the button up is ready to pan
+        assert_eq!(mode.next(true, false), PointerMode::Pan);
+    }
+
+    #[test]
+    fn test_zoom_keeps_point_under_cursor() {
+        // Canvas point, in cells from the canvas center, shown at `screen`
+        let canvas_point = |info: &ViewportInfo, screen: Pos2| {
+            let center = info.get_viewport_rect().unwrap().center();
+            (screen - center - info.target_pan_offset) / (info.target_zoom * CELL_SIZE)
+        };
+        let options = ViewportOptions::new();
+        let cursor = Pos2::new(620.0, 130.0);
+
+        let mut info = viewport_info();
+        info.pan_offset = Vec2::new(-35.0, 12.0);
+        info.target_pan_offset = info.pan_offset;
+        let before = canvas_point(&info, cursor);
+
+        for delta in [1.0, 1.0, -1.0] {
+            update_zoom(&hover_scroll_input(cursor, delta), &mut info, &options);
+            // Settle on the target before the next step
+            info.zoom = info.target_zoom;
+            info.pan_offset = info.target_pan_offset;
+
+            let after = canvas_point(&info, cursor);
+            assert!((after - before).length() < 1e-3, "{before:?} -> {after:?}");
+        }
+        assert!((info.zoom - 1.1 * 1.1 * 0.9).abs() < 1e-6);
+    }
+
+    /// Drags the canvas by `delta` in `steps` pointer moves
+    fn drag(info: &mut ViewportInfo, canvas_dimensions: (u32, u32), delta: Vec2, steps: u32) {
+        let mut pos = Pos2::new(400.0, 300.0);
+        info.last_mouse_pos = Some(pos);
+        for _ in 0..steps {
+            pos += delta / steps as f32;
+            info.mouse_pos = Some(pos);
+            update_pan_offset(info, canvas_dimensions);
+        }
+    }
+
+    #[test]
+    fn test_pan_reaches_far_corner_of_large_canvas() {
+        // 100x100 cells of 20px on an 800x600 viewport
+        let canvas = (100, 100);
+        let half_canvas = Vec2::splat(1000.0);
+        let half_view = Vec2::new(400.0, 300.0);
+
+        let mut info = viewport_info();
+        drag(&mut info, canvas, Vec2::new(-5000.0, -5000.0), 50);
+
+        // The bottom right corner is inside the viewport, with the canvas
+        // still covering at least the margin
+        let corner = info.pan_offset() + half_canvas;
+        assert!(corner.x <= half_view.x && corner.y <= half_view.y);
+        assert!(corner.x >= -half_view.x + PAN_MARGIN - 1e-3);
+        assert!(corner.y >= -half_view.y + PAN_MARGIN - 1e-3);
+
+        // Dragging the other way reveals the top left corner
+        drag(&mut info, canvas, Vec2::new(5000.0, 5000.0), 50);
+        let corner = info.pan_offset() - half_canvas;
+        assert!(corner.x >= -half_view.x && corner.y >= -half_view.y);
+    }
+
+    #[test]
+    fn test_pan_keeps_small_canvas_in_view() {
+        // 4x4 cells, 80px across
+        let bounds = pan_bounds((4, 4), CELL_SIZE, Vec2::new(800.0, 600.0));
+        assert_eq!(bounds, Vec2::new(400.0 + 40.0 - 32.0, 300.0 + 40.0 - 32.0));
+
+        let mut info = viewport_info();
+        drag(&mut info, (4, 4), Vec2::new(3000.0, 0.0), 10);
+        assert_eq!(info.pan_offset().x, bounds.x);
+
+        // A canvas narrower than the margin stays entirely in view
+        let bounds = pan_bounds((1, 1), CELL_SIZE, Vec2::new(800.0, 600.0));
+        assert_eq!(bounds, Vec2::new(400.0 - 10.0, 300.0 - 10.0));
+    }
+
+    #[test]
+    fn test_zoom_converges_within_bounds() {
+        let options = ViewportOptions {
+            zoom_speed: 0.5,
+            min_zoom: 0.5,
+            max_zoom: 2.0,
+            ..ViewportOptions::new()
+        };
+
+        // Scrolling in far past the bound stops at max_zoom
+        let mut info = viewport_info();
+        for _ in 0..20 {
+            update_zoom(&scroll_input(1.0), &mut info, &options);
+        }
+        assert_eq!(info.target_zoom, 2.0);
+        for _ in 0..30 {
+            update_zoom(&scroll_input(0.0), &mut info, &options);
+            assert!(info.zoom <= 2.0);
+        }
+        assert!((info.zoom - 2.0).abs() < 1e-3);
+
+        // A speed above 1 snaps to the target instead of overshooting
+        let options = ViewportOptions {
+            zoom_speed: 5.0,
+            ..options
+        };
+        for _ in 0..20 {
+            update_zoom(&scroll_input(-1.0), &mut info, &options);
+            assert!(info.zoom >= 0.5 && info.zoom <= 2.0);
+        }
+        assert_eq!(info.target_zoom, 0.5);
+        update_zoom(&scroll_input(0.0), &mut info, &options);
+        assert!((info.zoom - 0.5).abs() < 1e-6);
+
+        info.set_zoom(100.0, &options);
+        assert_eq!(info.zoom(), 2.0);
+    }
+
+    #[test]
+    fn test_zoom_invert_scroll() {
+        let options = ViewportOptions {
+            invert_scroll: true,
+            ..ViewportOptions::new()
+        };
+
+        let mut info = viewport_info();
+        update_zoom(&scroll_input(1.0), &mut info, &options);
+        assert!((info.target_zoom - 0.9).abs() < 1e-6);
+    }
+}
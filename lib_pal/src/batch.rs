@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use log::{debug, error, info};
+use thiserror::Error;
+
+use crate::image::decoder::DecodeError;
+use crate::image::encoder::EncodingError;
+use crate::image::migrate::ReEncodeError;
+use crate::re_encode;
+
+#[derive(Error, Debug)]
+pub enum BatchError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Failed to decode file: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("Failed to encode file: {0}")]
+    Encode(#[from] EncodingError),
+}
+
+impl From<ReEncodeError> for BatchError {
+    fn from(err: ReEncodeError) -> Self {
+        match err {
+            ReEncodeError::Decode(err) => BatchError::Decode(err),
+            ReEncodeError::Encode(err) => BatchError::Encode(err),
+        }
+    }
+}
+
+/// Outcome of re-encoding a single file
+#[derive(Debug)]
+pub struct BatchResult {
+    pub path: PathBuf,
+    pub original_size: usize,
+    pub reencoded_size: usize,
+    /// Whether the file was overwritten. Files are only replaced when the
+    /// new encoding is smaller.
+    pub written: bool,
+}
+
+/// Re-encodes `.pxc` files in place across `threads` worker threads.
+///
+/// Each file gets its own slot in the returned list, in the same order as
+/// `paths`, so one failing file doesn't stop the rest of the batch.
+pub fn batch_reencode(paths: &[PathBuf], threads: usize) -> Vec<Result<BatchResult, BatchError>> {
+    info!(
+        "Re-encoding {} files on {} threads",
+        paths.len(),
+        threads.max(1)
+    );
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, paths.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+
+                let result = reencode_file(path);
+                if let Err(err) = &result {
+                    error!("Failed to re-encode {}: {}", path.display(), err);
+                }
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("every path is processed"))
+        .collect()
+}
+
+fn reencode_file(path: &Path) -> Result<BatchResult, BatchError> {
+    let original = fs::read(path)?;
+    let reencoded = re_encode(&original)?;
+    let written = reencoded.len() < original.len();
+    if written {
+        fs::write(path, &reencoded)?;
+    }
+    debug!(
+        "Re-encoded {}: {} -> {} bytes",
+        path.display(),
+        original.len(),
+        reencoded.len()
+    );
+
+    Ok(BatchResult {
+        path: path.to_path_buf(),
+        original_size: original.len(),
+        reencoded_size: reencoded.len(),
+        written,
+    })
+}
@@ -0,0 +1,160 @@
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+/// Packs values into bytes MSB first, the first bit written landing in the
+/// top bit of the first byte
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    current: u8,
+    /// Bits of `current` already filled, always below 8
+    filled: u8,
+}
+
+impl BitWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_bit(&mut self, bit: bool) {
+        self.current = (self.current << 1) | bit as u8;
+        self.filled += 1;
+        if self.filled == 8 {
+            self.bytes.push(self.current);
+            self.current = 0;
+            self.filled = 0;
+        }
+    }
+
+    /// Writes the low `count` bits of `value`, most significant first.
+    ///
+    /// # Panics
+    /// If `count` is greater than 32
+    pub fn write_bits(&mut self, value: u32, count: u32) {
+        assert!(count <= u32::BITS, "cannot write {count} bits of a u32");
+        for shift in (0..count).rev() {
+            self.write_bit(value >> shift & 1 != 0);
+        }
+    }
+
+    /// Number of bits written so far
+    pub fn bit_len(&self) -> usize {
+        self.bytes.len() * 8 + self.filled as usize
+    }
+
+    /// Pads the last byte with zeros and returns the bytes along with the
+    /// number of padding bits, which is below 8
+    pub fn finish(mut self) -> (Vec<u8>, u8) {
+        if self.filled == 0 {
+            return (self.bytes, 0);
+        }
+        let padding = 8 - self.filled;
+        self.bytes.push(self.current << padding);
+        (self.bytes, padding)
+    }
+}
+
+/// Reads values written by [`BitWriter`], stopping before the padding bits
+/// at the end of the last byte
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    end: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Returns `None` if `padding_bits` is not below 8 or exceeds the data
+    pub fn new(data: &'a [u8], padding_bits: u8) -> Option<Self> {
+        let end = (data.len() * 8).checked_sub(padding_bits as usize)?;
+        (padding_bits < 8).then_some(Self {
+            data,
+            position: 0,
+            end,
+        })
+    }
+
+    pub fn read_bit(&mut self) -> Option<bool> {
+        if self.position >= self.end {
+            return None;
+        }
+        let byte = self.data[self.position / 8];
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Some(bit)
+    }
+
+    /// Reads a `count`-bit value, most significant bit first. Returns `None`
+    /// without consuming anything if fewer than `count` bits remain.
+    ///
+    /// # Panics
+    /// If `count` is greater than 32
+    pub fn read_bits(&mut self, count: u32) -> Option<u32> {
+        assert!(count <= u32::BITS, "cannot read {count} bits into a u32");
+        if self.remaining() < count as usize {
+            return None;
+        }
+        let mut value = 0u64;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Some(value as u32)
+    }
+
+    /// Bits left before the padding
+    pub fn remaining(&self) -> usize {
+        self.end - self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bits_across_byte_boundaries() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0b101, 3);
+        writer.write_bits(0x1ff, 9);
+        writer.write_bit(false);
+        writer.write_bits(0xdead_beef, 32);
+        assert_eq!(writer.bit_len(), 45);
+
+        let (bytes, padding) = writer.finish();
+        assert_eq!(bytes.len(), 6);
+        assert_eq!(padding, 3);
+        assert_eq!(&bytes[..2], &[0b1011_1111, 0b1111_0110]);
+
+        let mut reader = BitReader::new(&bytes, padding).unwrap();
+        assert_eq!(reader.read_bits(3), Some(0b101));
+        assert_eq!(reader.read_bits(9), Some(0x1ff));
+        assert_eq!(reader.read_bit(), Some(false));
+        assert_eq!(reader.read_bits(32), Some(0xdead_beef));
+        assert_eq!(reader.remaining(), 0);
+        assert_eq!(reader.read_bit(), None);
+    }
+
+    #[test]
+    fn test_whole_bytes_need_no_padding() {
+        let mut writer = BitWriter::new();
+        writer.write_bits(0xabcd, 16);
+        assert_eq!(writer.finish(), (vec![0xab, 0xcd], 0));
+    }
+
+    #[test]
+    fn test_read_stops_at_padding() {
+        let mut reader = BitReader::new(&[0b1100_0000], 6).unwrap();
+        assert_eq!(reader.read_bits(3), None);
+        assert_eq!(reader.remaining(), 2);
+        assert_eq!(reader.read_bits(2), Some(0b11));
+        assert_eq!(reader.read_bits(0), Some(0));
+        assert_eq!(reader.read_bit(), None);
+    }
+
+    #[test]
+    fn test_reader_rejects_invalid_padding() {
+        assert!(BitReader::new(&[0], 8).is_none());
+        assert!(BitReader::new(&[], 1).is_none());
+        assert!(BitReader::new(&[], 0).is_some());
+    }
+}
@@ -1,14 +1,22 @@
-use std::cmp::Reverse;
-use std::collections::BinaryHeap;
-use std::collections::HashMap;
+use alloc::collections::BinaryHeap;
+use core::cmp::Reverse;
 use thiserror::Error;
 
+use super::bitio::{BitReader, BitWriter};
+use super::HashMap;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
 #[derive(Error, Debug)]
 pub enum HuffmanError {
     #[error("Input data is empty")]
     EmptyInput,
     #[error("Failed to create Huffman tree")]
     TreeCreationFailed,
+    #[error("Invalid padding: {0} bits")]
+    InvalidPadding(u8),
+    #[error("Encoded data contains a bit sequence that matches no code")]
+    InvalidCode,
 }
 
 // Node structure for Huffman tree
@@ -29,13 +37,13 @@ impl PartialEq for HuffmanNode {
 impl Eq for HuffmanNode {}
 
 impl PartialOrd for HuffmanNode {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.frequency.cmp(&other.frequency))
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
     }
 }
 
 impl Ord for HuffmanNode {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
         self.frequency.cmp(&other.frequency)
     }
 }
@@ -45,6 +53,80 @@ pub struct HuffmanCode {
     pub encoded_data: Vec<u8>,
     pub padding_bits: u8,
 }
+
+/// The parts of a `HuffmanCode` needed to decode data stored elsewhere
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffmanTable {
+    pub encoding_map: HashMap<u8, Vec<bool>>,
+    pub padding_bits: u8,
+}
+
+impl HuffmanTable {
+    /// Writes the padding bit count and symbol count, then each symbol with
+    /// its code length. The codes are canonical, so the lengths are enough to
+    /// rebuild them.
+    ///
+    /// At most 256 symbols have codes of at most 255 bits, so the counts
+    /// always fit.
+    pub(crate) fn write_to(&self, out: &mut Vec<u8>) {
+        out.push(self.padding_bits);
+        out.extend_from_slice(&(self.encoding_map.len() as u16).to_be_bytes());
+
+        // Sorted so the same table always produces the same bytes
+        let mut symbols: Vec<_> = self.encoding_map.iter().collect();
+        symbols.sort_by_key(|&(&value, _)| value);
+
+        for (&value, code) in symbols {
+            out.push(value);
+            out.push(code.len() as u8);
+        }
+    }
+
+    /// Reverses `write_to`, advancing `cursor` past the table. Returns `None`
+    /// if the table is truncated or its lengths don't form a prefix code.
+    pub(crate) fn read_from(data: &[u8], cursor: &mut usize) -> Option<Self> {
+        let padding_bits = *data.get(*cursor)?;
+        *cursor += 1;
+        if padding_bits > 7 {
+            return None;
+        }
+
+        let symbol_count = u16::from_be_bytes([*data.get(*cursor)?, *data.get(*cursor + 1)?]);
+        let symbol_count = symbol_count as usize;
+        *cursor += 2;
+        if symbol_count == 0 || symbol_count > 256 {
+            return None;
+        }
+
+        let mut lengths = HashMap::with_capacity(symbol_count);
+        for _ in 0..symbol_count {
+            let value = *data.get(*cursor)?;
+            let code_length = *data.get(*cursor + 1)?;
+            *cursor += 2;
+
+            // Every symbol may only appear once
+            if code_length == 0 || lengths.insert(value, code_length).is_some() {
+                return None;
+            }
+        }
+        let encoding_map = canonical_codes(&lengths);
+
+        // Lengths that don't form a valid prefix code make canonical codes
+        // wrap around and collide. After sorting, a code that prefixes
+        // another sorts right before one of its extensions.
+        let mut codes: Vec<&Vec<bool>> = encoding_map.values().collect();
+        codes.sort();
+        if codes.windows(2).any(|pair| pair[1].starts_with(pair[0])) {
+            return None;
+        }
+
+        Some(Self {
+            encoding_map,
+            padding_bits,
+        })
+    }
+}
+
 pub fn huffman_encode(data: &[u8]) -> Result<HuffmanCode, HuffmanError> {
     if data.is_empty() {
         return Err(HuffmanError::EmptyInput);
@@ -56,49 +138,38 @@ pub fn huffman_encode(data: &[u8]) -> Result<HuffmanCode, HuffmanError> {
         *frequencies.entry(byte).or_insert(0) += 1;
     }
 
-    // Special case: if there's only one unique symbol
-    if frequencies.len() == 1 {
+    let encoding_map = if frequencies.len() == 1 {
+        // A single symbol gets a one-bit code, so the data packs to zeros
         let (&symbol, _) = frequencies.iter().next().unwrap();
         let mut encoding_map = HashMap::new();
-        encoding_map.insert(symbol, vec![false]); // Use a single bit for the only symbol
-
-        // Pack the encoded data
-        let data_length = data.len();
-        let full_bytes = data_length / 8;
-        let remaining_bits = data_length % 8;
-
-        let mut encoded_data = vec![
-            0;
-            if remaining_bits > 0 {
-                full_bytes + 1
-            } else {
-                full_bytes
-            }
-        ];
-
-        // For single-symbol case, we just need to pack the same bit value
-        for i in 0..full_bytes {
-            encoded_data[i] = 0; // All bits are 0 since we used false above
-        }
+        encoding_map.insert(symbol, vec![false]);
+        encoding_map
+    } else {
+        build_codes(&frequencies)?
+    };
 
-        if remaining_bits > 0 {
-            encoded_data[full_bytes] = 0;
+    // Encode the data
+    let mut writer = BitWriter::new();
+    for &byte in data {
+        for &bit in &encoding_map[&byte] {
+            writer.write_bit(bit);
         }
-
-        return Ok(HuffmanCode {
-            encoding_map,
-            encoded_data,
-            padding_bits: if remaining_bits > 0 {
-                8 - remaining_bits as u8
-            } else {
-                0
-            },
-        });
     }
+    let (encoded_data, padding_bits) = writer.finish();
 
+    Ok(HuffmanCode {
+        encoding_map,
+        encoded_data,
+        padding_bits,
+    })
+}
+
+/// Builds a Huffman tree over at least two symbols and returns canonical
+/// codes with the lengths it assigns
+fn build_codes(frequencies: &HashMap<u8, usize>) -> Result<HashMap<u8, Vec<bool>>, HuffmanError> {
     // Create priority queue with nodes
     let mut heap = BinaryHeap::new();
-    for (&value, &freq) in &frequencies {
+    for (&value, &freq) in frequencies {
         heap.push(Reverse(HuffmanNode {
             frequency: freq,
             value: Some(value),
@@ -123,47 +194,93 @@ pub fn huffman_encode(data: &[u8]) -> Result<HuffmanCode, HuffmanError> {
 
     let root = heap.pop().ok_or(HuffmanError::TreeCreationFailed)?.0;
 
-    // Generate encoding map
-    let mut encoding_map = HashMap::new();
-    generate_codes(&root, &mut Vec::new(), &mut encoding_map);
+    // Only the code lengths come from the tree; the codes themselves are
+    // canonical so they can be rebuilt from the lengths alone
+    let mut tree_codes = HashMap::new();
+    generate_codes(&root, &mut Vec::new(), &mut tree_codes);
+    let lengths: HashMap<u8, u8> = tree_codes
+        .iter()
+        .map(|(&value, code)| (value, code.len() as u8))
+        .collect();
+    Ok(canonical_codes(&lengths))
+}
 
-    // Encode the data
-    let mut encoded_bits: Vec<bool> = Vec::new();
-    for &byte in data {
-        encoded_bits.extend(encoding_map.get(&byte).unwrap());
+/// Assigns canonical Huffman codes: symbols are ordered by code length, then
+/// value, and receive sequential codes, each shifted left whenever the length
+/// grows.
+pub fn canonical_codes(lengths: &HashMap<u8, u8>) -> HashMap<u8, Vec<bool>> {
+    let mut symbols: Vec<(u8, u8)> = lengths.iter().map(|(&value, &len)| (len, value)).collect();
+    symbols.sort_unstable();
+
+    let mut codes = HashMap::with_capacity(symbols.len());
+    let mut code: Vec<bool> = Vec::new();
+    for (i, &(len, value)) in symbols.iter().enumerate() {
+        if i > 0 {
+            increment(&mut code);
+        }
+        code.resize(len as usize, false);
+        codes.insert(value, code.clone());
+    }
+    codes
+}
+
+/// Adds one to a big-endian bit vector, wrapping on overflow
+fn increment(code: &mut [bool]) {
+    for bit in code.iter_mut().rev() {
+        *bit = !*bit;
+        if *bit {
+            break;
+        }
     }
+}
 
-    // Pack bits into bytes
-    let mut encoded_data = Vec::new();
-    let mut current_byte = 0u8;
-    let mut bit_count = 0;
+/// Reverses `huffman_encode`, reading `encoded_data` bit by bit up to the
+/// padding at the end of the last byte.
+pub fn huffman_decode(code: &HuffmanCode) -> Result<Vec<u8>, HuffmanError> {
+    match decode_prefix(code)? {
+        (result, None) => Ok(result),
+        (_, Some(err)) => Err(err),
+    }
+}
 
-    for &bit in &encoded_bits {
-        current_byte = (current_byte << 1) | (bit as u8);
-        bit_count += 1;
+/// Like `huffman_decode`, but returns the values decoded before the first
+/// invalid or incomplete code instead of failing, e.g. for truncated data
+pub fn huffman_decode_partial(code: &HuffmanCode) -> Result<Vec<u8>, HuffmanError> {
+    decode_prefix(code).map(|(result, _)| result)
+}
 
-        if bit_count == 8 {
-            encoded_data.push(current_byte);
-            current_byte = 0;
-            bit_count = 0;
+/// Decodes values until the data ends or stops matching the codes, returning
+/// them along with the error that stopped decoding early, if any
+fn decode_prefix(code: &HuffmanCode) -> Result<(Vec<u8>, Option<HuffmanError>), HuffmanError> {
+    let mut reader = BitReader::new(&code.encoded_data, code.padding_bits)
+        .ok_or(HuffmanError::InvalidPadding(code.padding_bits))?;
+
+    let decoding_map: HashMap<&[bool], u8> = code
+        .encoding_map
+        .iter()
+        .map(|(&value, bits)| (bits.as_slice(), value))
+        .collect();
+    let max_code_length = code.encoding_map.values().map(Vec::len).max().unwrap_or(0);
+
+    let mut result = Vec::new();
+    let mut current_code = Vec::with_capacity(max_code_length);
+    while let Some(bit) = reader.read_bit() {
+        current_code.push(bit);
+
+        if let Some(&value) = decoding_map.get(current_code.as_slice()) {
+            result.push(value);
+            current_code.clear();
+        } else if current_code.len() >= max_code_length {
+            return Ok((result, Some(HuffmanError::InvalidCode)));
         }
     }
 
-    // Handle padding for last byte
-    let padding_bits = if bit_count > 0 {
-        let padding = 8 - bit_count;
-        current_byte <<= padding;
-        encoded_data.push(current_byte);
-        padding as u8
-    } else {
-        0
-    };
+    // Leftover bits mean the data ended in the middle of a code
+    if !current_code.is_empty() {
+        return Ok((result, Some(HuffmanError::InvalidCode)));
+    }
 
-    Ok(HuffmanCode {
-        encoding_map,
-        encoded_data,
-        padding_bits,
-    })
+    Ok((result, None))
 }
 
 fn generate_codes(
@@ -266,7 +383,7 @@ mod tests {
         assert_eq!(code.len(), 1);
 
         // Calculate expected output size (100 bits packed into bytes)
-        let expected_bytes = (100 + 7) / 8; // Round up to nearest byte
+        let expected_bytes = 100_usize.div_ceil(8); // Round up to nearest byte
         assert_eq!(result.encoded_data.len(), expected_bytes);
 
         // Calculate expected padding
@@ -298,6 +415,62 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_canonical_codes() {
+        let lengths = HashMap::from([(b'd', 3), (b'b', 2), (b'a', 1), (b'c', 3)]);
+        let codes = canonical_codes(&lengths);
+
+        assert_eq!(codes[&b'a'], [false]);
+        assert_eq!(codes[&b'b'], [true, false]);
+        assert_eq!(codes[&b'c'], [true, true, false]);
+        assert_eq!(codes[&b'd'], [true, true, true]);
+
+        // Symbols of equal length are ordered by value
+        let lengths = HashMap::from([(9, 2), (3, 2), (7, 1)]);
+        let codes = canonical_codes(&lengths);
+        assert_eq!(codes[&7], [false]);
+        assert_eq!(codes[&3], [true, false]);
+        assert_eq!(codes[&9], [true, true]);
+    }
+
+    #[test]
+    fn test_huffman_roundtrip() {
+        let inputs: [Vec<u8>; 6] = [
+            vec![1, 1, 1, 2, 2, 3],
+            vec![5, 5, 5, 5],
+            vec![42],
+            vec![7; 100],
+            vec![1, 1, 2, 2, 3, 3, 4, 4, 5],
+            (0..=255).cycle().take(1000).collect(),
+        ];
+
+        for input in inputs {
+            let encoded = huffman_encode(&input).unwrap();
+            assert_eq!(huffman_decode(&encoded).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_huffman_decode_invalid() {
+        let mut encoded = huffman_encode(&[1, 1, 2, 3]).unwrap();
+        encoded.padding_bits = 9;
+        assert!(matches!(
+            huffman_decode(&encoded),
+            Err(HuffmanError::InvalidPadding(9))
+        ));
+
+        // Drop the padding so the trailing zero bits must be decoded as codes
+        let mut encoded = huffman_encode(&[1, 1, 1, 2, 2, 3]).unwrap();
+        encoded
+            .encoding_map
+            .retain(|_, bits| bits.iter().any(|&bit| bit));
+        encoded.padding_bits = 0;
+        assert!(matches!(
+            huffman_decode(&encoded),
+            Err(HuffmanError::InvalidCode)
+        ));
+    }
+
     fn is_prefix(shorter: &[bool], longer: &[bool]) -> bool {
         if shorter.len() >= longer.len() {
             return false;
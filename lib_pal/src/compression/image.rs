@@ -1,18 +1,36 @@
-use std::fs::File;
-use std::io::{self, Read, Write};
+use std::fs;
+use std::io;
+
+use super::lzw::{pack_codes, unpack_codes};
+use crate::image::decoder::decode_header;
+use crate::image::encoder::{write_image, ExtraFrames, Payload};
 
 pub const EXTENSION: &str = "pxc";
 
+/// A `.pxc` image kept in its compressed form.
+///
+/// Reads and writes the same layout as [`crate::encode`] and [`crate::decode`],
+/// with `data` holding the LZW codes of the payload. Only still images on the
+/// default pipeline, without metadata, can be represented.
 pub struct PXCImage {
+    /// Format version, 0 for files from before the version byte
     pub version: u8,
-    pub width: u8,
-    pub height: u8,
+    pub width: u32,
+    pub height: u32,
     pub palette: Vec<[u8; 4]>,
     pub data: Vec<u16>,
 }
 
 impl PXCImage {
-    pub fn new(version: u8, width: u8, height: u8, palette: Vec<[u8; 4]>, data: Vec<u16>) -> Self {
+    pub const FORMAT_VERSION: u8 = crate::image::format::FORMAT_VERSION;
+
+    pub fn new(
+        version: u8,
+        width: u32,
+        height: u32,
+        palette: Vec<[u8; 4]>,
+        data: Vec<u16>,
+    ) -> Self {
         Self {
             version,
             width,
@@ -22,69 +40,144 @@ impl PXCImage {
         }
     }
 
-    /// Saves the compressed file in a custom binary format
-    pub fn save(&self, filename: &str) -> io::Result<()> {
-        let mut file = File::create(filename)?;
+    /// Serializes the image into the `.pxc` file layout
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let payload = pack_codes(&self.data);
 
-        // Write metadata
-        file.write_all(&[self.version, self.width, self.height])?;
+        write_image(
+            self.width,
+            self.height,
+            &self.palette,
+            &[],
+            Payload::indices(&payload),
+            ExtraFrames::NONE,
+        )
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    }
 
-        // Write palette size and colors
-        file.write_all(&(self.palette.len() as u8).to_le_bytes())?;
-        for color in &self.palette {
-            file.write_all(color)?;
-        }
+    /// Parses an image from the `.pxc` file layout without decompressing it.
+    ///
+    /// Files with anything `to_bytes` couldn't write back are rejected as
+    /// `io::ErrorKind::InvalidData`.
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let header =
+            decode_header(bytes).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
 
-        // Write compressed data size and data
-        file.write_all(&(self.data.len() as u16).to_le_bytes())?;
-        for &code in &self.data {
-            file.write_all(&code.to_le_bytes())?;
+        // `data` holds the codes of RLE-delta output for a single frame,
+        // which `to_bytes` writes back as such
+        let unsupported = [
+            (
+                !header.rle_delta,
+                "payload was compressed without the RLE-delta stage",
+            ),
+            (header.huffman.is_some(), "payload is Huffman coded"),
+            (header.tiles.is_some(), "payload holds deduplicated tiles"),
+            (header.channel_planes, "payload holds channel planes"),
+            (header.alphas.is_some(), "payload holds split-alpha indices"),
+            (
+                !header.frames.is_empty(),
+                "image has several animation frames",
+            ),
+            (!header.metadata.is_empty(), "image has metadata"),
+        ];
+        if let Some((_, reason)) = unsupported.into_iter().find(|&(found, _)| found) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, reason));
         }
 
-        Ok(())
+        let payload = header
+            .decode_payload(bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let data = unpack_codes(&payload)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok(Self {
+            version: header.version,
+            width: header.width,
+            height: header.height,
+            palette: header.palette,
+            data,
+        })
+    }
+
+    /// Saves the compressed file in the `.pxc` format
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        fs::write(filename, self.to_bytes()?)
     }
 
-    /// Loads a compressed file from a custom binary format
+    /// Loads a compressed file in the `.pxc` format
     pub fn load(filename: &str) -> io::Result<Self> {
-        let mut file = File::open(filename)?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
-
-        // Read metadata
-        let version = buffer[0];
-        let width = buffer[1];
-        let height = buffer[2];
-
-        // Read palette
-        let palette_size = buffer[3] as usize;
-        let mut palette = Vec::new();
-        let mut offset = 4;
-        for _ in 0..palette_size {
-            palette.push([
-                buffer[offset],
-                buffer[offset + 1],
-                buffer[offset + 2],
-                buffer[offset + 3],
-            ]);
-            offset += 4;
-        }
+        Self::from_bytes(&fs::read(filename)?)
+    }
+}
 
-        // Read compressed data
-        let data_size = u16::from_le_bytes([buffer[offset], buffer[offset + 1]]) as usize;
-        offset += 2;
-        let mut data = Vec::new();
-        for _ in 0..data_size {
-            let code = u16::from_le_bytes([buffer[offset], buffer[offset + 1]]);
-            data.push(code);
-            offset += 2;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::{compress, CompressionOptions};
+    use crate::{decode, encode, encode_frames, encode_with_metadata, encode_with_options};
 
-        Ok(Self {
-            version,
-            width,
-            height,
-            palette,
-            data,
-        })
+    const DATA: [u8; 16] = [
+        255, 0, 0, 255, 0, 255, 0, 255, 255, 0, 0, 255, 0, 0, 255, 255,
+    ];
+
+    fn temp_path(name: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("pxc-image-{}-{}.pxc", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_save_decodes_with_decode() {
+        let compressed = compress(&DATA).unwrap();
+        let codes = unpack_codes(&compressed.data).unwrap();
+        let image = PXCImage::new(PXCImage::FORMAT_VERSION, 2, 2, compressed.palette, codes);
+
+        let path = temp_path("save");
+        image.save(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!((decoded.width, decoded.height), (2, 2));
+        assert_eq!(decoded.rgba_data, DATA);
+    }
+
+    #[test]
+    fn test_load_reads_encode_output() {
+        let encoded = encode(2, 2, &DATA).unwrap();
+
+        let path = temp_path("load");
+        fs::write(&path, &encoded).unwrap();
+        let image = PXCImage::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(image.version, PXCImage::FORMAT_VERSION);
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.palette.len(), 3);
+        assert_eq!(image.to_bytes().unwrap(), encoded);
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_unrepresentable_data() {
+        let huffman = encode_with_options(
+            2,
+            2,
+            &DATA,
+            &[],
+            CompressionOptions {
+                huffman: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let frames = encode_frames(2, 2, &[&DATA, &DATA]).unwrap();
+        let metadata =
+            encode_with_metadata(2, 2, &DATA, &[("key".into(), "value".into())]).unwrap();
+
+        for bytes in [huffman, frames, metadata] {
+            let err = PXCImage::from_bytes(&bytes).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
     }
 }
@@ -1,6 +1,9 @@
-use std::collections::HashMap;
 use thiserror::Error;
 
+use super::HashMap;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
 #[derive(Debug)]
 struct LZWEntry {
     prefix: Option<u16>,
@@ -19,15 +22,90 @@ pub enum LzwCompressionError {
 pub enum LzwDecompressionError {
     #[error("invalid input data: incomplete code at position {position}")]
     IncompleteCode { position: usize },
-    #[error("invalid code: code value {code} exceeds dictionary size {dict_size}")]
-    InvalidCode { code: usize, dict_size: usize },
+    #[error("invalid code at position {position}: code value {code} exceeds dictionary size {dict_size}")]
+    InvalidCode {
+        code: usize,
+        dict_size: usize,
+        position: usize,
+    },
     #[error("dictionary overflow: reached maximum code value of 65535")]
     DictionaryOverflow,
 }
 
+/// Narrowest code width, enough for the 256 single-byte codes plus one
+const MIN_CODE_WIDTH: u32 = 9;
+/// Widest code width, enough for every `u16` code
+const MAX_CODE_WIDTH: u32 = 16;
+/// Width of every code in files from before the format version byte, which
+/// stored each code as a little-endian `u16`
+const LEGACY_CODE_WIDTH: u32 = 16;
+
+/// Width in bits of the code at `index` in the stream.
+///
+/// The compressor adds one dictionary entry after every code but the last, so
+/// the `index`-th code is at most `255 + index`. Both sides derive the width
+/// from the index alone, which keeps them in lockstep.
+fn code_width(index: usize) -> u32 {
+    let max_code = 255 + index;
+    (usize::BITS - max_code.leading_zeros()).clamp(MIN_CODE_WIDTH, MAX_CODE_WIDTH)
+}
+
+/// Packs codes LSB first, each in the width given by `code_width`
+pub fn pack_codes(codes: &[u16]) -> Vec<u8> {
+    let mut packed = Vec::new();
+    let mut buffer = 0u32;
+    let mut buffered_bits = 0;
+
+    for (index, &code) in codes.iter().enumerate() {
+        buffer |= (code as u32) << buffered_bits;
+        buffered_bits += code_width(index);
+
+        while buffered_bits >= 8 {
+            packed.push(buffer as u8);
+            buffer >>= 8;
+            buffered_bits -= 8;
+        }
+    }
+
+    // Pad the last partial byte with zeros
+    if buffered_bits > 0 {
+        packed.push(buffer as u8);
+    }
+    packed
+}
+
+/// Reverses `pack_codes`. Fewer than 8 trailing bits are padding; anything
+/// longer that doesn't fill a code is an incomplete code.
+pub fn unpack_codes(data: &[u8]) -> Result<Vec<u16>, LzwDecompressionError> {
+    let mut codes = Vec::new();
+    let mut buffer = 0u32;
+    let mut buffered_bits = 0;
+    let mut bytes = data.iter();
+
+    loop {
+        let width = code_width(codes.len());
+        while buffered_bits < width {
+            let Some(&byte) = bytes.next() else {
+                if buffered_bits >= 8 {
+                    return Err(LzwDecompressionError::IncompleteCode {
+                        position: data.len() - buffered_bits.div_ceil(8) as usize,
+                    });
+                }
+                return Ok(codes);
+            };
+            buffer |= (byte as u32) << buffered_bits;
+            buffered_bits += 8;
+        }
+
+        codes.push((buffer & ((1 << width) - 1)) as u16);
+        buffer >>= width;
+        buffered_bits -= width;
+    }
+}
+
 pub fn lzw_compression(data: &[u8]) -> Result<Vec<u8>, LzwCompressionError> {
     let mut dictionary = HashMap::new();
-    let mut result = Vec::new();
+    let mut codes = Vec::new();
     let mut next_code = 256u16; // Start after single byte values
 
     // Initialize dictionary with single bytes
@@ -36,7 +114,7 @@ pub fn lzw_compression(data: &[u8]) -> Result<Vec<u8>, LzwCompressionError> {
     }
 
     if data.is_empty() {
-        return Ok(result);
+        return Ok(Vec::new());
     }
 
     let mut current = vec![data[0]];
@@ -50,7 +128,7 @@ pub fn lzw_compression(data: &[u8]) -> Result<Vec<u8>, LzwCompressionError> {
         } else {
             // Output code for current sequence
             if let Some(&code) = dictionary.get(&current) {
-                result.extend_from_slice(&code.to_le_bytes());
+                codes.push(code);
             }
 
             // Add new sequence to dictionary if we haven't hit the limit
@@ -69,91 +147,202 @@ pub fn lzw_compression(data: &[u8]) -> Result<Vec<u8>, LzwCompressionError> {
 
     // Output code for final sequence
     if let Some(&code) = dictionary.get(&current) {
-        result.extend_from_slice(&code.to_le_bytes());
+        codes.push(code);
     }
 
-    Ok(result)
+    Ok(pack_codes(&codes))
 }
 
-pub fn lzw_decompression(data: &[u8]) -> Result<Vec<u8>, LzwDecompressionError> {
-    if data.is_empty() {
-        return Ok(Vec::new());
+/// Decodes LZW data incrementally, so a large stream can be fed in blocks.
+///
+/// Codes straddling the end of a block stay buffered until the next `push`.
+pub struct LzwDecoder {
+    dictionary: Vec<LZWEntry>,
+    previous: Option<(usize, Vec<u8>)>,
+    buffer: u32,
+    buffered_bits: u32,
+    codes_read: usize,
+    /// Bits consumed by complete codes, to report error positions in bytes
+    bit_position: usize,
+    bytes_read: usize,
+    /// Reads fixed `LEGACY_CODE_WIDTH` codes instead of variable-width ones
+    legacy: bool,
+}
+
+impl Default for LzwDecoder {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    let mut dictionary = Vec::with_capacity(65535);
-    let mut result = Vec::new();
+impl LzwDecoder {
+    pub fn new() -> Self {
+        // Initialize dictionary with single bytes
+        let mut dictionary = Vec::with_capacity(65535);
+        for i in 0..256 {
+            dictionary.push(LZWEntry {
+                prefix: None,
+                suffix: i as u8,
+            });
+        }
 
-    // Initialize dictionary with single bytes
-    for i in 0..256 {
-        dictionary.push(LZWEntry {
-            prefix: None,
-            suffix: i as u8,
-        });
+        Self {
+            dictionary,
+            previous: None,
+            buffer: 0,
+            buffered_bits: 0,
+            codes_read: 0,
+            bit_position: 0,
+            bytes_read: 0,
+            legacy: false,
+        }
     }
 
-    // Process first code
-    if data.len() < 2 {
-        return Err(LzwDecompressionError::IncompleteCode { position: 0 });
+    /// Decoder for the fixed 16-bit code layout of files from before the
+    /// format version byte
+    pub fn legacy() -> Self {
+        Self {
+            legacy: true,
+            ..Self::new()
+        }
     }
 
-    let mut previous_code = u16::from_le_bytes([data[0], data[1]]) as usize;
-    if previous_code >= dictionary.len() {
-        return Err(LzwDecompressionError::InvalidCode {
-            code: previous_code,
-            dict_size: dictionary.len(),
-        });
+    fn code_width(&self) -> u32 {
+        if self.legacy {
+            LEGACY_CODE_WIDTH
+        } else {
+            code_width(self.codes_read)
+        }
     }
 
-    let mut previous_string = get_string(&dictionary, previous_code);
-    result.extend(&previous_string);
+    /// Feeds the next block of compressed bytes and returns the bytes of
+    /// every code it completes
+    pub fn push(&mut self, bytes: &[u8]) -> Result<Vec<u8>, LzwDecompressionError> {
+        let mut result = Vec::new();
+
+        for &byte in bytes {
+            self.buffer |= (byte as u32) << self.buffered_bits;
+            self.buffered_bits += 8;
+            self.bytes_read += 1;
+
+            let width = self.code_width();
+            if self.buffered_bits >= width {
+                let code = (self.buffer & ((1 << width) - 1)) as usize;
+                self.buffer >>= width;
+                self.buffered_bits -= width;
 
-    let mut next_code = 256u16;
+                let position = self.bit_position / 8;
+                self.bit_position += width as usize;
+                self.codes_read += 1;
 
-    // Process remaining codes
-    for (chunk_index, chunk) in data[2..].chunks(2).enumerate() {
-        if chunk.len() < 2 {
+                self.decode_code(code, position, &mut result)?;
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Checks that the stream ended on a code boundary. Fewer than 8 leftover
+    /// bits are padding; anything longer is an incomplete code.
+    pub fn finish(self) -> Result<(), LzwDecompressionError> {
+        if self.buffered_bits >= 8 {
             return Err(LzwDecompressionError::IncompleteCode {
-                position: 2 + chunk_index * 2,
+                position: self.bytes_read - self.buffered_bits.div_ceil(8) as usize,
             });
         }
+        Ok(())
+    }
 
-        let current_code = u16::from_le_bytes([chunk[0], chunk[1]]) as usize;
+    /// Every code, including the first, is validated against the dictionary
+    /// as it stands when the code is read
+    fn decode_code(
+        &mut self,
+        current_code: usize,
+        position: usize,
+        result: &mut Vec<u8>,
+    ) -> Result<(), LzwDecompressionError> {
+        let dictionary = &mut self.dictionary;
 
         // Get the current string
-        let current_string = if current_code < dictionary.len() {
-            get_string(&dictionary, current_code)
-        } else if current_code == dictionary.len() && next_code < 65535 {
-            // Special case: current code is next code to be added
-            let mut s = previous_string.clone();
-            s.push(previous_string[0]);
-            s
-        } else {
-            return Err(LzwDecompressionError::InvalidCode {
-                code: current_code,
-                dict_size: dictionary.len(),
-            });
+        let current_string = match &self.previous {
+            _ if current_code < dictionary.len() => get_string(dictionary, current_code),
+            // Special case: current code is next code to be added, which
+            // needs a previous string to be built from
+            Some((_, previous_string))
+                if current_code == dictionary.len() && dictionary.len() < 65535 =>
+            {
+                let mut s = previous_string.clone();
+                s.push(previous_string[0]);
+                s
+            }
+            _ => {
+                return Err(LzwDecompressionError::InvalidCode {
+                    code: current_code,
+                    dict_size: dictionary.len(),
+                    position,
+                });
+            }
         };
 
         result.extend(&current_string);
 
         // Add new code to dictionary if we haven't hit the limit
-        if next_code < 65535 {
-            dictionary.push(LZWEntry {
-                prefix: Some(previous_code as u16),
-                suffix: current_string[0],
-            });
-            next_code += 1;
-        } else {
-            return Err(LzwDecompressionError::DictionaryOverflow);
+        if let Some((previous_code, _)) = self.previous {
+            if dictionary.len() < 65535 {
+                dictionary.push(LZWEntry {
+                    prefix: Some(previous_code as u16),
+                    suffix: current_string[0],
+                });
+            } else {
+                return Err(LzwDecompressionError::DictionaryOverflow);
+            }
         }
 
-        previous_code = current_code;
-        previous_string = current_string;
+        self.previous = Some((current_code, current_string));
+        Ok(())
     }
+}
+
+pub fn lzw_decompression(data: &[u8]) -> Result<Vec<u8>, LzwDecompressionError> {
+    decompress_with(LzwDecoder::new(), data)
+}
+
+/// Like `lzw_decompression`, but for the fixed 16-bit codes of files from
+/// before the format version byte
+pub fn lzw_decompression_legacy(data: &[u8]) -> Result<Vec<u8>, LzwDecompressionError> {
+    decompress_with(LzwDecoder::legacy(), data)
+}
 
+fn decompress_with(mut decoder: LzwDecoder, data: &[u8]) -> Result<Vec<u8>, LzwDecompressionError> {
+    let result = decoder.push(data)?;
+    decoder.finish()?;
     Ok(result)
 }
 
+/// Like `lzw_decompression`, but returns the bytes of every code decoded
+/// before the first invalid one instead of failing, e.g. for truncated data
+pub fn lzw_decompression_partial(data: &[u8]) -> Vec<u8> {
+    decompress_partial_with(LzwDecoder::new(), data)
+}
+
+/// Like `lzw_decompression_partial`, but for the fixed 16-bit codes of files
+/// from before the format version byte
+pub fn lzw_decompression_legacy_partial(data: &[u8]) -> Vec<u8> {
+    decompress_partial_with(LzwDecoder::legacy(), data)
+}
+
+fn decompress_partial_with(mut decoder: LzwDecoder, data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+    // Every byte completes at most one code, so nothing valid is lost
+    for byte in data {
+        match decoder.push(core::slice::from_ref(byte)) {
+            Ok(bytes) => result.extend(bytes),
+            Err(_) => break,
+        }
+    }
+    result
+}
+
 fn get_string(dictionary: &[LZWEntry], mut code: usize) -> Vec<u8> {
     let mut result = Vec::new();
 
@@ -202,8 +391,70 @@ mod tests {
         let result = lzw_decompression(&[0xFF, 0xFF]);
         assert!(matches!(
             result,
-            Err(LzwDecompressionError::InvalidCode { code: 65535, .. })
+            Err(LzwDecompressionError::InvalidCode { code: 511, .. })
+        ));
+    }
+
+    #[test]
+    fn test_lzw_decomp_first_code_not_yet_built() {
+        // Code 256 is only valid once a previous string exists to build it from
+        let result = lzw_decompression(&pack_codes(&[256]));
+        assert!(matches!(
+            result,
+            Err(LzwDecompressionError::InvalidCode {
+                code: 256,
+                dict_size: 256,
+                position: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_lzw_decomp_invalid_code_position() {
+        // 'a', then 256 ("aa"), then 258 which skips past the next entry (257)
+        let data = pack_codes(&[0x61, 256, 258]);
+        let result = lzw_decompression(&data);
+        assert!(matches!(
+            result,
+            Err(LzwDecompressionError::InvalidCode {
+                code: 258,
+                dict_size: 257,
+                position: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_lzw_decomp_partial() {
+        let data: Vec<u8> = (0..200u8).chain(0..200).collect();
+        let compressed = lzw_compression(&data).unwrap();
+        assert_eq!(lzw_decompression_partial(&compressed), data);
+
+        // A truncated stream decodes to a prefix of the input
+        let partial = lzw_decompression_partial(&compressed[..compressed.len() / 2]);
+        assert!(!partial.is_empty());
+        assert!(data.starts_with(&partial));
+
+        // Decoding stops at an invalid code
+        assert_eq!(lzw_decompression_partial(&[0x41, 0xfe, 0xff]), [0x41]);
+    }
+
+    #[test]
+    fn test_lzw_decomp_legacy() {
+        // 'a', 'b', 256 ("ab") and 258 ("aba"), each a little-endian u16
+        let data = [0x61, 0x00, 0x62, 0x00, 0x00, 0x01, 0x02, 0x01];
+        assert_eq!(lzw_decompression_legacy(&data).unwrap(), b"abababa");
+        assert_eq!(lzw_decompression_legacy_partial(&data), b"abababa");
+
+        // The variable-width layout reads the same bytes differently
+        assert_ne!(lzw_decompression(&data).ok(), Some(b"abababa".to_vec()));
+
+        // An odd trailing byte is half a code
+        assert!(matches!(
+            lzw_decompression_legacy(&data[..7]),
+            Err(LzwDecompressionError::IncompleteCode { position: 6 })
         ));
+        assert_eq!(lzw_decompression_legacy_partial(&data[..7]), b"abab");
     }
 
     #[test]
@@ -214,6 +465,67 @@ mod tests {
         assert_eq!(original, decompressed);
     }
 
+    #[test]
+    fn test_lzw_variable_width_smaller_than_fixed() {
+        let original = b"Hello, World!".to_vec();
+        let compressed = lzw_compression(&original).unwrap();
+
+        let codes = unpack_codes(&compressed).unwrap();
+        assert!(compressed.len() < codes.len() * 2);
+        assert_eq!(lzw_decompression(&compressed).unwrap(), original);
+    }
+
+    #[test]
+    fn test_pack_codes_width_growth() {
+        assert_eq!(code_width(0), 9);
+        assert_eq!(code_width(256), 9);
+        assert_eq!(code_width(257), 10);
+        assert_eq!(code_width(65_279), 16);
+        assert_eq!(code_width(100_000), 16);
+
+        // Enough codes to cross several width boundaries
+        let codes: Vec<u16> = (0..2000).map(|i| (i % 256) as u16).collect();
+        assert_eq!(unpack_codes(&pack_codes(&codes)).unwrap(), codes);
+    }
+
+    #[test]
+    fn test_lzw_long_input_roundtrip() {
+        let data: Vec<u8> = (0..20_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let compressed = lzw_compression(&data).unwrap();
+        assert_eq!(lzw_decompression(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_lzw_decoder_byte_at_a_time() {
+        let data: Vec<u8> = (0..5_000u32).map(|i| (i * 13 % 97) as u8).collect();
+        let compressed = lzw_compression(&data).unwrap();
+
+        let mut decoder = LzwDecoder::new();
+        let mut streamed = Vec::new();
+        for byte in &compressed {
+            streamed.extend(decoder.push(core::slice::from_ref(byte)).unwrap());
+        }
+        decoder.finish().unwrap();
+
+        assert_eq!(streamed, lzw_decompression(&compressed).unwrap());
+        assert_eq!(streamed, data);
+    }
+
+    #[test]
+    fn test_lzw_decoder_buffers_partial_code() {
+        let compressed = lzw_compression(b"Hello, World!").unwrap();
+
+        // The first 9-bit code isn't complete until the second byte arrives
+        let mut decoder = LzwDecoder::new();
+        assert!(decoder.push(&compressed[..1]).unwrap().is_empty());
+        let mut decoded = decoder.push(&compressed[1..2]).unwrap();
+        assert_eq!(decoded, b"H");
+
+        decoded.extend(decoder.push(&compressed[2..]).unwrap());
+        decoder.finish().unwrap();
+        assert_eq!(decoded, b"Hello, World!");
+    }
+
     #[test]
     fn test_lzw_empty() {
         let data = vec![];
@@ -1,14 +1,28 @@
+pub mod bitio;
 pub mod huffman;
+#[cfg(feature = "std")]
 pub mod image;
 pub mod lzw;
 pub mod palette;
+pub mod planes;
 pub mod rle_delta;
+pub mod tiles;
 
-use log::{debug, error, info};
+use huffman::{HuffmanCode, HuffmanError, HuffmanTable};
+use log::{debug, info};
 use lzw::{LzwCompressionError, LzwDecompressionError};
 use palette::{PaletteCompressionError, PaletteDecompressionError};
+use planes::PlaneError;
 use rle_delta::{RleCompressionError, RleDecompressionError};
 use thiserror::Error;
+use tiles::{TileError, TileOptions};
+
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+#[cfg(not(feature = "std"))]
+pub(crate) use hashbrown::HashMap;
+#[cfg(feature = "std")]
+pub(crate) use std::collections::HashMap;
 
 #[derive(Error, Debug)]
 pub enum CompressionError {
@@ -18,6 +32,12 @@ pub enum CompressionError {
     RleDeltaCompressionFailed(#[from] RleCompressionError),
     #[error("LZW compression failed")]
     LzwCompressionFailed(#[from] LzwCompressionError),
+    #[error("Huffman compression failed")]
+    HuffmanCompressionFailed(#[from] HuffmanError),
+    #[error("Tile deduplication failed")]
+    TileDeduplicationFailed(#[from] TileError),
+    #[error("Channel plane encoding failed")]
+    ChannelPlanesFailed(#[from] PlaneError),
 }
 
 #[derive(Error, Debug)]
@@ -28,21 +48,289 @@ pub enum DecompressionError {
     RleDeltaDecompressionFailed(#[from] RleDecompressionError),
     #[error("LZW decompression failed")]
     LzwDecompressionFailed(#[from] LzwDecompressionError),
+    #[error("Huffman decompression failed")]
+    HuffmanDecompressionFailed(#[from] HuffmanError),
+    #[error("Tile expansion failed")]
+    TileExpansionFailed(#[from] TileError),
+    #[error("Channel plane decoding failed")]
+    ChannelPlanesFailed(#[from] PlaneError),
+    #[error("Invalid or truncated serialized compression result")]
+    InvalidSerializedResult,
+}
+
+/// Optional stages of the compression pipeline
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompressionOptions {
+    /// Huffman-code the LZW output as a final stage
+    pub huffman: bool,
+    /// Sort the palette by luminance so similar colors get nearby indices
+    pub sort_palette: bool,
+    /// Feed the palette indices straight to LZW. Noisy images can come out
+    /// smaller without the RLE-delta stage.
+    pub skip_rle_delta: bool,
+    /// Deduplicate repeated tiles of the palette indices before the later
+    /// stages, which pays off on tilemaps that LZW misses in row-major order
+    pub tiles: Option<TileOptions>,
+    /// Skip the palette and compress delta-coded RGBA channel planes
+    /// instead, see `planes::channel_planes`. Suits smooth images with more
+    /// than 256 colors. Tiling only applies to palette indices, so `tiles`
+    /// is ignored.
+    pub channel_planes: bool,
+    /// Index RGB and alpha separately, see
+    /// `palette::split_alpha_palette_compression`, so a color used at many
+    /// alpha levels takes one palette slot. Suits anti-aliased sprites.
+    /// `sort_palette` and `tiles` are ignored, and `channel_planes` wins.
+    pub split_alpha: bool,
 }
 
+/// Method tag bit: the data is Huffman coded
+pub const METHOD_HUFFMAN: u8 = 0b01;
+/// Method tag bit: the RLE-delta stage was skipped, so LZW ran on the raw
+/// palette indices
+pub const METHOD_NO_RLE_DELTA: u8 = 0b10;
+/// Method tag bit: the palette indices went through tile deduplication
+pub const METHOD_TILES: u8 = 0b100;
+/// Method tag bit: the data holds delta-coded RGBA channel planes instead of
+/// palette indices, and the palette is empty
+pub const METHOD_CHANNEL_PLANES: u8 = 0b1000;
+/// Method tag bit: RGB and alpha were indexed separately, the alpha table
+/// follows the Huffman table and the data holds the color indices followed by
+/// the alpha indices
+pub const METHOD_SPLIT_ALPHA: u8 = 0b1_0000;
+
 pub struct CompressionResult {
     pub palette: Vec<[u8; 4]>,
     pub data: Vec<u8>,
+    /// Code table of the Huffman stage, if it ran
+    pub huffman: Option<HuffmanTable>,
+    /// Whether the RLE-delta stage ran, see
+    /// `CompressionOptions::skip_rle_delta`
+    pub rle_delta: bool,
+    /// Whether the palette indices were tiled, see `CompressionOptions::tiles`
+    pub tiled: bool,
+    /// Whether the data holds channel planes, see
+    /// `CompressionOptions::channel_planes`
+    pub channel_planes: bool,
+    /// Alpha table of the split-alpha stage, if it ran, see
+    /// `CompressionOptions::split_alpha`. The alpha of the palette entries
+    /// is then unused.
+    pub alphas: Option<Vec<u8>>,
+}
+
+impl CompressionResult {
+    /// One-byte tag of the stages that ran, made of the `METHOD_*` bits
+    pub fn method(&self) -> u8 {
+        let mut method = 0;
+        if self.huffman.is_some() {
+            method |= METHOD_HUFFMAN;
+        }
+        if !self.rle_delta {
+            method |= METHOD_NO_RLE_DELTA;
+        }
+        if self.tiled {
+            method |= METHOD_TILES;
+        }
+        if self.channel_planes {
+            method |= METHOD_CHANNEL_PLANES;
+        }
+        if self.alphas.is_some() {
+            method |= METHOD_SPLIT_ALPHA;
+        }
+        method
+    }
+
+    /// Serializes the palette, Huffman table and data into one blob, without
+    /// the image header of a `.pxc` file.
+    ///
+    /// Layout: palette length (`u16`, big endian), the RGBA palette entries,
+    /// the method tag, the Huffman table if the tag has `METHOD_HUFFMAN`,
+    /// the alpha table length (`u16`, big endian) and entries if it has
+    /// `METHOD_SPLIT_ALPHA`, then the compressed data to the end of the blob.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + self.palette.len() * 4 + 1 + self.data.len());
+        bytes.extend_from_slice(&(self.palette.len() as u16).to_be_bytes());
+        for color in &self.palette {
+            bytes.extend_from_slice(color);
+        }
+
+        bytes.push(self.method());
+        if let Some(table) = &self.huffman {
+            table.write_to(&mut bytes);
+        }
+        if let Some(alphas) = &self.alphas {
+            bytes.extend_from_slice(&(alphas.len() as u16).to_be_bytes());
+            bytes.extend_from_slice(alphas);
+        }
+
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Reverses `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecompressionError> {
+        let invalid = || DecompressionError::InvalidSerializedResult;
+
+        let palette_len = bytes.get(..2).ok_or_else(invalid)?;
+        let palette_len = u16::from_be_bytes([palette_len[0], palette_len[1]]) as usize;
+        let mut cursor = 2;
+
+        let palette_bytes = bytes
+            .get(cursor..cursor + palette_len * 4)
+            .ok_or_else(invalid)?;
+        let palette = palette_bytes
+            .chunks_exact(4)
+            .map(|color| [color[0], color[1], color[2], color[3]])
+            .collect();
+        cursor += palette_len * 4;
+
+        let method = *bytes.get(cursor).ok_or_else(invalid)?;
+        let known = METHOD_HUFFMAN
+            | METHOD_NO_RLE_DELTA
+            | METHOD_TILES
+            | METHOD_CHANNEL_PLANES
+            | METHOD_SPLIT_ALPHA;
+        if method & !known != 0 {
+            return Err(invalid());
+        }
+        cursor += 1;
+        let huffman = if method & METHOD_HUFFMAN != 0 {
+            Some(HuffmanTable::read_from(bytes, &mut cursor).ok_or_else(invalid)?)
+        } else {
+            None
+        };
+        let alphas = if method & METHOD_SPLIT_ALPHA != 0 {
+            let len = bytes.get(cursor..cursor + 2).ok_or_else(invalid)?;
+            let len = u16::from_be_bytes([len[0], len[1]]) as usize;
+            cursor += 2;
+            let alphas = bytes.get(cursor..cursor + len).ok_or_else(invalid)?;
+            cursor += len;
+            Some(alphas.to_vec())
+        } else {
+            None
+        };
+
+        Ok(Self {
+            palette,
+            data: bytes[cursor..].to_vec(),
+            huffman,
+            rle_delta: method & METHOD_NO_RLE_DELTA == 0,
+            tiled: method & METHOD_TILES != 0,
+            channel_planes: method & METHOD_CHANNEL_PLANES != 0,
+            alphas,
+        })
+    }
+}
+
+/// Byte counts after each stage of the compression pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionStats {
+    pub input_len: usize,
+    pub palette_size: usize,
+    /// Number of palette indices, two per pixel with split alpha, or of
+    /// channel plane bytes if the palette stage was skipped
+    pub palette_indices_len: usize,
+    /// Size of the tile dictionary and map, if tiling ran
+    pub tiles_len: Option<usize>,
+    pub rle_delta_len: usize,
+    pub lzw_len: usize,
+    /// Size after Huffman coding, if that stage ran
+    pub huffman_len: Option<usize>,
 }
 
 pub fn compress(data: &[u8]) -> Result<CompressionResult, CompressionError> {
+    compress_with_options(data, CompressionOptions::default())
+}
+
+pub fn compress_with_options(
+    data: &[u8],
+    options: CompressionOptions,
+) -> Result<CompressionResult, CompressionError> {
+    compress_with_stats(data, options).map(|(result, _)| result)
+}
+
+/// Tries every combination of the optional RLE-delta and Huffman stages and
+/// returns the smallest result, like PNG picks a filter per image.
+///
+/// Noisy images often come out smaller without RLE-delta, whose runs and
+/// deltas only pay off on smooth data. Sizes are compared as serialized by
+/// `to_bytes`, so the Huffman table counts against that stage. The plain
+/// pipeline wins ties, so the result is never larger than `compress`.
+pub fn compress_best(data: &[u8]) -> Result<CompressionResult, CompressionError> {
+    let mut best: Option<(usize, CompressionResult)> = None;
+    for (skip_rle_delta, huffman) in [(false, false), (false, true), (true, false), (true, true)] {
+        let options = CompressionOptions {
+            huffman,
+            skip_rle_delta,
+            ..Default::default()
+        };
+        let result = compress_with_options(data, options)?;
+        let size = result.to_bytes().len();
+        debug!("Pipeline {:?}: {} bytes", options, size);
+        if best.as_ref().is_none_or(|(best_size, _)| size < *best_size) {
+            best = Some((size, result));
+        }
+    }
+
+    let (_, result) = best.expect("at least one pipeline ran");
+    info!("Best pipeline has method tag {:#04b}", result.method());
+    Ok(result)
+}
+
+/// Like `compress_with_options`, also returning the size of the data after
+/// each stage. Without the RLE-delta stage, `rle_delta_len` is the number of
+/// indices passed through unchanged.
+pub fn compress_with_stats(
+    data: &[u8],
+    options: CompressionOptions,
+) -> Result<(CompressionResult, CompressionStats), CompressionError> {
     info!("Starting compression");
+    let rle_delta = !options.skip_rle_delta;
 
     debug!("Input data length: {}", data.len());
     debug!("Input data: {:?}\n\n", data);
 
-    // Step 1: Palette Compression
-    let palette_compressed = palette::palette_compression(data)?;
+    // A blank canvas has no pixels, which is a valid, empty result
+    if data.is_empty() {
+        let stats = CompressionStats {
+            input_len: 0,
+            palette_size: 0,
+            palette_indices_len: 0,
+            tiles_len: None,
+            rle_delta_len: 0,
+            lzw_len: 0,
+            huffman_len: None,
+        };
+        let result = CompressionResult {
+            palette: Vec::new(),
+            data: Vec::new(),
+            huffman: None,
+            rle_delta,
+            tiled: false,
+            channel_planes: options.channel_planes,
+            alphas: None,
+        };
+        return Ok((result, stats));
+    }
+
+    if options.channel_planes {
+        return compress_channel_planes(data, options);
+    }
+    if options.split_alpha {
+        return compress_split_alpha(data, options);
+    }
+
+    // Step 1: Palette Compression. A single color needs no palette lookups,
+    // and its all-zero indices collapse to a few bytes in the later stages.
+    let mut palette_compressed = match single_color(data) {
+        Some(color) => palette::PaletteCompression {
+            palette: vec![color],
+            indices: vec![0; data.len() / 4],
+        },
+        None => palette::palette_compression(data)?,
+    };
+    if options.sort_palette {
+        palette_compressed = palette::sort_by_luminance(palette_compressed);
+    }
     debug!(
         "Palette compressed: {} unique colors, {} bytes",
         palette_compressed.palette.len(),
@@ -51,25 +339,210 @@ pub fn compress(data: &[u8]) -> Result<CompressionResult, CompressionError> {
     debug!("Palette: {:?}", palette_compressed.palette);
     debug!("Palette indices: {:?}\n\n", palette_compressed.indices);
 
-    // Step 2: RLE Delta Encoding
-    let rle_delta_encoded = rle_delta::rle_delta_compression(&palette_compressed.indices)?;
+    // Optional tile deduplication, the later stages compress the tile
+    // dictionary and map in place of the indices
+    let tiled = match options.tiles {
+        Some(tile_options) => {
+            let tiled = tiles::tile_deduplication(&palette_compressed.indices, tile_options)?;
+            debug!("Tile deduplication: {} bytes", tiled.len());
+            Some(tiled)
+        }
+        None => None,
+    };
+    let indices = tiled.as_deref().unwrap_or(&palette_compressed.indices);
+
+    // Steps 2 and 3: RLE Delta Encoding and LZW Compression
+    let (lzw_compressed, rle_delta_len) = if rle_delta {
+        compress_indices_inner(indices)?
+    } else {
+        let lzw_compressed = lzw::lzw_compression(indices)?;
+        debug!(
+            "LZW compression without RLE-delta: {} bytes",
+            lzw_compressed.len()
+        );
+        (lzw_compressed, indices.len())
+    };
+    let lzw_len = lzw_compressed.len();
+
+    // Step 4: Optional Huffman coding
+    let (compressed, huffman) = huffman_stage(lzw_compressed, options.huffman)?;
+
+    info!(
+        "Compression completed successfully: {}%",
+        ((data.len() as f32 - compressed.len() as f32) / data.len() as f32) * 100.0
+    );
+
+    let stats = CompressionStats {
+        input_len: data.len(),
+        palette_size: palette_compressed.palette.len(),
+        palette_indices_len: palette_compressed.indices.len(),
+        tiles_len: tiled.as_ref().map(Vec::len),
+        rle_delta_len,
+        lzw_len,
+        huffman_len: huffman.as_ref().map(|_| compressed.len()),
+    };
+
+    Ok((
+        CompressionResult {
+            palette: palette_compressed.palette,
+            data: compressed,
+            huffman,
+            rle_delta,
+            tiled: tiled.is_some(),
+            channel_planes: false,
+            alphas: None,
+        },
+        stats,
+    ))
+}
+
+/// The channel plane pipeline of `compress_with_stats`, in place of the
+/// palette and tile stages
+fn compress_channel_planes(
+    data: &[u8],
+    options: CompressionOptions,
+) -> Result<(CompressionResult, CompressionStats), CompressionError> {
+    let planes = planes::channel_planes(data)?;
+    debug!("Channel planes: {} bytes", planes.len());
+
+    let (lzw_compressed, rle_delta_len) = if options.skip_rle_delta {
+        (lzw::lzw_compression(&planes)?, planes.len())
+    } else {
+        compress_indices_inner(&planes)?
+    };
+    let lzw_len = lzw_compressed.len();
+    let (compressed, huffman) = huffman_stage(lzw_compressed, options.huffman)?;
+
+    let stats = CompressionStats {
+        input_len: data.len(),
+        palette_size: 0,
+        palette_indices_len: planes.len(),
+        tiles_len: None,
+        rle_delta_len,
+        lzw_len,
+        huffman_len: huffman.as_ref().map(|_| compressed.len()),
+    };
+    let result = CompressionResult {
+        palette: Vec::new(),
+        data: compressed,
+        huffman,
+        rle_delta: !options.skip_rle_delta,
+        tiled: false,
+        channel_planes: true,
+        alphas: None,
+    };
+    Ok((result, stats))
+}
+
+/// The split-alpha pipeline of `compress_with_stats`, in place of the
+/// palette and tile stages
+fn compress_split_alpha(
+    data: &[u8],
+    options: CompressionOptions,
+) -> Result<(CompressionResult, CompressionStats), CompressionError> {
+    let split = palette::split_alpha_palette_compression(data)?;
+    let indices = split.indices();
+    debug!(
+        "Split-alpha palette: {} colors, {} alpha levels",
+        split.palette.len(),
+        split.alphas.len()
+    );
+
+    let (lzw_compressed, rle_delta_len) = if options.skip_rle_delta {
+        (lzw::lzw_compression(&indices)?, indices.len())
+    } else {
+        compress_indices_inner(&indices)?
+    };
+    let lzw_len = lzw_compressed.len();
+    let (compressed, huffman) = huffman_stage(lzw_compressed, options.huffman)?;
+
+    let stats = CompressionStats {
+        input_len: data.len(),
+        palette_size: split.palette.len(),
+        palette_indices_len: indices.len(),
+        tiles_len: None,
+        rle_delta_len,
+        lzw_len,
+        huffman_len: huffman.as_ref().map(|_| compressed.len()),
+    };
+    let result = CompressionResult {
+        palette: split
+            .palette
+            .iter()
+            .map(|&[r, g, b]| [r, g, b, 255])
+            .collect(),
+        data: compressed,
+        huffman,
+        rle_delta: !options.skip_rle_delta,
+        tiled: false,
+        channel_planes: false,
+        alphas: Some(split.alphas),
+    };
+    Ok((result, stats))
+}
+
+/// Huffman-codes the LZW output if `enabled`. Empty data has nothing to code.
+fn huffman_stage(
+    lzw_compressed: Vec<u8>,
+    enabled: bool,
+) -> Result<(Vec<u8>, Option<HuffmanTable>), CompressionError> {
+    if !enabled || lzw_compressed.is_empty() {
+        return Ok((lzw_compressed, None));
+    }
+    let code = huffman::huffman_encode(&lzw_compressed)?;
+    debug!("Huffman coding: {} bytes", code.encoded_data.len());
+    let table = HuffmanTable {
+        encoding_map: code.encoding_map,
+        padding_bits: code.padding_bits,
+    };
+    Ok((code.encoded_data, Some(table)))
+}
+
+/// The color of every pixel, if they all share one
+fn single_color(pixels: &[u8]) -> Option<[u8; 4]> {
+    let (first, rest) = pixels.split_first_chunk::<4>()?;
+    let uniform = rest.len().is_multiple_of(4) && rest.chunks_exact(4).all(|pixel| pixel == first);
+    uniform.then_some(*first)
+}
+
+/// Compresses already palette-indexed data, skipping the palette stage.
+pub fn compress_indices(indices: &[u8]) -> Result<Vec<u8>, CompressionError> {
+    // Mirrors `decompress_indices`, which reads empty data as no indices
+    if indices.is_empty() {
+        return Ok(Vec::new());
+    }
+    compress_indices_inner(indices).map(|(lzw_compressed, _)| lzw_compressed)
+}
+
+/// Runs the RLE-delta and LZW stages, also returning the RLE-delta size
+fn compress_indices_inner(indices: &[u8]) -> Result<(Vec<u8>, usize), CompressionError> {
+    let rle_delta_encoded = rle_delta::rle_delta_compression(indices)?;
     debug!("RLE Delta encoding: {} bytes", rle_delta_encoded.len());
     debug!("RLE Delta encoded data: {:?}\n\n", rle_delta_encoded);
 
-    // Step 3: LZW Compression
     let lzw_compressed = lzw::lzw_compression(&rle_delta_encoded)?;
     debug!("LZW compression: {} bytes", lzw_compressed.len());
     debug!("LZW compressed data: {:?}\n\n", lzw_compressed);
 
-    info!(
-        "Compression completed successfully: {}%",
-        ((data.len() as f32 - lzw_compressed.len() as f32) / data.len() as f32) * 100.0
-    );
+    Ok((lzw_compressed, rle_delta_encoded.len()))
+}
+
+/// Reverses `compress_indices`, returning the palette indices.
+pub fn decompress_indices(data: &[u8]) -> Result<Vec<u8>, DecompressionError> {
+    // What `compress` produces for an empty input
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let lzw_decompressed = lzw::lzw_decompression(data)?;
+    debug!("LZW decompression: {} bytes", lzw_decompressed.len());
+    debug!("LZW decompressed data: {:?}\n\n", lzw_decompressed);
+
+    let rle_delta_decoded = rle_delta::rle_delta_decompression(&lzw_decompressed)?;
+    debug!("RLE Delta decoding: {} bytes", rle_delta_decoded.len());
+    debug!("RLE Delta decoded data: {:?}\n\n", rle_delta_decoded);
 
-    Ok(CompressionResult {
-        palette: palette_compressed.palette,
-        data: lzw_compressed,
-    })
+    Ok(rle_delta_decoded)
 }
 
 pub fn decompress(data: CompressionResult) -> Result<Vec<u8>, DecompressionError> {
@@ -78,15 +551,62 @@ pub fn decompress(data: CompressionResult) -> Result<Vec<u8>, DecompressionError
     debug!("Input data length: {}", data.data.len());
     debug!("Input data: {:?}\n\n", data.data);
 
-    // Step 1: LZW Decompression
-    let lzw_decompressed = lzw::lzw_decompression(&data.data)?;
-    debug!("LZW decompression: {} bytes", lzw_decompressed.len());
-    debug!("LZW decompressed data: {:?}\n\n", lzw_decompressed);
+    // Step 0: Undo the optional Huffman stage
+    let lzw_data = match data.huffman {
+        Some(table) => {
+            let decoded = huffman::huffman_decode(&HuffmanCode {
+                encoding_map: table.encoding_map,
+                encoded_data: data.data,
+                padding_bits: table.padding_bits,
+            })?;
+            debug!("Huffman decoding: {} bytes", decoded.len());
+            decoded
+        }
+        None => data.data,
+    };
 
-    // Step 2: RLE and Delta Decoding
-    let rle_delta_decoded = rle_delta::rle_delta_decompression(&lzw_decompressed)?;
-    debug!("RLE Delta decoding: {} bytes", rle_delta_decoded.len());
-    debug!("RLE Delta decoded data: {:?}\n\n", rle_delta_decoded);
+    // Steps 1 and 2: LZW Decompression and RLE and Delta Decoding
+    let rle_delta_decoded = if data.rle_delta {
+        decompress_indices(&lzw_data)?
+    } else {
+        let indices = lzw::lzw_decompression(&lzw_data)?;
+        debug!(
+            "LZW decompression without RLE-delta: {} bytes",
+            indices.len()
+        );
+        indices
+    };
+
+    // Channel planes hold the pixels themselves, there is no palette
+    if data.channel_planes {
+        let pixels = planes::merge_channel_planes(&rle_delta_decoded)?;
+        debug!("Channel plane merge: {} bytes", pixels.len());
+        info!("Decompression completed successfully");
+        return Ok(pixels);
+    }
+
+    // Split-alpha data pairs every pixel's color index with an alpha index
+    if let Some(alphas) = data.alphas {
+        let palette = data.palette.iter().map(|&[r, g, b, _]| [r, g, b]).collect();
+        let split = palette::SplitAlphaPaletteCompression::from_indices(
+            palette,
+            alphas,
+            &rle_delta_decoded,
+        )?;
+        let pixels = palette::split_alpha_palette_decompression(&split)?;
+        debug!("Split-alpha expansion: {} bytes", pixels.len());
+        info!("Decompression completed successfully");
+        return Ok(pixels);
+    }
+
+    // Undo the optional tile deduplication
+    let rle_delta_decoded = if data.tiled {
+        let indices = tiles::tile_expansion(&rle_delta_decoded)?;
+        debug!("Tile expansion: {} bytes", indices.len());
+        indices
+    } else {
+        rle_delta_decoded
+    };
 
     // Step 3: Palette Expansion to RGBA
     let expanded_pixels = palette::palette_decompression(&palette::PaletteCompression {
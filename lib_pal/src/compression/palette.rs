@@ -1,4 +1,6 @@
-use std::collections::HashMap;
+use super::HashMap;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
 
 use thiserror::Error;
 
@@ -8,12 +10,28 @@ pub enum PaletteCompressionError {
     InvalidPixelDataLength(usize),
     #[error("Palette overflow: maximum 256 colors supported, attempted to add color #{0}")]
     PaletteOverflow(usize),
+    #[error("Palette overflow: maximum 65536 colors supported, attempted to add color #{0}")]
+    WidePaletteOverflow(usize),
+    #[error("Invalid quantization target: {0} colors, expected 1 to 256")]
+    InvalidMaxColors(usize),
+    #[error("Cannot map pixels onto an empty palette")]
+    EmptyPalette,
+    #[error("Color {0:?} is not in the palette")]
+    ColorNotInPalette([u8; 4]),
 }
 
 #[derive(Error, Debug)]
 pub enum PaletteDecompressionError {
-    #[error("Invalid palette index: {0} exceeds palette size of {1}")]
-    InvalidPaletteIndex(usize, usize),
+    #[error(
+        "Invalid palette index at pixel {pixel}: {index} exceeds palette size of {palette_size}"
+    )]
+    InvalidPaletteIndex {
+        index: usize,
+        palette_size: usize,
+        pixel: usize,
+    },
+    #[error("Split-alpha data holds {0} indices, expected a color and an alpha index per pixel")]
+    UnpairedSplitAlphaIndices(usize),
 }
 
 pub struct PaletteCompression {
@@ -21,6 +39,59 @@ pub struct PaletteCompression {
     pub indices: Vec<u8>,      // Palette indices for each pixel
 }
 
+impl PaletteCompression {
+    /// Maps each palette color to its index, for looking up the colors of
+    /// another buffer without scanning the palette per pixel
+    pub fn index_map(&self) -> HashMap<[u8; 4], u8> {
+        index_map(&self.palette)
+    }
+}
+
+/// Duplicate palette entries resolve to the first occurrence
+fn index_map(palette: &[[u8; 4]]) -> HashMap<[u8; 4], u8> {
+    let mut lookup = HashMap::with_capacity(palette.len());
+    for (index, &color) in palette.iter().enumerate() {
+        lookup.entry(color).or_insert(index as u8);
+    }
+    lookup
+}
+
+/// Palette compression with the alpha channel indexed separately from RGB.
+pub struct SplitAlphaPaletteCompression {
+    pub palette: Vec<[u8; 3]>,  // Array of unique colors in RGB format
+    pub alphas: Vec<u8>,        // Array of unique alpha values
+    pub color_indices: Vec<u8>, // Palette indices for each pixel
+    pub alpha_indices: Vec<u8>, // Alpha indices for each pixel
+}
+
+impl SplitAlphaPaletteCompression {
+    /// The color indices followed by the alpha indices, as the later stages
+    /// of the pipeline compress them
+    pub fn indices(&self) -> Vec<u8> {
+        [&self.color_indices[..], &self.alpha_indices[..]].concat()
+    }
+
+    /// Reverses `indices`, pairing them with the color and alpha tables
+    pub fn from_indices(
+        palette: Vec<[u8; 3]>,
+        alphas: Vec<u8>,
+        indices: &[u8],
+    ) -> Result<Self, PaletteDecompressionError> {
+        if !indices.len().is_multiple_of(2) {
+            return Err(PaletteDecompressionError::UnpairedSplitAlphaIndices(
+                indices.len(),
+            ));
+        }
+        let (color_indices, alpha_indices) = indices.split_at(indices.len() / 2);
+        Ok(Self {
+            palette,
+            alphas,
+            color_indices: color_indices.to_vec(),
+            alpha_indices: alpha_indices.to_vec(),
+        })
+    }
+}
+
 /// Compresses a raw RGBA pixel buffer to use a limited color palette.
 ///
 /// # Parameters
@@ -35,7 +106,7 @@ pub struct PaletteCompression {
 /// - Returns `PaletteError::PaletteOverflow` if more than 256 unique colors are found
 pub fn palette_compression(pixels: &[u8]) -> Result<PaletteCompression, PaletteCompressionError> {
     // Validate input length
-    if pixels.len() % 4 != 0 {
+    if !pixels.len().is_multiple_of(4) {
         return Err(PaletteCompressionError::InvalidPixelDataLength(
             pixels.len(),
         ));
@@ -68,6 +139,401 @@ pub fn palette_compression(pixels: &[u8]) -> Result<PaletteCompression, PaletteC
     Ok(PaletteCompression { palette, indices })
 }
 
+/// Indexes a raw RGBA pixel buffer like `palette_compression`, with `u16`
+/// indices into a palette of up to 65536 colors, matching the LZW code space.
+///
+/// The `.pxc` format stores one byte per index, so the default pipeline keeps
+/// to `palette_compression`.
+///
+/// # Errors
+/// - Returns `PaletteCompressionError::InvalidPixelDataLength` if input length is not a multiple of 4
+/// - Returns `PaletteCompressionError::WidePaletteOverflow` if more than 65536 unique colors are found
+pub fn to_indexed16(pixels: &[u8]) -> Result<(Vec<[u8; 4]>, Vec<u16>), PaletteCompressionError> {
+    if !pixels.len().is_multiple_of(4) {
+        return Err(PaletteCompressionError::InvalidPixelDataLength(
+            pixels.len(),
+        ));
+    }
+
+    let mut unique_colors: HashMap<[u8; 4], u16> = HashMap::new();
+    let mut palette = Vec::new();
+    let mut indices = Vec::with_capacity(pixels.len() / 4);
+    for pixel in pixels.chunks(4) {
+        let color = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        if let Some(&index) = unique_colors.get(&color) {
+            indices.push(index);
+        } else {
+            if palette.len() > u16::MAX as usize {
+                return Err(PaletteCompressionError::WidePaletteOverflow(
+                    palette.len() + 1,
+                ));
+            }
+
+            let index = palette.len() as u16;
+            palette.push(color);
+            unique_colors.insert(color, index);
+            indices.push(index);
+        }
+    }
+
+    Ok((palette, indices))
+}
+
+/// Reorders the palette by perceived luminance (0.299R + 0.587G + 0.114B),
+/// darkest first, and remaps the indices to match.
+///
+/// Neighbouring pixels tend to have similar colors, so sorted indices can give
+/// the delta encoder longer runs than first-seen order. The delta encoder only
+/// rewards repeated deltas, not small ones, so some images come out larger.
+pub fn sort_by_luminance(compression: PaletteCompression) -> PaletteCompression {
+    let luminance = |&[r, g, b, _]: &[u8; 4]| 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+
+    let mut order: Vec<usize> = (0..compression.palette.len()).collect();
+    order.sort_by_key(|&index| luminance(&compression.palette[index]));
+
+    let mut remap = vec![0u8; compression.palette.len()];
+    for (new_index, &old_index) in order.iter().enumerate() {
+        remap[old_index] = new_index as u8;
+    }
+
+    PaletteCompression {
+        palette: order
+            .iter()
+            .map(|&index| compression.palette[index])
+            .collect(),
+        indices: compression
+            .indices
+            .iter()
+            .map(|&index| remap[index as usize])
+            .collect(),
+    }
+}
+
+/// Compresses a raw RGBA pixel buffer, reducing it to at most `max_colors` colors.
+///
+/// Images that already fit are indexed exactly as by `palette_compression`.
+/// Otherwise the unique colors are split with median cut until there are
+/// `max_colors` boxes, each box is averaged into one palette entry weighted by
+/// pixel count, and every pixel is mapped to its nearest entry.
+///
+/// # Errors
+/// - Returns `PaletteCompressionError::InvalidPixelDataLength` if input length is not a multiple of 4
+/// - Returns `PaletteCompressionError::InvalidMaxColors` if `max_colors` is not in `1..=256`
+pub fn palette_compression_quantized(
+    pixels: &[u8],
+    max_colors: usize,
+) -> Result<PaletteCompression, PaletteCompressionError> {
+    if !(1..=256).contains(&max_colors) {
+        return Err(PaletteCompressionError::InvalidMaxColors(max_colors));
+    }
+    let (colors, counts) = count_colors(pixels)?;
+
+    if colors.len() <= max_colors {
+        return palette_compression(pixels);
+    }
+
+    let palette: Vec<[u8; 4]> = median_cut(colors, max_colors)
+        .iter()
+        .map(|colors| average_color(colors, &counts))
+        .collect();
+
+    let indices = map_to_palette(pixels, &palette)?;
+
+    Ok(PaletteCompression { palette, indices })
+}
+
+/// Counts the pixels of each RGBA color, most frequent first. Colors with the
+/// same count keep their order of first appearance.
+///
+/// Unlike `palette_compression` there is no 256-color limit, so tools can
+/// tell how far over it an image is before encoding.
+///
+/// # Errors
+/// - Returns `PaletteCompressionError::InvalidPixelDataLength` if input length is not a multiple of 4
+pub fn color_histogram(pixels: &[u8]) -> Result<Vec<([u8; 4], usize)>, PaletteCompressionError> {
+    let (colors, counts) = count_colors(pixels)?;
+    let mut histogram: Vec<([u8; 4], usize)> = colors
+        .into_iter()
+        .map(|color| (color, counts[&color]))
+        .collect();
+    histogram.sort_by(|(_, a), (_, b)| b.cmp(a));
+    Ok(histogram)
+}
+
+/// Pixel count of each color
+type ColorCounts = HashMap<[u8; 4], usize>;
+
+/// Unique colors in order of first appearance, with their pixel counts
+fn count_colors(pixels: &[u8]) -> Result<(Vec<[u8; 4]>, ColorCounts), PaletteCompressionError> {
+    if !pixels.len().is_multiple_of(4) {
+        return Err(PaletteCompressionError::InvalidPixelDataLength(
+            pixels.len(),
+        ));
+    }
+
+    let mut counts = ColorCounts::new();
+    let mut colors = Vec::new();
+    for pixel in pixels.chunks(4) {
+        let color = [pixel[0], pixel[1], pixel[2], pixel[3]];
+        let count = counts.entry(color).or_insert(0);
+        if *count == 0 {
+            colors.push(color);
+        }
+        *count += 1;
+    }
+    Ok((colors, counts))
+}
+
+/// Compresses a raw RGBA pixel buffer against a caller-supplied palette.
+///
+/// Reusing one palette across frames keeps index assignments stable between
+/// them. Every pixel must match a palette color exactly; use `map_to_palette`
+/// to snap to the nearest color instead.
+///
+/// # Errors
+/// - Returns `PaletteCompressionError::InvalidPixelDataLength` if input length is not a multiple of 4
+/// - Returns `PaletteCompressionError::PaletteOverflow` if `palette` has more than 256 colors
+/// - Returns `PaletteCompressionError::ColorNotInPalette` for the first pixel without a match
+pub fn palette_compression_with(
+    pixels: &[u8],
+    palette: &[[u8; 4]],
+) -> Result<PaletteCompression, PaletteCompressionError> {
+    if !pixels.len().is_multiple_of(4) {
+        return Err(PaletteCompressionError::InvalidPixelDataLength(
+            pixels.len(),
+        ));
+    }
+    if palette.len() > 256 {
+        return Err(PaletteCompressionError::PaletteOverflow(palette.len()));
+    }
+
+    let lookup = index_map(palette);
+    let indices = pixels
+        .chunks(4)
+        .map(|pixel| {
+            let color = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            lookup
+                .get(&color)
+                .copied()
+                .ok_or(PaletteCompressionError::ColorNotInPalette(color))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(PaletteCompression {
+        palette: palette.to_vec(),
+        indices,
+    })
+}
+
+/// Maps a raw RGBA pixel buffer onto a fixed palette.
+///
+/// Each pixel gets the index of its nearest palette color by squared Euclidean
+/// distance in RGBA, ties going to the lower index. The indices can be fed
+/// straight into `rle_delta_compression`.
+///
+/// # Errors
+/// - Returns `PaletteCompressionError::InvalidPixelDataLength` if input length is not a multiple of 4
+/// - Returns `PaletteCompressionError::EmptyPalette` if `palette` is empty
+/// - Returns `PaletteCompressionError::PaletteOverflow` if `palette` has more than 256 colors
+pub fn map_to_palette(
+    pixels: &[u8],
+    palette: &[[u8; 4]],
+) -> Result<Vec<u8>, PaletteCompressionError> {
+    if !pixels.len().is_multiple_of(4) {
+        return Err(PaletteCompressionError::InvalidPixelDataLength(
+            pixels.len(),
+        ));
+    }
+    if palette.is_empty() {
+        return Err(PaletteCompressionError::EmptyPalette);
+    }
+    if palette.len() > 256 {
+        return Err(PaletteCompressionError::PaletteOverflow(palette.len()));
+    }
+
+    let mut nearest = HashMap::new();
+    Ok(pixels
+        .chunks(4)
+        .map(|pixel| {
+            let color = [pixel[0], pixel[1], pixel[2], pixel[3]];
+            *nearest
+                .entry(color)
+                .or_insert_with(|| nearest_index(palette, color))
+        })
+        .collect())
+}
+
+/// Splits `colors` into at most `max_boxes` boxes, always cutting the box with
+/// the widest channel range at the median of that channel.
+fn median_cut(colors: Vec<[u8; 4]>, max_boxes: usize) -> Vec<Vec<[u8; 4]>> {
+    let widest_channel = |colors: &[[u8; 4]]| {
+        (0..4)
+            .map(|channel| {
+                let (min, max) = colors.iter().fold((u8::MAX, u8::MIN), |(min, max), c| {
+                    (min.min(c[channel]), max.max(c[channel]))
+                });
+                (max - min, channel)
+            })
+            .max()
+            .unwrap_or((0, 0))
+    };
+
+    let mut boxes = vec![colors];
+    while boxes.len() < max_boxes {
+        let Some((index, (_, channel))) = boxes
+            .iter()
+            .map(|colors| widest_channel(colors))
+            .enumerate()
+            .filter(|(_, (range, _))| *range > 0)
+            .max_by_key(|(_, range)| *range)
+        else {
+            break;
+        };
+
+        let mut colors = boxes.swap_remove(index);
+        colors.sort_unstable_by_key(|color| color[channel]);
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+
+    boxes
+}
+
+fn average_color(colors: &[[u8; 4]], counts: &HashMap<[u8; 4], usize>) -> [u8; 4] {
+    let mut sums = [0usize; 4];
+    let mut total = 0;
+    for color in colors {
+        let count = counts[color];
+        for (sum, &channel) in sums.iter_mut().zip(color) {
+            *sum += channel as usize * count;
+        }
+        total += count;
+    }
+
+    sums.map(|sum| ((sum + total / 2) / total) as u8)
+}
+
+fn nearest_index(palette: &[[u8; 4]], color: [u8; 4]) -> u8 {
+    let distance = |entry: &[u8; 4]| -> u32 {
+        entry
+            .iter()
+            .zip(&color)
+            .map(|(&a, &b)| (a as i32 - b as i32).pow(2) as u32)
+            .sum()
+    };
+
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map_or(0, |(index, _)| index as u8)
+}
+
+/// Compresses a raw RGBA pixel buffer, indexing RGB and alpha independently.
+///
+/// Soft-edged sprites often reuse one RGB color at many alpha levels; keeping
+/// alpha in its own small table stops each level from taking a palette slot.
+///
+/// # Parameters
+/// - `pixels`: A slice of raw pixel data in RGBA format.
+///
+/// # Returns
+/// A Result containing either a `SplitAlphaPaletteCompression` or a `PaletteCompressionError`.
+///
+/// # Errors
+/// - Returns `PaletteCompressionError::InvalidPixelDataLength` if input length is not a multiple of 4
+/// - Returns `PaletteCompressionError::PaletteOverflow` if more than 256 unique RGB colors are found
+pub fn split_alpha_palette_compression(
+    pixels: &[u8],
+) -> Result<SplitAlphaPaletteCompression, PaletteCompressionError> {
+    if !pixels.len().is_multiple_of(4) {
+        return Err(PaletteCompressionError::InvalidPixelDataLength(
+            pixels.len(),
+        ));
+    }
+
+    let mut unique_colors = HashMap::new();
+    let mut unique_alphas = HashMap::new();
+    let mut palette = Vec::new();
+    let mut alphas = Vec::new();
+    let mut color_indices = Vec::with_capacity(pixels.len() / 4);
+    let mut alpha_indices = Vec::with_capacity(pixels.len() / 4);
+
+    for pixel in pixels.chunks(4) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let alpha = pixel[3];
+
+        let color_index = match unique_colors.get(&color) {
+            Some(&index) => index,
+            None => {
+                if palette.len() >= 256 {
+                    return Err(PaletteCompressionError::PaletteOverflow(palette.len() + 1));
+                }
+
+                let index = palette.len() as u8;
+                palette.push(color);
+                unique_colors.insert(color, index);
+                index
+            }
+        };
+
+        // At most 256 distinct alpha values exist, so this can't overflow
+        let alpha_index = *unique_alphas.entry(alpha).or_insert_with(|| {
+            alphas.push(alpha);
+            (alphas.len() - 1) as u8
+        });
+
+        color_indices.push(color_index);
+        alpha_indices.push(alpha_index);
+    }
+
+    Ok(SplitAlphaPaletteCompression {
+        palette,
+        alphas,
+        color_indices,
+        alpha_indices,
+    })
+}
+
+/// Expands separately indexed RGB and alpha back into RGBA pixel data.
+///
+/// # Errors
+/// - Returns `PaletteDecompressionError::InvalidPaletteIndex` if any color or alpha
+///   index exceeds its table size
+pub fn split_alpha_palette_decompression(
+    compression: &SplitAlphaPaletteCompression,
+) -> Result<Vec<u8>, PaletteDecompressionError> {
+    let mut decoded_pixels = Vec::with_capacity(compression.color_indices.len() * 4);
+
+    for (pixel, (&color_index, &alpha_index)) in compression
+        .color_indices
+        .iter()
+        .zip(&compression.alpha_indices)
+        .enumerate()
+    {
+        let color = compression.palette.get(color_index as usize).ok_or(
+            PaletteDecompressionError::InvalidPaletteIndex {
+                index: color_index as usize,
+                palette_size: compression.palette.len(),
+                pixel,
+            },
+        )?;
+        let alpha = compression.alphas.get(alpha_index as usize).ok_or(
+            PaletteDecompressionError::InvalidPaletteIndex {
+                index: alpha_index as usize,
+                palette_size: compression.alphas.len(),
+                pixel,
+            },
+        )?;
+
+        decoded_pixels.extend_from_slice(color);
+        decoded_pixels.push(*alpha);
+    }
+
+    Ok(decoded_pixels)
+}
+
 /// Expands palette indices back into RGBA pixel data.
 ///
 /// # Parameters
@@ -83,16 +549,17 @@ pub fn palette_decompression(
 ) -> Result<Vec<u8>, PaletteDecompressionError> {
     let mut decoded_pixels = Vec::with_capacity(compression.indices.len() * 4);
 
-    for &index in &compression.indices {
+    for (pixel, &index) in compression.indices.iter().enumerate() {
         let palette_size = compression.palette.len();
         let index_usize = index as usize;
 
         // Validate index before accessing palette
         if index_usize >= palette_size {
-            return Err(PaletteDecompressionError::InvalidPaletteIndex(
-                index_usize,
+            return Err(PaletteDecompressionError::InvalidPaletteIndex {
+                index: index_usize,
                 palette_size,
-            ));
+                pixel,
+            });
         }
 
         let color = compression.palette[index_usize];
@@ -157,7 +624,11 @@ mod tests {
         let result = palette_decompression(&compressed);
         assert!(matches!(
             result,
-            Err(PaletteDecompressionError::InvalidPaletteIndex(1, 1))
+            Err(PaletteDecompressionError::InvalidPaletteIndex {
+                index: 1,
+                palette_size: 1,
+                pixel: 1
+            })
         ));
     }
 
@@ -184,6 +655,164 @@ mod tests {
         assert_eq!(decompressed, pixels);
     }
 
+    #[test]
+    fn test_palette_split_alpha_levels() {
+        let mut pixels = Vec::new();
+        for level in 0..8u8 {
+            pixels.extend_from_slice(&[250, 75, 98, level * 32 + 31]);
+        }
+
+        // Every alpha level takes its own slot in the regular palette
+        let compressed = palette_compression(&pixels).unwrap();
+        assert_eq!(compressed.palette.len(), 8);
+
+        let split = split_alpha_palette_compression(&pixels).unwrap();
+        assert_eq!(split.palette, vec![[250, 75, 98]]);
+        assert_eq!(split.alphas.len(), 8);
+        assert!(split.color_indices.iter().all(|&index| index == 0));
+        assert_eq!(split.alpha_indices, (0..8).collect::<Vec<u8>>());
+
+        let decompressed = split_alpha_palette_decompression(&split).unwrap();
+        assert_eq!(decompressed, pixels);
+
+        let indices = split.indices();
+        assert_eq!(indices.len(), 16);
+        let rebuilt =
+            SplitAlphaPaletteCompression::from_indices(split.palette, split.alphas, &indices)
+                .unwrap();
+        assert_eq!(split_alpha_palette_decompression(&rebuilt).unwrap(), pixels);
+        assert!(matches!(
+            SplitAlphaPaletteCompression::from_indices(vec![], vec![], &indices[1..]),
+            Err(PaletteDecompressionError::UnpairedSplitAlphaIndices(15))
+        ));
+    }
+
+    #[test]
+    fn test_palette_split_alpha_invalid_index() {
+        let split = SplitAlphaPaletteCompression {
+            palette: vec![[255, 0, 0]],
+            alphas: vec![255],
+            color_indices: vec![0, 0],
+            alpha_indices: vec![0, 1],
+        };
+        let result = split_alpha_palette_decompression(&split);
+        assert!(matches!(
+            result,
+            Err(PaletteDecompressionError::InvalidPaletteIndex {
+                index: 1,
+                palette_size: 1,
+                pixel: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_palette_quantized_invalid_max_colors() {
+        for max_colors in [0, 257] {
+            assert!(matches!(
+                palette_compression_quantized(&[0, 0, 0, 255], max_colors),
+                Err(PaletteCompressionError::InvalidMaxColors(n)) if n == max_colors
+            ));
+        }
+    }
+
+    #[test]
+    fn test_palette_quantized_exact_when_it_fits() {
+        let pixels = vec![
+            255, 0, 0, 255, // Red
+            0, 255, 0, 255, // Green
+            255, 0, 0, 255, // Red
+        ];
+        let compressed = palette_compression_quantized(&pixels, 2).unwrap();
+        assert_eq!(compressed.palette, vec![[255, 0, 0, 255], [0, 255, 0, 255]]);
+        assert_eq!(compressed.indices, vec![0, 1, 0]);
+    }
+
+    #[test]
+    fn test_map_to_palette_nearest() {
+        let palette = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+        let pixels = vec![
+            255, 0, 0, 255, // Red
+            0, 255, 0, 255, // Green
+            0, 0, 255, 255, // Blue
+            200, 30, 10, 255, // Close to red
+        ];
+        assert_eq!(map_to_palette(&pixels, &palette).unwrap(), vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_index_map_matches_palette_order() {
+        let pixels = vec![
+            0, 0, 255, 255, // Blue
+            255, 0, 0, 255, // Red
+            0, 0, 255, 255, // Blue
+            0, 255, 0, 128, // Half green
+        ];
+
+        let compressed = palette_compression(&pixels).unwrap();
+        let map = compressed.index_map();
+        assert_eq!(map.len(), compressed.palette.len());
+        for (index, color) in compressed.palette.iter().enumerate() {
+            assert_eq!(map[color], index as u8);
+        }
+        assert_eq!(map[&[0, 255, 0, 128]], 2);
+
+        // Sorting reorders the palette, and the map follows it
+        let sorted = sort_by_luminance(compressed);
+        assert_eq!(sorted.index_map()[&sorted.palette[0]], 0);
+    }
+
+    #[test]
+    fn test_palette_compression_with_shared_palette() {
+        let first = vec![
+            255, 0, 0, 255, // Red
+            0, 0, 255, 255, // Blue
+            0, 255, 0, 255, // Green
+        ];
+        let second = vec![
+            0, 255, 0, 255, // Green
+            255, 0, 0, 255, // Red
+            0, 255, 0, 255, // Green
+        ];
+
+        let first = palette_compression(&first).unwrap();
+        let shared = palette_compression_with(&second, &first.palette).unwrap();
+        assert_eq!(shared.palette, first.palette);
+        // Green keeps the index it got in the first frame
+        assert_eq!(shared.indices, vec![2, 0, 2]);
+        assert_eq!(palette_decompression(&shared).unwrap(), second);
+
+        assert!(matches!(
+            palette_compression_with(&[1, 2, 3, 255], &first.palette),
+            Err(PaletteCompressionError::ColorNotInPalette([1, 2, 3, 255]))
+        ));
+    }
+
+    #[test]
+    fn test_map_to_palette_empty_palette() {
+        assert!(matches!(
+            map_to_palette(&[0, 0, 0, 255], &[]),
+            Err(PaletteCompressionError::EmptyPalette)
+        ));
+    }
+
+    #[test]
+    fn test_sort_by_luminance() {
+        let pixels = vec![
+            255, 255, 255, 255, // White
+            0, 0, 255, 255, // Blue
+            0, 0, 0, 255, // Black
+            255, 255, 255, 255, // White
+        ];
+        let sorted = sort_by_luminance(palette_compression(&pixels).unwrap());
+        assert_eq!(
+            sorted.palette,
+            vec![[0, 0, 0, 255], [0, 0, 255, 255], [255, 255, 255, 255]]
+        );
+        assert_eq!(sorted.indices, vec![2, 1, 0, 2]);
+        assert_eq!(palette_decompression(&sorted).unwrap(), pixels);
+    }
+
     #[test]
     fn test_palette_gradients() {
         let mut data = Vec::new();
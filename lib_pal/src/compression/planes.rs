@@ -0,0 +1,82 @@
+use thiserror::Error;
+
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+#[derive(Error, Debug)]
+pub enum PlaneError {
+    #[error("Invalid input length: {0} is not a whole number of RGBA pixels")]
+    InvalidLength(usize),
+}
+
+/// Splits RGBA pixels into their R, G, B and A planes, stored one after the
+/// other, and replaces every byte of a plane by its wrapping difference to
+/// the previous one.
+///
+/// Smooth gradients turn into long runs of small, repeated deltas, which the
+/// RLE-delta and LZW stages compress better than palette indices.
+pub fn channel_planes(pixels: &[u8]) -> Result<Vec<u8>, PlaneError> {
+    if !pixels.len().is_multiple_of(4) {
+        return Err(PlaneError::InvalidLength(pixels.len()));
+    }
+
+    let mut planes = Vec::with_capacity(pixels.len());
+    for channel in 0..4 {
+        let mut previous = 0u8;
+        for pixel in pixels.chunks_exact(4) {
+            planes.push(pixel[channel].wrapping_sub(previous));
+            previous = pixel[channel];
+        }
+    }
+    Ok(planes)
+}
+
+/// Reverses `channel_planes`, returning interleaved RGBA pixels
+pub fn merge_channel_planes(planes: &[u8]) -> Result<Vec<u8>, PlaneError> {
+    if !planes.len().is_multiple_of(4) {
+        return Err(PlaneError::InvalidLength(planes.len()));
+    }
+
+    let pixel_count = planes.len() / 4;
+    let mut pixels = vec![0; planes.len()];
+    for (channel, plane) in planes.chunks_exact(pixel_count.max(1)).enumerate() {
+        let mut value = 0u8;
+        for (pixel, delta) in plane.iter().enumerate() {
+            value = value.wrapping_add(*delta);
+            pixels[pixel * 4 + channel] = value;
+        }
+    }
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_planes_layout_and_roundtrip() {
+        let pixels = [10, 20, 30, 255, 11, 18, 30, 255, 12, 16, 30, 0];
+        let planes = channel_planes(&pixels).unwrap();
+        assert_eq!(
+            planes,
+            [10, 1, 1, 20, 254, 254, 30, 0, 0, 255, 0, 1],
+            "R, G, B and A deltas in order"
+        );
+        assert_eq!(merge_channel_planes(&planes).unwrap(), pixels);
+
+        assert_eq!(channel_planes(&[]).unwrap(), Vec::<u8>::new());
+        assert_eq!(merge_channel_planes(&[]).unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_planes_reject_partial_pixels() {
+        assert!(matches!(
+            channel_planes(&[1, 2, 3]),
+            Err(PlaneError::InvalidLength(3))
+        ));
+        assert!(matches!(
+            merge_channel_planes(&[1, 2, 3, 4, 5]),
+            Err(PlaneError::InvalidLength(5))
+        ));
+    }
+}
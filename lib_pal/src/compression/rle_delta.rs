@@ -1,5 +1,8 @@
 use thiserror::Error;
 
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
 #[derive(Error, Debug)]
 pub enum RleCompressionError {
     #[error("Invalid input length: data is empty")]
@@ -10,7 +13,7 @@ pub enum RleCompressionError {
 pub enum RleDecompressionError {
     #[error("Invalid input length: data is empty")]
     EmptyInput,
-    #[error("Invalid input length: expected multiple of 2 bytes, got {0}")]
+    #[error("Invalid input length: expected initial value plus pairs of bytes, got {0}")]
     InvalidInputLength(usize),
 }
 
@@ -30,6 +33,11 @@ pub fn rle_delta_compression(data: &[u8]) -> Result<Vec<u8>, RleCompressionError
     let initial_value = data[0];
     encoded.push(initial_value); // Start with the initial value
 
+    // A single value has no deltas to encode
+    if data.len() == 1 {
+        return Ok(encoded);
+    }
+
     let mut count = 1u16; // Use u16 to support larger counts
     let mut prev_delta = data[1].wrapping_sub(initial_value);
 
@@ -39,42 +47,30 @@ pub fn rle_delta_compression(data: &[u8]) -> Result<Vec<u8>, RleCompressionError
         if current_delta == prev_delta && count < u16::MAX {
             count += 1;
         } else {
-            if prev_delta == 0 {
-                // Handle large blocks of the same value more efficiently
-                while count > 255 {
-                    encoded.push(255);
-                    encoded.push(0); // delta 0 for identical values
-                    count -= 255;
-                }
-                encoded.push(count as u8);
-                encoded.push(0); // delta 0 for identical values
-            } else {
-                // Encode the count and delta normally
-                encoded.push(count as u8);
-                encoded.push(prev_delta);
-            }
+            push_run(&mut encoded, count, prev_delta);
             count = 1;
             prev_delta = current_delta;
         }
     }
 
     // Final block
-    if prev_delta == 0 {
-        while count > 255 {
-            encoded.push(255);
-            encoded.push(0);
-            count -= 255;
-        }
-        encoded.push(count as u8);
-        encoded.push(0);
-    } else {
-        encoded.push(count as u8);
-        encoded.push(prev_delta);
-    }
+    push_run(&mut encoded, count, prev_delta);
 
     Ok(encoded)
 }
 
+/// Writes a run as (count, delta) pairs, splitting counts that don't fit in
+/// a byte
+fn push_run(encoded: &mut Vec<u8>, mut count: u16, delta: u8) {
+    while count > 255 {
+        encoded.push(255);
+        encoded.push(delta);
+        count -= 255;
+    }
+    encoded.push(count as u8);
+    encoded.push(delta);
+}
+
 /// Decompresses data that was compressed using `rle_delta_compression`.
 ///
 /// # Arguments
@@ -86,7 +82,8 @@ pub fn rle_delta_decompression(data: &[u8]) -> Result<Vec<u8>, RleDecompressionE
     if data.is_empty() {
         return Err(RleDecompressionError::EmptyInput);
     }
-    if data.len() < 3 || data.len() % 2 != 1 {
+    // The initial value is followed by (count, delta) pairs, if any
+    if data.len().is_multiple_of(2) {
         return Err(RleDecompressionError::InvalidInputLength(data.len()));
     }
 
@@ -113,6 +110,18 @@ pub fn rle_delta_decompression(data: &[u8]) -> Result<Vec<u8>, RleDecompressionE
     Ok(decoded)
 }
 
+/// Like `rle_delta_decompression`, but drops a trailing incomplete pair
+/// instead of failing and reads empty data as no values, e.g. for the
+/// partial output of a truncated stream
+pub fn rle_delta_decompression_partial(data: &[u8]) -> Vec<u8> {
+    let complete = if data.len().is_multiple_of(2) {
+        data.len().saturating_sub(1)
+    } else {
+        data.len()
+    };
+    rle_delta_decompression(&data[..complete]).unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,6 +166,30 @@ mod tests {
         assert_eq!(input, decompressed);
     }
 
+    #[test]
+    fn test_rle_long_constant_delta() {
+        // 599 consecutive deltas of 1, more than fit in a single count byte
+        let data: Vec<u8> = (0..600).map(|i| i as u8).collect();
+        let compressed = rle_delta_compression(&data).unwrap();
+        assert_eq!(rle_delta_decompression(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_rle_single_value() {
+        let data = vec![5];
+        let compressed = rle_delta_compression(&data).unwrap();
+        assert_eq!(compressed, [5]);
+        assert_eq!(rle_delta_decompression(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_rle_two_values() {
+        for data in [vec![5, 5], vec![5, 9]] {
+            let compressed = rle_delta_compression(&data).unwrap();
+            assert_eq!(rle_delta_decompression(&compressed).unwrap(), data);
+        }
+    }
+
     #[test]
     fn test_rle_invalid_compressed_data() {
         assert!(matches!(
@@ -165,6 +198,13 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_rle_partial_drops_incomplete_pair() {
+        assert_eq!(rle_delta_decompression_partial(&[]), []);
+        assert_eq!(rle_delta_decompression_partial(&[5, 2]), [5]);
+        assert_eq!(rle_delta_decompression_partial(&[5, 2, 1, 3]), [5, 6, 7]);
+    }
+
     #[test]
     fn test_rle_radnom_compressed_data() {
         let input = vec![1, 2, 3, 3, 3, 3, 3, 3, 4, 5, 6, 7, 8, 9, 9, 9, 9, 99, 10];
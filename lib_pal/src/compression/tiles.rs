@@ -0,0 +1,255 @@
+use thiserror::Error;
+
+use super::HashMap;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+/// Bytes before the tile dictionary: width and height (`u32`), tile size
+/// (`u8`) and tile count (`u32`), all big endian
+const HEADER_LEN: usize = 13;
+
+#[derive(Error, Debug)]
+pub enum TileError {
+    #[error("Tile size must be at least 1")]
+    ZeroTileSize,
+    #[error("Invalid width {width} for {len} indices")]
+    InvalidWidth { width: u32, len: usize },
+    #[error("Too many unique tiles: {0}, at most 65536 are supported")]
+    TooManyTiles(usize),
+    #[error("Invalid or truncated tile data")]
+    InvalidTileData,
+}
+
+/// How `tile_deduplication` splits the index buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileOptions {
+    /// Image width in pixels, the height follows from the buffer length
+    pub width: u32,
+    /// Side of the square tiles in pixels
+    pub tile_size: u8,
+}
+
+/// Splits row-major palette indices into `tile_size` square tiles, stores
+/// each distinct tile once and replaces the image by a map of tile ids.
+///
+/// Tiles on the right and bottom edges are padded with index 0. The output
+/// is the header, the tile dictionary (each tile row-major, in order of first
+/// appearance) and the row-major tile map. Map entries are one byte if there
+/// are at most 256 tiles and a big endian `u16` otherwise.
+pub fn tile_deduplication(indices: &[u8], options: TileOptions) -> Result<Vec<u8>, TileError> {
+    let TileOptions { width, tile_size } = options;
+    if tile_size == 0 {
+        return Err(TileError::ZeroTileSize);
+    }
+    let invalid_width = || TileError::InvalidWidth {
+        width,
+        len: indices.len(),
+    };
+    if width == 0 || !indices.len().is_multiple_of(width as usize) {
+        return Err(invalid_width());
+    }
+    let height = u32::try_from(indices.len() / width as usize).map_err(|_| invalid_width())?;
+
+    let tile = tile_size as usize;
+    let tiles_x = (width as usize).div_ceil(tile);
+    let tiles_y = (height as usize).div_ceil(tile);
+
+    let mut ids: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut dictionary = Vec::new();
+    let mut map = Vec::with_capacity(tiles_x * tiles_y);
+    for tile_y in 0..tiles_y {
+        for tile_x in 0..tiles_x {
+            let mut pixels = vec![0; tile * tile];
+            for row in 0..tile {
+                let y = tile_y * tile + row;
+                let x = tile_x * tile;
+                if y >= height as usize {
+                    break;
+                }
+                let len = tile.min(width as usize - x);
+                let start = y * width as usize + x;
+                pixels[row * tile..row * tile + len].copy_from_slice(&indices[start..start + len]);
+            }
+
+            let next_id = ids.len();
+            let id = *ids.entry(pixels).or_insert_with_key(|pixels| {
+                dictionary.extend_from_slice(pixels);
+                next_id
+            });
+            map.push(id);
+        }
+    }
+
+    let tile_count = ids.len();
+    if tile_count > u16::MAX as usize + 1 {
+        return Err(TileError::TooManyTiles(tile_count));
+    }
+
+    let wide_ids = tile_count > 256;
+    let mut encoded =
+        Vec::with_capacity(HEADER_LEN + dictionary.len() + map.len() * (1 + wide_ids as usize));
+    encoded.extend_from_slice(&width.to_be_bytes());
+    encoded.extend_from_slice(&height.to_be_bytes());
+    encoded.push(tile_size);
+    encoded.extend_from_slice(&(tile_count as u32).to_be_bytes());
+    encoded.extend_from_slice(&dictionary);
+    for id in map {
+        if wide_ids {
+            encoded.extend_from_slice(&(id as u16).to_be_bytes());
+        } else {
+            encoded.push(id as u8);
+        }
+    }
+    Ok(encoded)
+}
+
+/// Reverses `tile_deduplication`, returning the row-major palette indices
+pub fn tile_expansion(data: &[u8]) -> Result<Vec<u8>, TileError> {
+    let invalid = || TileError::InvalidTileData;
+    let header = data.get(..HEADER_LEN).ok_or_else(invalid)?;
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let tile = header[8] as usize;
+    let tile_count = u32::from_be_bytes([header[9], header[10], header[11], header[12]]) as usize;
+    if tile == 0 || width == 0 || tile_count > u16::MAX as usize + 1 {
+        return Err(invalid());
+    }
+
+    let tiles_x = width.div_ceil(tile);
+    let tiles_y = height.div_ceil(tile);
+    let id_len = if tile_count > 256 { 2 } else { 1 };
+    let dictionary_len = (tile_count.checked_mul(tile * tile)).ok_or_else(invalid)?;
+    let map_len = (tiles_x.checked_mul(tiles_y))
+        .and_then(|count| count.checked_mul(id_len))
+        .ok_or_else(invalid)?;
+    // The lengths must add up exactly. That doesn't bound the output, which
+    // is sized by the declared dimensions alone: each map entry expands to
+    // as many as 255 * 255 indices.
+    let section_len = (dictionary_len.checked_add(map_len)).ok_or_else(invalid)?;
+    if data.len() - HEADER_LEN != section_len {
+        return Err(invalid());
+    }
+    let (dictionary, map) = data[HEADER_LEN..].split_at(dictionary_len);
+
+    let output_len = (width.checked_mul(height)).ok_or_else(invalid)?;
+    let mut indices = vec![0; output_len];
+    for (position, id) in map.chunks_exact(id_len).enumerate() {
+        let id = match id {
+            [id] => *id as usize,
+            [high, low] => u16::from_be_bytes([*high, *low]) as usize,
+            _ => unreachable!("ids are one or two bytes"),
+        };
+        if id >= tile_count {
+            return Err(invalid());
+        }
+        let pixels = &dictionary[id * tile * tile..(id + 1) * tile * tile];
+
+        let (tile_x, tile_y) = (position % tiles_x, position / tiles_x);
+        let x = tile_x * tile;
+        let len = tile.min(width - x);
+        for row in 0..tile.min(height - tile_y * tile) {
+            let start = (tile_y * tile + row) * width + x;
+            indices[start..start + len].copy_from_slice(&pixels[row * tile..row * tile + len]);
+        }
+    }
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiles_roundtrip_with_partial_edges() {
+        // 5x3 image in 2x2 tiles leaves a partial column and row
+        let indices: Vec<u8> = (0..15).collect();
+        let options = TileOptions {
+            width: 5,
+            tile_size: 2,
+        };
+        let encoded = tile_deduplication(&indices, options).unwrap();
+        assert_eq!(tile_expansion(&encoded).unwrap(), indices);
+    }
+
+    #[test]
+    fn test_tiles_deduplicate_repeats() {
+        // Four copies of the same 2x2 tile
+        let indices = [1, 2, 1, 2, 3, 4, 3, 4, 1, 2, 1, 2, 3, 4, 3, 4];
+        let options = TileOptions {
+            width: 4,
+            tile_size: 2,
+        };
+        let encoded = tile_deduplication(&indices, options).unwrap();
+        assert_eq!(&encoded[9..13], &1u32.to_be_bytes());
+        assert_eq!(&encoded[HEADER_LEN..], &[1, 2, 3, 4, 0, 0, 0, 0]);
+        assert_eq!(tile_expansion(&encoded).unwrap(), indices);
+    }
+
+    #[test]
+    fn test_tiles_wide_ids() {
+        // 256 distinct 1x1 tiles still fit one-byte ids
+        let indices: Vec<u8> = (0..=255).cycle().take(300).collect();
+        let options = TileOptions {
+            width: 300,
+            tile_size: 1,
+        };
+        let encoded = tile_deduplication(&indices, options).unwrap();
+        assert_eq!(encoded.len(), HEADER_LEN + 256 + 300);
+        assert_eq!(tile_expansion(&encoded).unwrap(), indices);
+
+        // 300 distinct 2x2 tiles, numbered in their top row, need two
+        let mut indices: Vec<u8> = (0..300u16).flat_map(u16::to_le_bytes).collect();
+        indices.resize(1200, 0);
+        let options = TileOptions {
+            width: 600,
+            tile_size: 2,
+        };
+        let encoded = tile_deduplication(&indices, options).unwrap();
+        assert_eq!(&encoded[9..13], &300u32.to_be_bytes());
+        assert_eq!(encoded.len(), HEADER_LEN + 300 * 4 + 300 * 2);
+        assert_eq!(tile_expansion(&encoded).unwrap(), indices);
+    }
+
+    #[test]
+    fn test_tiles_invalid_input() {
+        let options = TileOptions {
+            width: 3,
+            tile_size: 2,
+        };
+        assert!(matches!(
+            tile_deduplication(&[0; 10], options),
+            Err(TileError::InvalidWidth { width: 3, len: 10 })
+        ));
+        assert!(matches!(
+            tile_deduplication(
+                &[0; 9],
+                TileOptions {
+                    tile_size: 0,
+                    ..options
+                }
+            ),
+            Err(TileError::ZeroTileSize)
+        ));
+
+        let encoded = tile_deduplication(&[0; 9], options).unwrap();
+        assert!(tile_expansion(&encoded[..encoded.len() - 1]).is_err());
+        assert!(tile_expansion(&encoded[..HEADER_LEN - 1]).is_err());
+
+        // A map entry past the dictionary
+        let mut bad = encoded.clone();
+        *bad.last_mut().unwrap() = 1;
+        assert!(matches!(
+            tile_expansion(&bad),
+            Err(TileError::InvalidTileData)
+        ));
+
+        // Sizes that would overflow are rejected rather than computed
+        let mut huge = u32::MAX.to_be_bytes().repeat(2);
+        huge.push(u8::MAX);
+        huge.extend_from_slice(&(u16::MAX as u32 + 1).to_be_bytes());
+        assert!(matches!(
+            tile_expansion(&huge),
+            Err(TileError::InvalidTileData)
+        ));
+    }
+}
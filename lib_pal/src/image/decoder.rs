@@ -1,53 +1,356 @@
-use super::format::{Image, MAGIC_HEADER};
-use crate::compression::{decompress, CompressionResult, DecompressionError};
-use log::{debug, error, info};
+use super::format::{
+    Image, IndexedImage, FLAG_CHANNEL_PLANES, FLAG_FRAMES, FLAG_FRAME_DELTA, FLAG_HUFFMAN,
+    FLAG_METADATA, FLAG_NO_RLE_DELTA, FLAG_SPLIT_ALPHA, FLAG_TILES, FORMAT_VERSION, MAGIC_HEADER,
+    MAGIC_HEADER_EXTENDED, MAGIC_HEADER_VERSIONED,
+};
+use crate::compression::huffman::{
+    huffman_decode, huffman_decode_partial, HuffmanCode, HuffmanTable,
+};
+use crate::compression::lzw::{
+    lzw_decompression, lzw_decompression_legacy, lzw_decompression_legacy_partial,
+    lzw_decompression_partial, LzwDecompressionError,
+};
+use crate::compression::palette::{
+    palette_decompression, split_alpha_palette_decompression, PaletteCompression,
+    SplitAlphaPaletteCompression,
+};
+use crate::compression::planes::merge_channel_planes;
+use crate::compression::rle_delta::{rle_delta_decompression, rle_delta_decompression_partial};
+use crate::compression::tiles::{tile_expansion, TileOptions};
+use crate::compression::{decompress_indices, DecompressionError};
+use log::{debug, error, info, warn};
+use std::io::{self, Read};
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum DecodeError {
     #[error("Invalid format or header")]
     InvalidHeader,
+    #[error("Unsupported format version {0}")]
+    UnsupportedVersion(u8),
     #[error("Unexpected end of data while reading palette color #{0}")]
     UnexpectedEofPaletteColor(usize),
     #[error("Insufficient data for palette size")]
     InsufficientDataForPaletteSize,
     #[error("Failed to parse image dimensions")]
     DimensionParsingFailed,
+    #[error("Palette declares {declared} colors but pixel data references index {max_index}")]
+    PaletteSizeInconsistent { declared: usize, max_index: usize },
+    #[error("Invalid or truncated metadata section")]
+    InvalidMetadata,
+    #[error("Invalid or truncated Huffman code table")]
+    InvalidHuffmanTable,
+    #[error("Invalid or truncated tile section")]
+    InvalidTileSection,
+    #[error("Invalid or truncated alpha section")]
+    InvalidAlphaSection,
+    #[error("Tile size must be greater than zero")]
+    InvalidTileSize,
+    #[error("Checksum mismatch: expected {expected:#010x}, found {found:#010x}")]
+    ChecksumMismatch { expected: u32, found: u32 },
+    #[error("Invalid or truncated frames section")]
+    InvalidFrames,
+    #[error("Unknown pipeline flags {0:#010b}")]
+    UnknownPipeline(u8),
+    #[error("Image is not stored as one palette index per pixel")]
+    NotIndexed,
 
     #[error("Decompression failed")]
     DecompressionFailed(#[from] DecompressionError),
+    #[error("Failed to read encoded data: {0}")]
+    Io(#[from] io::Error),
 }
 
-pub fn decode(encoded_data: &[u8]) -> Result<Image, DecodeError> {
+/// Header fields and palette shared by every decode entry point
+pub(crate) struct DecodedHeader {
+    /// Format version, 0 for files from before the version byte
+    pub version: u8,
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 4]>,
+    pub metadata: Vec<(String, String)>,
+    pub huffman: Option<HuffmanTable>,
+    pub payload_offset: usize,
+    /// End of the payload, before the checksum trailer or the remaining
+    /// frames
+    pub payload_end: usize,
+    /// Payloads of the animation frames after the first
+    pub frames: Vec<Range<usize>>,
+    /// Frames after the first hold index diffs against the previous frame
+    pub frame_delta: bool,
+    /// Whether the RLE-delta stage ran on the payload
+    pub rle_delta: bool,
+    /// How the indices were split, if tile deduplication ran
+    pub tiles: Option<TileOptions>,
+    /// The payload holds channel planes rather than palette indices
+    pub channel_planes: bool,
+    /// Alpha table, if RGB and alpha were indexed separately
+    pub alphas: Option<Vec<u8>>,
+}
+
+impl DecodedHeader {
+    /// Returns the LZW payload following the header, undoing the Huffman
+    /// stage if the file uses it
+    pub fn decode_payload(&self, encoded_data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let payload = &encoded_data[self.payload_offset..self.payload_end];
+        debug!("Compressed data length: {}", payload.len());
+
+        let Some(table) = &self.huffman else {
+            return Ok(payload.to_vec());
+        };
+        let decoded = huffman_decode(&HuffmanCode {
+            encoding_map: table.encoding_map.clone(),
+            encoded_data: payload.to_vec(),
+            padding_bits: table.padding_bits,
+        })
+        .map_err(DecompressionError::from)?;
+        debug!("Huffman decoding: {} bytes", decoded.len());
+        Ok(decoded)
+    }
+
+    /// Returns the palette indices of the payload, undoing every stage the
+    /// flags name. Channel planes and split alpha have no single index per
+    /// pixel and fail with `NotIndexed`.
+    pub fn decode_indices(&self, encoded_data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        if self.channel_planes || self.alphas.is_some() {
+            error!("Image is not stored as one palette index per pixel");
+            return Err(DecodeError::NotIndexed);
+        }
+        self.decode_stages(encoded_data)
+    }
+
+    /// Returns the RGBA pixels of the payload, from its palette indices,
+    /// split-alpha indices or channel planes
+    pub fn decode_rgba(&self, encoded_data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        if let Some(alphas) = &self.alphas {
+            let split = SplitAlphaPaletteCompression::from_indices(
+                self.palette.iter().map(|&[r, g, b, _]| [r, g, b]).collect(),
+                alphas.clone(),
+                &self.decode_stages(encoded_data)?,
+            )
+            .map_err(DecompressionError::from)?;
+            let pixels =
+                split_alpha_palette_decompression(&split).map_err(DecompressionError::from)?;
+            debug!("Split-alpha expansion: {} bytes", pixels.len());
+            return Ok(pixels);
+        }
+        if !self.channel_planes {
+            return self.indices_to_rgba(self.decode_indices(encoded_data)?);
+        }
+        let planes = self.decode_stages(encoded_data)?;
+        let pixels = merge_channel_planes(&planes).map_err(DecompressionError::from)?;
+        debug!("Channel plane merge: {} bytes", pixels.len());
+        Ok(pixels)
+    }
+
+    /// Undoes every stage the flags name, up to the palette indices or
+    /// channel planes
+    fn decode_stages(&self, encoded_data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let compressed_data = self.decode_payload(encoded_data)?;
+        let indices = if compressed_data.is_empty() {
+            // Mirrors `decompress_indices`, which reads empty data as no
+            // indices
+            Vec::new()
+        } else {
+            let lzw_decompressed = self
+                .decompress_lzw(&compressed_data)
+                .map_err(DecompressionError::from)?;
+            debug!("LZW decompression: {} bytes", lzw_decompressed.len());
+            if self.rle_delta {
+                rle_delta_decompression(&lzw_decompressed).map_err(DecompressionError::from)?
+            } else {
+                lzw_decompressed
+            }
+        };
+
+        if self.tiles.is_none() {
+            return Ok(indices);
+        }
+        let indices = tile_expansion(&indices).map_err(DecompressionError::from)?;
+        debug!("Tile expansion: {} bytes", indices.len());
+        Ok(indices)
+    }
+
+    /// Files from before the version byte store fixed 16-bit LZW codes;
+    /// every versioned file uses variable-width ones
+    fn decompress_lzw(&self, data: &[u8]) -> Result<Vec<u8>, LzwDecompressionError> {
+        if self.version == 0 {
+            lzw_decompression_legacy(data)
+        } else {
+            lzw_decompression(data)
+        }
+    }
+
+    /// Like `decode_stages`, but returns whatever decodes before the first
+    /// error in any stage, which may be fewer indices or plane bytes than
+    /// the image needs
+    fn decode_stages_partial(&self, encoded_data: &[u8]) -> Vec<u8> {
+        let payload = &encoded_data[self.payload_offset..self.payload_end];
+        let compressed_data = match &self.huffman {
+            Some(table) => huffman_decode_partial(&HuffmanCode {
+                encoding_map: table.encoding_map.clone(),
+                encoded_data: payload.to_vec(),
+                padding_bits: table.padding_bits,
+            })
+            .unwrap_or_default(),
+            None => payload.to_vec(),
+        };
+
+        let lzw_decompressed = if self.version == 0 {
+            lzw_decompression_legacy_partial(&compressed_data)
+        } else {
+            lzw_decompression_partial(&compressed_data)
+        };
+        let indices = if self.rle_delta {
+            rle_delta_decompression_partial(&lzw_decompressed)
+        } else {
+            lzw_decompressed
+        };
+
+        // Tiles only expand once complete, so a damaged tiled payload
+        // yields no pixels
+        if self.tiles.is_some() {
+            return tile_expansion(&indices).unwrap_or_default();
+        }
+        indices
+    }
+
+    /// Returns the RGBA pixels of every animation frame, one frame for a
+    /// still image
+    pub fn decode_frames_rgba(&self, encoded_data: &[u8]) -> Result<Vec<Vec<u8>>, DecodeError> {
+        // Only palette indices are animated
+        if self.channel_planes || self.alphas.is_some() {
+            return Ok(vec![self.decode_rgba(encoded_data)?]);
+        }
+
+        let mut indices = self.decode_indices(encoded_data)?;
+        let mut frames = Vec::with_capacity(self.frames.len() + 1);
+        for range in self.frames.iter().cloned() {
+            let mut next = decompress_indices(&encoded_data[range])?;
+            if self.frame_delta {
+                // Apply the diff to the previous frame
+                if next.len() != indices.len() {
+                    error!(
+                        "Frame diff covers {} pixels, expected {}",
+                        next.len(),
+                        indices.len()
+                    );
+                    return Err(DecodeError::InvalidFrames);
+                }
+                for (index, previous) in next.iter_mut().zip(&indices) {
+                    *index ^= previous;
+                }
+            }
+            frames.push(std::mem::replace(&mut indices, next));
+        }
+        frames.push(indices);
+
+        frames
+            .into_iter()
+            .map(|indices| self.indices_to_rgba(indices))
+            .collect()
+    }
+
+    /// The most pixels the payload could decode to, taking every stage to
+    /// expand as much as its format allows. Lets `decode_lenient` reject
+    /// dimensions that no file of this size could fill.
+    fn max_pixels(&self) -> usize {
+        let mut len = self.payload_end - self.payload_offset;
+        // Huffman codes are at least one bit per byte
+        if self.huffman.is_some() {
+            len = len.saturating_mul(8);
+        }
+        // LZW codes are at least 9 bits, and each one decodes to at most one
+        // byte more than the one before it
+        let codes = len.saturating_mul(8) / 9;
+        len = codes.saturating_mul(codes.saturating_add(1)) / 2;
+        // An RLE-delta run of two bytes repeats its delta up to 255 times
+        if self.rle_delta {
+            len = (len / 2).saturating_mul(255);
+        }
+        if let Some(tiles) = self.tiles {
+            let tile_size = tiles.tile_size as usize;
+            len = len.saturating_mul(tile_size * tile_size);
+        }
+        if self.channel_planes {
+            len /= 4;
+        }
+        if self.alphas.is_some() {
+            len /= 2;
+        }
+        len
+    }
+
+    /// Expands palette indices to RGBA
+    fn indices_to_rgba(&self, indices: Vec<u8>) -> Result<Vec<u8>, DecodeError> {
+        check_palette_size(self.palette.len(), &indices)?;
+        let rgba_data = palette_decompression(&PaletteCompression {
+            palette: self.palette.clone(),
+            indices,
+        })
+        .map_err(DecompressionError::from)?;
+        Ok(rgba_data)
+    }
+}
+
+pub(crate) fn decode_header(encoded_data: &[u8]) -> Result<DecodedHeader, DecodeError> {
+    read_header(encoded_data, false)
+}
+
+/// Parses everything before the payload. A `lenient` read accepts a file
+/// whose checksum doesn't match, taking it to be cut short and reading the
+/// payload to the end of the data.
+fn read_header(encoded_data: &[u8], lenient: bool) -> Result<DecodedHeader, DecodeError> {
     let mut cursor = 0;
 
-    // Check the header and magic number
-    if encoded_data.len() < Image::MAGIC_SIZE || !encoded_data.starts_with(&MAGIC_HEADER) {
+    // Check the header and magic number. Only versioned files carry a
+    // version byte; older ones are version 0.
+    let versioned = encoded_data.starts_with(&MAGIC_HEADER_VERSIONED);
+    let extended = versioned || encoded_data.starts_with(&MAGIC_HEADER_EXTENDED);
+    if !(extended || encoded_data.starts_with(&MAGIC_HEADER)) {
         error!("Invalid format or missing magic number in header");
         return Err(DecodeError::InvalidHeader);
     }
     debug!("Magic number validated successfully");
     cursor += Image::MAGIC_SIZE;
 
-    // Read width and height
-    let width = u16::from_be_bytes(
-        encoded_data[cursor..cursor + Image::WIDTH_HEIGHT_SIZE]
-            .try_into()
-            .map_err(|_| {
-                error!("Failed to parse width");
-                DecodeError::DimensionParsingFailed
-            })?,
-    );
-    cursor += Image::WIDTH_HEIGHT_SIZE;
-    let height = u16::from_be_bytes(
-        encoded_data[cursor..cursor + Image::WIDTH_HEIGHT_SIZE]
-            .try_into()
-            .map_err(|_| {
-                error!("Failed to parse height");
-                DecodeError::DimensionParsingFailed
-            })?,
-    );
-    cursor += Image::WIDTH_HEIGHT_SIZE;
+    let version = if versioned {
+        let version = *encoded_data.get(cursor).ok_or(DecodeError::InvalidHeader)?;
+        if version == 0 || version > FORMAT_VERSION {
+            error!("Unsupported format version {}", version);
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        cursor += Image::VERSION_SIZE;
+        version
+    } else {
+        0
+    };
+    debug!("Format version: {}", version);
+
+    // Read width and height, which were u16 before version 2
+    let dimension_size = if version >= 2 {
+        Image::WIDTH_HEIGHT_SIZE
+    } else {
+        Image::LEGACY_WIDTH_HEIGHT_SIZE
+    };
+    let read_dimension = |cursor| {
+        if version >= 2 {
+            read_u32(encoded_data, cursor)
+        } else {
+            read_u16(encoded_data, cursor).map(u32::from)
+        }
+    };
+    let width = read_dimension(cursor).ok_or_else(|| {
+        error!("Failed to parse width");
+        DecodeError::DimensionParsingFailed
+    })?;
+    cursor += dimension_size;
+    let height = read_dimension(cursor).ok_or_else(|| {
+        error!("Failed to parse height");
+        DecodeError::DimensionParsingFailed
+    })?;
+    cursor += dimension_size;
     debug!("Image dimensions read: width={} height={}", width, height);
 
     // Read palette size
@@ -55,7 +358,12 @@ pub fn decode(encoded_data: &[u8]) -> Result<Image, DecodeError> {
         error!("Insufficient data for palette size");
         return Err(DecodeError::InsufficientDataForPaletteSize);
     }
-    let palette_size = encoded_data[cursor] as usize;
+    // A full 256-color palette doesn't fit the size byte and is stored as 0.
+    // Palettes are never empty, so 0 always means 256.
+    let palette_size = match encoded_data[cursor] {
+        0 => 256,
+        size => size as usize,
+    };
     cursor += Image::PALETTE_SIZE_SIZE;
     debug!("Palette size: {}", palette_size);
 
@@ -77,23 +385,484 @@ pub fn decode(encoded_data: &[u8]) -> Result<Image, DecodeError> {
         debug!("Read palette color #{}: {:?}", i, color);
     }
 
-    // The remaining data is compressed image data
-    let compressed_data = &encoded_data[cursor..];
-    debug!("Compressed data length: {}", compressed_data.len());
+    // Since version 3 a CRC32 of everything after the palette trails the
+    // file. Check it before reading any further.
+    let encoded_data = if version >= 3 {
+        match check_checksum(encoded_data, cursor) {
+            Ok(data_end) => &encoded_data[..data_end],
+            Err(err) if lenient => {
+                warn!("{}, reading the data as truncated", err);
+                encoded_data
+            }
+            Err(err) => return Err(err),
+        }
+    } else {
+        encoded_data
+    };
 
-    // Perform decompression
-    let rgba_data = decompress(CompressionResult {
-        palette: palette.clone(),
-        data: compressed_data.to_vec(),
-    })?;
-    info!("Decompression successful");
+    // Extended files announce their optional sections in a flags byte
+    let flags = if extended {
+        let flags = *encoded_data.get(cursor).ok_or(DecodeError::InvalidHeader)?;
+        check_pipeline(flags)?;
+        cursor += 1;
+        flags
+    } else {
+        0
+    };
 
-    // Return the decoded image
-    Ok(Image::new(
+    let metadata = if flags & FLAG_METADATA != 0 {
+        read_metadata(encoded_data, &mut cursor).ok_or_else(|| {
+            error!("Invalid or truncated metadata section");
+            DecodeError::InvalidMetadata
+        })?
+    } else {
+        Vec::new()
+    };
+
+    let huffman = if flags & FLAG_HUFFMAN != 0 {
+        let table = HuffmanTable::read_from(encoded_data, &mut cursor).ok_or_else(|| {
+            error!("Invalid or truncated Huffman code table");
+            DecodeError::InvalidHuffmanTable
+        })?;
+        Some(table)
+    } else {
+        None
+    };
+
+    let tiles = if flags & FLAG_TILES != 0 {
+        let tiles = read_tile_section(encoded_data, &mut cursor).ok_or_else(|| {
+            error!("Invalid or truncated tile section");
+            DecodeError::InvalidTileSection
+        })?;
+        Some(tiles)
+    } else {
+        None
+    };
+
+    let alphas = if flags & FLAG_SPLIT_ALPHA != 0 {
+        let alphas = read_alpha_section(encoded_data, &mut cursor).ok_or_else(|| {
+            error!("Invalid or truncated alpha section");
+            DecodeError::InvalidAlphaSection
+        })?;
+        Some(alphas)
+    } else {
+        None
+    };
+
+    let (payload_end, frames) = if flags & FLAG_FRAMES != 0 {
+        match read_frames(encoded_data, &mut cursor) {
+            Some(frames) => frames,
+            // Keep what is left of the first frame
+            None => match read_u32(encoded_data, cursor).filter(|_| lenient) {
+                Some(len) => {
+                    warn!("Truncated frames section, reading the first frame only");
+                    cursor += 4;
+                    let end = cursor.saturating_add(len as usize).min(encoded_data.len());
+                    (end, Vec::new())
+                }
+                None => {
+                    error!("Invalid or truncated frames section");
+                    return Err(DecodeError::InvalidFrames);
+                }
+            },
+        }
+    } else {
+        (encoded_data.len(), Vec::new())
+    };
+
+    Ok(DecodedHeader {
+        version,
         width,
         height,
-        palette_size as u8,
         palette,
-        rgba_data,
-    ))
+        metadata,
+        huffman,
+        payload_offset: cursor,
+        payload_end,
+        frames,
+        frame_delta: flags & FLAG_FRAME_DELTA != 0,
+        rle_delta: flags & FLAG_NO_RLE_DELTA == 0,
+        tiles,
+        channel_planes: flags & FLAG_CHANNEL_PLANES != 0,
+        alphas,
+    })
+}
+
+/// Checks the CRC32 trailer of the data after the palette, which starts at
+/// `cursor`, and returns where the trailer starts
+fn check_checksum(encoded_data: &[u8], cursor: usize) -> Result<usize, DecodeError> {
+    let data_end = encoded_data
+        .len()
+        .checked_sub(Image::CHECKSUM_SIZE)
+        .filter(|&end| end >= cursor)
+        .ok_or_else(|| {
+            error!("Missing checksum trailer");
+            DecodeError::InvalidHeader
+        })?;
+    let expected = read_u32(encoded_data, data_end).ok_or(DecodeError::InvalidHeader)?;
+    let found = crc32fast::hash(&encoded_data[cursor..data_end]);
+    if expected != found {
+        error!(
+            "Checksum mismatch: expected {:#010x}, found {:#010x}",
+            expected, found
+        );
+        return Err(DecodeError::ChecksumMismatch { expected, found });
+    }
+    Ok(data_end)
+}
+
+/// Rejects unknown flag bits and combinations the encoder never writes
+fn check_pipeline(flags: u8) -> Result<(), DecodeError> {
+    let known = FLAG_METADATA
+        | FLAG_HUFFMAN
+        | FLAG_FRAMES
+        | FLAG_FRAME_DELTA
+        | FLAG_NO_RLE_DELTA
+        | FLAG_TILES
+        | FLAG_CHANNEL_PLANES
+        | FLAG_SPLIT_ALPHA;
+    let frames = flags & FLAG_FRAMES != 0;
+    // Each of these replaces the single palette index per pixel
+    let index_layouts = FLAG_TILES | FLAG_CHANNEL_PLANES | FLAG_SPLIT_ALPHA;
+    let non_default = FLAG_HUFFMAN | FLAG_NO_RLE_DELTA | index_layouts;
+    let supported = flags & !known == 0
+        // Frames always run the default pipeline
+        && !(frames && flags & non_default != 0)
+        && (flags & index_layouts).count_ones() <= 1
+        && (frames || flags & FLAG_FRAME_DELTA == 0);
+    if !supported {
+        error!("Unknown pipeline flags: {:#010b}", flags);
+        return Err(DecodeError::UnknownPipeline(flags));
+    }
+    Ok(())
+}
+
+/// Reads the length of the first frame at `cursor`, leaving it at the start
+/// of that frame, then the frames following it. Returns the end of the first
+/// frame and the ranges of the others, which must end with `data`.
+fn read_frames(data: &[u8], cursor: &mut usize) -> Option<(usize, Vec<Range<usize>>)> {
+    let read_frame = |cursor: &mut usize| {
+        let len = read_u32(data, *cursor)? as usize;
+        let start = *cursor + 4;
+        let end = start.checked_add(len).filter(|&end| end <= data.len())?;
+        *cursor = end;
+        Some(start..end)
+    };
+
+    let first = read_frame(cursor)?;
+    *cursor = first.start;
+
+    let mut frames_cursor = first.end;
+    let count = read_u32(data, frames_cursor)?;
+    frames_cursor += 4;
+    let frames = (0..count)
+        .map(|_| read_frame(&mut frames_cursor))
+        .collect::<Option<Vec<_>>>()?;
+    if frames_cursor != data.len() {
+        return None;
+    }
+    Some((first.end, frames))
+}
+
+/// Reads the alpha count, 0 standing for 256, and values of the
+/// `FLAG_SPLIT_ALPHA` section
+fn read_alpha_section(data: &[u8], cursor: &mut usize) -> Option<Vec<u8>> {
+    let len = match *data.get(*cursor)? {
+        0 => 256,
+        len => len as usize,
+    };
+    let alphas = data.get(*cursor + 1..*cursor + 1 + len)?.to_vec();
+    *cursor += 1 + len;
+    Some(alphas)
+}
+
+/// Reads the tile width and tile size of the `FLAG_TILES` section
+fn read_tile_section(data: &[u8], cursor: &mut usize) -> Option<TileOptions> {
+    let width = read_u32(data, *cursor)?;
+    let tile_size = *data.get(*cursor + 4)?;
+    if width == 0 || tile_size == 0 {
+        return None;
+    }
+    *cursor += Image::TILE_SECTION_SIZE;
+    Some(TileOptions { width, tile_size })
+}
+
+fn read_metadata(data: &[u8], cursor: &mut usize) -> Option<Vec<(String, String)>> {
+    let read_string = |cursor: &mut usize| {
+        let len = read_u16(data, *cursor)? as usize;
+        *cursor += 2;
+        let bytes = data.get(*cursor..*cursor + len)?;
+        *cursor += len;
+        String::from_utf8(bytes.to_vec()).ok()
+    };
+
+    let count = read_u16(data, *cursor)?;
+    *cursor += 2;
+    (0..count)
+        .map(|_| Some((read_string(cursor)?, read_string(cursor)?)))
+        .collect()
+}
+
+/// Ensures every decompressed index fits within the declared palette
+fn check_palette_size(declared: usize, indices: &[u8]) -> Result<(), DecodeError> {
+    let Some(max_index) = indices.iter().max().map(|&index| index as usize) else {
+        return Ok(());
+    };
+    if max_index >= declared {
+        error!(
+            "Palette declares {} colors but pixel data references index {}",
+            declared, max_index
+        );
+        return Err(DecodeError::PaletteSizeInconsistent {
+            declared,
+            max_index,
+        });
+    }
+    Ok(())
+}
+
+fn read_u16(data: &[u8], cursor: usize) -> Option<u16> {
+    data.get(cursor..cursor + std::mem::size_of::<u16>())
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u16::from_be_bytes)
+}
+
+fn read_u32(data: &[u8], cursor: usize) -> Option<u32> {
+    data.get(cursor..cursor + std::mem::size_of::<u32>())
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_be_bytes)
+}
+
+pub fn decode(encoded_data: &[u8]) -> Result<Image, DecodeError> {
+    let header = decode_header(encoded_data)?;
+
+    // The remaining data is compressed image data
+    let rgba_data = header.decode_rgba(encoded_data)?;
+    info!("Decompression successful");
+
+    // Return the decoded image
+    Ok(Image {
+        magic: encoded_data[..Image::MAGIC_SIZE].try_into().unwrap(),
+        version: header.version,
+        ..Image::new(
+            header.width,
+            header.height,
+            header.palette.len() as u8,
+            header.palette,
+            rgba_data,
+        )
+    })
+}
+
+/// Like [`decode`], but recovers what it can of a damaged file, e.g. to
+/// open it in the editor.
+///
+/// A checksum mismatch is taken to mean the file was cut short, and the
+/// payload is decoded up to the first error. Pixels that didn't decode, or
+/// whose index is outside the palette, are transparent, and an animation
+/// yields its first frame. The header and palette must still be intact, and
+/// dimensions far beyond what the payload could fill are rejected.
+pub fn decode_lenient(encoded_data: &[u8]) -> Result<Image, DecodeError> {
+    let header = read_header(encoded_data, true)?;
+    let pixel_count = (header.width as usize)
+        .checked_mul(header.height as usize)
+        .filter(|&pixel_count| pixel_count <= header.max_pixels())
+        .ok_or_else(|| {
+            error!(
+                "Dimensions {}x{} exceed what the payload could hold",
+                header.width, header.height
+            );
+            DecodeError::DimensionParsingFailed
+        })?;
+    let rgba_len = pixel_count
+        .checked_mul(4)
+        .ok_or(DecodeError::DimensionParsingFailed)?;
+    let decoded = header.decode_stages_partial(encoded_data);
+
+    let rgba_data = if header.channel_planes {
+        // Planes follow one another, so missing bytes only lose the end of
+        // the last channels
+        let mut planes = decoded;
+        if planes.len() < rgba_len {
+            warn!("Recovered {} of {} plane bytes", planes.len(), rgba_len);
+        }
+        planes.resize(rgba_len, 0);
+        merge_channel_planes(&planes).map_err(DecompressionError::from)?
+    } else if let Some(alphas) = &header.alphas {
+        // The alpha indices follow all color indices, so a pixel only
+        // decodes if the payload reaches its alpha index
+        let (color_indices, alpha_indices) = decoded.split_at(pixel_count.min(decoded.len()));
+        if alpha_indices.len() < pixel_count {
+            warn!(
+                "Recovered {} of {} pixels",
+                alpha_indices.len(),
+                pixel_count
+            );
+        }
+        let mut rgba_data = vec![0; rgba_len];
+        for ((pixel, &color), &alpha) in rgba_data
+            .chunks_exact_mut(4)
+            .zip(color_indices)
+            .zip(alpha_indices)
+        {
+            let color = header.palette.get(color as usize);
+            if let (Some(&[r, g, b, _]), Some(&alpha)) = (color, alphas.get(alpha as usize)) {
+                pixel.copy_from_slice(&[r, g, b, alpha]);
+            }
+        }
+        rgba_data
+    } else {
+        let indices = decoded;
+        if indices.len() < pixel_count {
+            warn!("Recovered {} of {} pixels", indices.len(), pixel_count);
+        }
+        let mut rgba_data = vec![0; rgba_len];
+        for (pixel, &index) in rgba_data.chunks_exact_mut(4).zip(&indices) {
+            if let Some(color) = header.palette.get(index as usize) {
+                pixel.copy_from_slice(color);
+            }
+        }
+        rgba_data
+    };
+
+    Ok(Image {
+        magic: encoded_data[..Image::MAGIC_SIZE].try_into().unwrap(),
+        version: header.version,
+        ..Image::new(
+            header.width,
+            header.height,
+            header.palette.len() as u8,
+            header.palette,
+            rgba_data,
+        )
+    })
+}
+
+/// Decodes every frame of an animated sprite written by
+/// [`encode_frames`](super::encode_frames). A still image yields one frame.
+pub fn decode_frames(encoded_data: &[u8]) -> Result<Vec<Image>, DecodeError> {
+    let header = decode_header(encoded_data)?;
+    let magic: [u8; 4] = encoded_data[..Image::MAGIC_SIZE].try_into().unwrap();
+
+    let frames: Vec<Image> = header
+        .decode_frames_rgba(encoded_data)?
+        .into_iter()
+        .map(|rgba_data| Image {
+            magic,
+            version: header.version,
+            ..Image::new(
+                header.width,
+                header.height,
+                header.palette.len() as u8,
+                header.palette.clone(),
+                rgba_data,
+            )
+        })
+        .collect();
+    info!("Decoded {} frames", frames.len());
+
+    Ok(frames)
+}
+
+/// Reads and decodes an image from `reader`, e.g. straight from a `File`.
+///
+/// The magic number is checked before anything else is read, so other files
+/// are rejected without being pulled in. The payload has no length prefix,
+/// so the rest of the reader is consumed.
+pub fn decode_from<R: Read>(reader: &mut R) -> Result<Image, DecodeError> {
+    let mut encoded_data = vec![0; Image::MAGIC_SIZE];
+    reader.read_exact(&mut encoded_data).map_err(|err| {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            error!("Invalid format or missing magic number in header");
+            DecodeError::InvalidHeader
+        } else {
+            DecodeError::Io(err)
+        }
+    })?;
+    if ![MAGIC_HEADER, MAGIC_HEADER_EXTENDED, MAGIC_HEADER_VERSIONED]
+        .iter()
+        .any(|magic| encoded_data == magic)
+    {
+        error!("Invalid format or missing magic number in header");
+        return Err(DecodeError::InvalidHeader);
+    }
+
+    reader.read_to_end(&mut encoded_data)?;
+    decode(&encoded_data)
+}
+
+/// Reads the metadata stored in an encoded image without decompressing its
+/// pixels. Files without a metadata section yield an empty list.
+pub fn decode_metadata(encoded_data: &[u8]) -> Result<Vec<(String, String)>, DecodeError> {
+    Ok(decode_header(encoded_data)?.metadata)
+}
+
+/// Decodes an image into its palette and per-pixel palette indices without
+/// expanding them to RGBA.
+pub fn decode_indexed(encoded_data: &[u8]) -> Result<IndexedImage, DecodeError> {
+    let header = decode_header(encoded_data)?;
+
+    let indices = header.decode_indices(encoded_data)?;
+    check_palette_size(header.palette.len(), &indices)?;
+    info!("Index decompression successful");
+
+    Ok(IndexedImage {
+        width: header.width,
+        height: header.height,
+        palette: header.palette,
+        indices,
+    })
+}
+
+/// Decodes an image and splits it into `tile_size`×`tile_size` RGBA tiles.
+///
+/// Returns the image dimensions and the tiles in row-major order. Tiles on the
+/// right and bottom edges are padded with transparent pixels.
+pub fn decode_tiles(
+    encoded_data: &[u8],
+    tile_size: u32,
+) -> Result<(u32, u32, Vec<Vec<u8>>), DecodeError> {
+    if tile_size == 0 {
+        error!("Tile size must be greater than zero");
+        return Err(DecodeError::InvalidTileSize);
+    }
+    let tile_size = tile_size as usize;
+    let Some((tile_row_bytes, tile_bytes)) = (tile_size.checked_mul(4))
+        .and_then(|row_bytes| Some((row_bytes, row_bytes.checked_mul(tile_size)?)))
+    else {
+        error!("Tile size {} is too large", tile_size);
+        return Err(DecodeError::InvalidTileSize);
+    };
+
+    let image = decode(encoded_data)?;
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let tiles_x = width.div_ceil(tile_size);
+    let tiles_y = height.div_ceil(tile_size);
+
+    let mut tiles = Vec::with_capacity(tiles_x * tiles_y);
+    for tile_y in 0..tiles_y {
+        for tile_x in 0..tiles_x {
+            let mut tile = vec![0; tile_bytes];
+            let start_x = tile_x * tile_size;
+            let copy_width = tile_size.min(width - start_x);
+
+            for row in 0..tile_size {
+                let y = tile_y * tile_size + row;
+                if y >= height {
+                    break;
+                }
+                let src = (y * width + start_x) * 4;
+                let dst = row * tile_row_bytes;
+                let len = copy_width * 4;
+                if let Some(pixels) = image.rgba_data.get(src..src + len) {
+                    tile[dst..dst + len].copy_from_slice(pixels);
+                }
+            }
+            tiles.push(tile);
+        }
+    }
+    debug!("Split image into {}x{} tiles", tiles_x, tiles_y);
+
+    Ok((width as u32, height as u32, tiles))
 }
@@ -0,0 +1,103 @@
+use super::format::Image;
+
+/// Structured comparison between two images
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImageDiff {
+    /// `Some((self_dimensions, other_dimensions))` if the sizes differ
+    pub dimension_mismatch: Option<((u32, u32), (u32, u32))>,
+    pub differing_pixels: usize,
+    /// Inclusive (min_x, min_y, max_x, max_y) of all differing pixels
+    pub bounding_box: Option<(u32, u32, u32, u32)>,
+    pub max_channel_delta: u8,
+}
+
+impl ImageDiff {
+    pub fn is_identical(&self) -> bool {
+        self.dimension_mismatch.is_none() && self.differing_pixels == 0
+    }
+}
+
+impl Image {
+    /// Compares two images pixel by pixel.
+    ///
+    /// Images with different dimensions only report the mismatch.
+    pub fn diff(&self, other: &Image) -> ImageDiff {
+        let mut diff = ImageDiff::default();
+
+        if (self.width, self.height) != (other.width, other.height) {
+            diff.dimension_mismatch =
+                Some(((self.width, self.height), (other.width, other.height)));
+            return diff;
+        }
+
+        let pixels = self.rgba_data.chunks(4).zip(other.rgba_data.chunks(4));
+        for (i, (a, b)) in pixels.enumerate() {
+            if a == b {
+                continue;
+            }
+
+            let x = (i % self.width as usize) as u32;
+            let y = (i / self.width as usize) as u32;
+
+            diff.differing_pixels += 1;
+            diff.bounding_box = Some(match diff.bounding_box {
+                Some((min_x, min_y, max_x, max_y)) => {
+                    (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                }
+                None => (x, y, x, y),
+            });
+
+            for (&channel_a, &channel_b) in a.iter().zip(b) {
+                diff.max_channel_delta = diff.max_channel_delta.max(channel_a.abs_diff(channel_b));
+            }
+        }
+
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid_image(width: u32, height: u32, color: [u8; 4]) -> Image {
+        let rgba_data = color.repeat(width as usize * height as usize);
+        Image::new(width, height, 1, vec![color], rgba_data)
+    }
+
+    #[test]
+    fn test_diff_identical() {
+        let a = solid_image(4, 4, [255, 0, 0, 255]);
+        let b = solid_image(4, 4, [255, 0, 0, 255]);
+
+        let diff = a.diff(&b);
+        assert!(diff.is_identical());
+        assert_eq!(diff, ImageDiff::default());
+    }
+
+    #[test]
+    fn test_diff_single_pixel() {
+        let a = solid_image(4, 4, [255, 0, 0, 255]);
+        let mut b = solid_image(4, 4, [255, 0, 0, 255]);
+
+        // Pixel (2, 1)
+        let index = (4 + 2) * 4;
+        b.rgba_data[index..index + 4].copy_from_slice(&[200, 10, 0, 255]);
+
+        let diff = a.diff(&b);
+        assert!(!diff.is_identical());
+        assert_eq!(diff.differing_pixels, 1);
+        assert_eq!(diff.bounding_box, Some((2, 1, 2, 1)));
+        assert_eq!(diff.max_channel_delta, 55);
+    }
+
+    #[test]
+    fn test_diff_dimension_mismatch() {
+        let a = solid_image(4, 4, [255, 0, 0, 255]);
+        let b = solid_image(2, 8, [255, 0, 0, 255]);
+
+        let diff = a.diff(&b);
+        assert_eq!(diff.dimension_mismatch, Some(((4, 4), (2, 8))));
+        assert_eq!(diff.differing_pixels, 0);
+    }
+}
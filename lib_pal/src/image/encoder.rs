@@ -1,8 +1,17 @@
 use log::{debug, error, info};
+use std::io::{self, Write};
 use thiserror::Error;
 
-use super::format::MAGIC_HEADER;
-use crate::compression::{compress, CompressionError};
+use super::format::{
+    FLAG_CHANNEL_PLANES, FLAG_FRAMES, FLAG_FRAME_DELTA, FLAG_HUFFMAN, FLAG_METADATA,
+    FLAG_NO_RLE_DELTA, FLAG_SPLIT_ALPHA, FLAG_TILES, FORMAT_VERSION, MAGIC_HEADER_VERSIONED,
+};
+use crate::compression::huffman::HuffmanTable;
+use crate::compression::palette::{palette_compression, palette_compression_with};
+use crate::compression::tiles::TileOptions;
+use crate::compression::{
+    compress_indices, compress_with_options, CompressionError, CompressionOptions,
+};
 
 #[derive(Error, Debug)]
 pub enum EncodingError {
@@ -10,53 +19,505 @@ pub enum EncodingError {
     CompressionFailed(#[from] CompressionError),
     #[error("Palette size exceeds 256 colors")]
     PaletteTooLarge,
+    #[error("Invalid palette index: {0} exceeds palette size of {1}")]
+    InvalidPaletteIndex(usize, usize),
+    #[error("Metadata entry is too large to encode")]
+    MetadataTooLarge,
+    #[error("Huffman code table is too large to encode")]
+    HuffmanTableTooLarge,
+    #[error("Invalid RGB data length: expected multiple of 3 bytes, got {0}")]
+    InvalidRgbDataLength(usize),
+    #[error("An animation needs at least one frame")]
+    NoFrames,
+    #[error("Frame #{frame} has {found} bytes of RGBA data, expected {expected}")]
+    InvalidFrameLength {
+        frame: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Frame payload is too large to encode")]
+    FrameTooLarge,
+    #[error("Alpha table has {0} entries, expected 1 to 256")]
+    InvalidAlphaTable(usize),
 }
 
-pub fn encode(width: u16, height: u16, rgba_data: &[u8]) -> Result<Vec<u8>, EncodingError> {
+/// How the frames of an animated sprite are stored
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameOptions {
+    /// Store each frame after the first as the XOR of its palette indices
+    /// with the previous frame's. Frames that barely change turn into long
+    /// runs of zeros, which the RLE-delta and LZW stages shrink to a few
+    /// bytes.
+    pub delta: bool,
+}
+
+pub fn encode(width: u32, height: u32, rgba_data: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    encode_with_metadata(width, height, rgba_data, &[])
+}
+
+/// Encodes 3-byte RGB pixels as fully opaque RGBA. Decoding yields RGBA.
+pub fn encode_rgb(width: u32, height: u32, rgb_data: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    if !rgb_data.len().is_multiple_of(3) {
+        return Err(EncodingError::InvalidRgbDataLength(rgb_data.len()));
+    }
+
+    let rgba_data: Vec<u8> = rgb_data
+        .chunks_exact(3)
+        .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+        .collect();
+    encode(width, height, &rgba_data)
+}
+
+/// Encodes single-channel gray levels, e.g. a tile mask.
+///
+/// The palette holds each distinct level once, darkest first, so the indices
+/// go straight to the RLE-delta and LZW stages without RGBA palette
+/// compression. Decoding yields opaque RGBA with `r = g = b = level`.
+pub fn encode_gray(width: u32, height: u32, gray: &[u8]) -> Result<Vec<u8>, EncodingError> {
+    let mut used = [false; 256];
+    for &level in gray {
+        used[level as usize] = true;
+    }
+
+    let mut palette = Vec::new();
+    let mut index_of = [0u8; 256];
+    for level in (0..=255u8).filter(|&level| used[level as usize]) {
+        index_of[level as usize] = palette.len() as u8;
+        palette.push([level, level, level, 255]);
+    }
+
+    let indices: Vec<u8> = gray.iter().map(|&level| index_of[level as usize]).collect();
+    encode_indexed(width, height, &palette, &indices)
+}
+
+/// Encodes an image and writes it to `writer`, e.g. straight into a `File`.
+///
+/// Encoding errors are reported as `io::ErrorKind::InvalidInput`.
+pub fn encode_to<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    rgba_data: &[u8],
+) -> io::Result<()> {
+    let encoded_data = encode(width, height, rgba_data)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    writer.write_all(&encoded_data)
+}
+
+/// Encodes an image along with key/value metadata, which
+/// [`decode_metadata`](super::decode_metadata) reads back. Without metadata
+/// the output is identical to [`encode`].
+pub fn encode_with_metadata(
+    width: u32,
+    height: u32,
+    rgba_data: &[u8],
+    metadata: &[(String, String)],
+) -> Result<Vec<u8>, EncodingError> {
+    encode_with_options(
+        width,
+        height,
+        rgba_data,
+        metadata,
+        CompressionOptions::default(),
+    )
+}
+
+/// Encodes an image with metadata and optional compression stages. The
+/// Huffman code table is stored in the file so `decode` can reverse it.
+pub fn encode_with_options(
+    width: u32,
+    height: u32,
+    rgba_data: &[u8],
+    metadata: &[(String, String)],
+    options: CompressionOptions,
+) -> Result<Vec<u8>, EncodingError> {
     info!("Starting encoding");
 
+    // Compress the image data
+    let compressed_data = compress_with_options(rgba_data, options)?;
+    debug!(
+        "Image data compressed successfully with palette size: {}",
+        compressed_data.palette.len()
+    );
+
+    write_image(
+        width,
+        height,
+        &compressed_data.palette,
+        metadata,
+        Payload {
+            data: &compressed_data.data,
+            huffman: compressed_data.huffman.as_ref(),
+            rle_delta: compressed_data.rle_delta,
+            tiles: options.tiles.filter(|_| compressed_data.tiled),
+            channel_planes: compressed_data.channel_planes,
+            alphas: compressed_data.alphas.as_deref(),
+        },
+        ExtraFrames::NONE,
+    )
+}
+
+/// Encodes already palette-indexed data, skipping palette generation.
+///
+/// The palette is written exactly in the given order, so indices decoded
+/// with `decode_indexed` stay valid.
+pub fn encode_indexed(
+    width: u32,
+    height: u32,
+    palette: &[[u8; 4]],
+    indices: &[u8],
+) -> Result<Vec<u8>, EncodingError> {
+    info!("Starting indexed encoding");
+
+    if let Some(&index) = indices
+        .iter()
+        .find(|&&index| index as usize >= palette.len())
+    {
+        error!(
+            "Palette index {} exceeds palette size of {}",
+            index,
+            palette.len()
+        );
+        return Err(EncodingError::InvalidPaletteIndex(
+            index as usize,
+            palette.len(),
+        ));
+    }
+
+    let compressed_data = compress_indices(indices)?;
+    debug!("Indices compressed successfully");
+
+    write_image(
+        width,
+        height,
+        palette,
+        &[],
+        Payload::indices(&compressed_data),
+        ExtraFrames::NONE,
+    )
+}
+
+/// Encodes the frames of an animated sprite, each `width`×`height` RGBA.
+///
+/// All frames share one palette, written once. The first frame is stored as
+/// the regular payload, so `decode` reads it as a still image, and
+/// [`decode_frames`](super::decode_frames) returns every frame.
+pub fn encode_frames(width: u32, height: u32, frames: &[&[u8]]) -> Result<Vec<u8>, EncodingError> {
+    encode_frames_with_options(width, height, frames, FrameOptions::default())
+}
+
+/// Encodes the frames of an animated sprite like [`encode_frames`], storing
+/// them as `options` asks
+pub fn encode_frames_with_options(
+    width: u32,
+    height: u32,
+    frames: &[&[u8]],
+    options: FrameOptions,
+) -> Result<Vec<u8>, EncodingError> {
+    encode_frames_with_metadata(width, height, frames, &[], options)
+}
+
+/// [`encode_frames_with_options`] with key/value metadata
+pub(crate) fn encode_frames_with_metadata(
+    width: u32,
+    height: u32,
+    frames: &[&[u8]],
+    metadata: &[(String, String)],
+    options: FrameOptions,
+) -> Result<Vec<u8>, EncodingError> {
+    info!("Starting encoding of {} frames", frames.len());
+
+    let expected = width as usize * height as usize * 4;
+    if frames.is_empty() {
+        error!("No frames to encode");
+        return Err(EncodingError::NoFrames);
+    }
+    if let Some((frame, found)) = frames
+        .iter()
+        .map(|frame| frame.len())
+        .enumerate()
+        .find(|&(_, len)| len != expected)
+    {
+        error!(
+            "Frame #{} has {} bytes of RGBA data, expected {}",
+            frame, found, expected
+        );
+        return Err(EncodingError::InvalidFrameLength {
+            frame,
+            expected,
+            found,
+        });
+    }
+
+    let palette = palette_compression(&frames.concat())
+        .map_err(CompressionError::from)?
+        .palette;
+    debug!("Shared palette built with {} colors", palette.len());
+
+    let indices = frames
+        .iter()
+        .map(|frame| palette_compression_with(frame, &palette).map(|result| result.indices))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(CompressionError::from)?;
+
+    // The first frame is always a keyframe
+    let mut payloads = vec![compress_indices(&indices[0])?];
+    for (previous, current) in indices.iter().zip(&indices[1..]) {
+        let payload = if options.delta {
+            let diff: Vec<u8> = previous.iter().zip(current).map(|(a, b)| a ^ b).collect();
+            compress_indices(&diff)?
+        } else {
+            compress_indices(current)?
+        };
+        payloads.push(payload);
+    }
+
+    write_image(
+        width,
+        height,
+        &palette,
+        metadata,
+        Payload::indices(&payloads[0]),
+        ExtraFrames {
+            payloads: &payloads[1..],
+            delta: options.delta,
+        },
+    )
+}
+
+/// Compressed image data and the optional stages it went through
+pub(crate) struct Payload<'a> {
+    pub data: &'a [u8],
+    /// Code table of the Huffman stage, if it ran
+    pub huffman: Option<&'a HuffmanTable>,
+    /// Whether the RLE-delta stage ran
+    pub rle_delta: bool,
+    /// How the indices were split, if tile deduplication ran
+    pub tiles: Option<TileOptions>,
+    /// The data holds channel planes rather than palette indices
+    pub channel_planes: bool,
+    /// Alpha table, if RGB and alpha were indexed separately
+    pub alphas: Option<&'a [u8]>,
+}
+
+impl<'a> Payload<'a> {
+    /// Palette indices through the default RLE-delta and LZW stages, as
+    /// `compress_indices` returns them
+    pub fn indices(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            huffman: None,
+            rle_delta: true,
+            tiles: None,
+            channel_planes: false,
+            alphas: None,
+        }
+    }
+}
+
+/// Compressed animation frames following the first one
+pub(crate) struct ExtraFrames<'a> {
+    pub payloads: &'a [Vec<u8>],
+    /// The payloads hold index diffs against the previous frame
+    pub delta: bool,
+}
+
+impl ExtraFrames<'_> {
+    /// A still image
+    pub const NONE: Self = Self {
+        payloads: &[],
+        delta: false,
+    };
+}
+
+/// Writes the header, palette, optional sections, already compressed payload
+/// and checksum trailer of a `.pxc` file.
+///
+/// `payload` is the first frame of an animation, followed by
+/// `extra_frames`.
+pub(crate) fn write_image(
+    width: u32,
+    height: u32,
+    palette: &[[u8; 4]],
+    metadata: &[(String, String)],
+    payload: Payload,
+    extra_frames: ExtraFrames,
+) -> Result<Vec<u8>, EncodingError> {
+    let Payload {
+        data: compressed_data,
+        huffman,
+        rle_delta,
+        tiles,
+        channel_planes,
+        alphas,
+    } = payload;
+    let ExtraFrames {
+        payloads: extra_frames,
+        delta,
+    } = extra_frames;
     let mut encoded_data: Vec<u8> = Vec::new();
 
+    let mut flags = 0;
+    if !metadata.is_empty() {
+        flags |= FLAG_METADATA;
+    }
+    if huffman.is_some() {
+        flags |= FLAG_HUFFMAN;
+    }
+    if !rle_delta {
+        flags |= FLAG_NO_RLE_DELTA;
+    }
+    if tiles.is_some() {
+        flags |= FLAG_TILES;
+    }
+    if channel_planes {
+        flags |= FLAG_CHANNEL_PLANES;
+    }
+    if alphas.is_some() {
+        flags |= FLAG_SPLIT_ALPHA;
+    }
+    if !extra_frames.is_empty() {
+        flags |= FLAG_FRAMES;
+        if delta {
+            flags |= FLAG_FRAME_DELTA;
+        }
+    }
+
     // Step 1: Write header
-    encoded_data.extend_from_slice(&MAGIC_HEADER); // Magic Number
+    encoded_data.extend_from_slice(&MAGIC_HEADER_VERSIONED); // Magic Number
+    encoded_data.push(FORMAT_VERSION); // Version
     encoded_data.extend_from_slice(&width.to_be_bytes()); // Width
     encoded_data.extend_from_slice(&height.to_be_bytes()); // Height
     debug!(
-        "Header written:\nMagic: {:?}\nWidth: {}\nHeight: {}",
-        MAGIC_HEADER, width, height
+        "Header written:\nMagic: {:?}\nVersion: {}\nWidth: {}\nHeight: {}",
+        MAGIC_HEADER_VERSIONED, FORMAT_VERSION, width, height
     );
 
-    // Step 2: Compress the image data
-    let compressed_data = compress(rgba_data)?;
-    debug!(
-        "Image data compressed successfully with palette size: {}",
-        compressed_data.palette.len()
-    );
-
-    // Check that the palette size does not exceed 256 colors
-    if compressed_data.palette.len() > 256 {
+    // Step 2: Check that the palette size does not exceed 256 colors
+    if palette.len() > 256 {
         error!(
             "Palette size {} exceeds the maximum allowed limit of 256 colors",
-            compressed_data.palette.len()
+            palette.len()
         );
         return Err(EncodingError::PaletteTooLarge);
     }
-    encoded_data.push(compressed_data.palette.len() as u8);
+    // 256 wraps to 0, which the decoder reads back as a full palette. An
+    // image without pixels stores one unused transparent entry instead.
+    let palette: &[[u8; 4]] = if palette.is_empty() {
+        &[[0; 4]]
+    } else {
+        palette
+    };
+    encoded_data.push(palette.len() as u8);
     debug!("Palette size added to encoded data");
 
     // Add palette data (each color is [u8; 4])
-    for color in &compressed_data.palette {
+    for color in palette {
         encoded_data.extend_from_slice(color);
     }
-    debug!(
-        "Palette data written with {} colors",
-        compressed_data.palette.len()
-    );
+    debug!("Palette data written with {} colors", palette.len());
+
+    let checksum_start = encoded_data.len();
+    encoded_data.push(flags);
+    if !metadata.is_empty() {
+        write_metadata(&mut encoded_data, metadata)?;
+        debug!("Metadata written with {} entries", metadata.len());
+    }
+    if let Some(table) = huffman {
+        write_huffman_table(&mut encoded_data, table)?;
+        debug!(
+            "Huffman table written with {} symbols",
+            table.encoding_map.len()
+        );
+    }
+    if let Some(TileOptions { width, tile_size }) = tiles {
+        encoded_data.extend_from_slice(&width.to_be_bytes());
+        encoded_data.push(tile_size);
+        debug!(
+            "Tile section written: width={} tile_size={}",
+            width, tile_size
+        );
+    }
+    if let Some(alphas) = alphas {
+        write_alpha_table(&mut encoded_data, alphas)?;
+        debug!("Alpha section written with {} levels", alphas.len());
+    }
 
-    // Add LZW-compressed indices directly
-    encoded_data.extend_from_slice(&compressed_data.data);
-    debug!("LZW-compressed indices added to encoded data");
+    if extra_frames.is_empty() {
+        // Add LZW-compressed indices directly
+        encoded_data.extend_from_slice(compressed_data);
+        debug!("LZW-compressed indices added to encoded data");
+    } else {
+        // Length-prefix the first frame so the others can follow it
+        write_frame(&mut encoded_data, compressed_data)?;
+        let count = u32::try_from(extra_frames.len()).map_err(|_| EncodingError::FrameTooLarge)?;
+        encoded_data.extend_from_slice(&count.to_be_bytes());
+        for frame in extra_frames {
+            write_frame(&mut encoded_data, frame)?;
+        }
+        debug!("{} frames added to encoded data", extra_frames.len() + 1);
+    }
+
+    // Checksum everything after the palette so corruption is caught before
+    // decompression
+    let checksum = crc32fast::hash(&encoded_data[checksum_start..]);
+    encoded_data.extend_from_slice(&checksum.to_be_bytes());
+    debug!("Checksum written: {:#010x}", checksum);
 
     info!("Encoding process completed successfully");
     Ok(encoded_data)
 }
+
+/// Writes the entry count followed by length-prefixed UTF-8 keys and values
+fn write_metadata(
+    encoded_data: &mut Vec<u8>,
+    metadata: &[(String, String)],
+) -> Result<(), EncodingError> {
+    let to_len = |len: usize| u16::try_from(len).map_err(|_| EncodingError::MetadataTooLarge);
+
+    encoded_data.extend_from_slice(&to_len(metadata.len())?.to_be_bytes());
+    for (key, value) in metadata {
+        for field in [key, value] {
+            encoded_data.extend_from_slice(&to_len(field.len())?.to_be_bytes());
+            encoded_data.extend_from_slice(field.as_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Writes the alpha count, 256 wrapping to 0 like the palette size, followed
+/// by the alpha values
+fn write_alpha_table(encoded_data: &mut Vec<u8>, alphas: &[u8]) -> Result<(), EncodingError> {
+    if alphas.is_empty() || alphas.len() > 256 {
+        return Err(EncodingError::InvalidAlphaTable(alphas.len()));
+    }
+    encoded_data.push(alphas.len() as u8);
+    encoded_data.extend_from_slice(alphas);
+    Ok(())
+}
+
+/// Writes a frame payload preceded by its length
+fn write_frame(encoded_data: &mut Vec<u8>, payload: &[u8]) -> Result<(), EncodingError> {
+    let len = u32::try_from(payload.len()).map_err(|_| EncodingError::FrameTooLarge)?;
+    encoded_data.extend_from_slice(&len.to_be_bytes());
+    encoded_data.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Writes the table with `HuffmanTable::write_to`, after checking its counts
+/// fit the format
+fn write_huffman_table(
+    encoded_data: &mut Vec<u8>,
+    table: &HuffmanTable,
+) -> Result<(), EncodingError> {
+    let too_large = table.encoding_map.len() > u16::MAX as usize
+        || table
+            .encoding_map
+            .values()
+            .any(|code| code.len() > u8::MAX as usize);
+    if too_large {
+        return Err(EncodingError::HuffmanTableTooLarge);
+    }
+    table.write_to(encoded_data);
+    Ok(())
+}
@@ -1,10 +1,74 @@
+use image::error::{ParameterError, ParameterErrorKind};
+use image::{ExtendedColorType, ImageError, ImageFormat, ImageResult};
+use std::fs;
+use std::io;
+use std::path::Path;
+
 pub const MAGIC_HEADER: [u8; 4] = *b"PXC1";
+/// Magic number of files with a flags byte after the palette, announcing the
+/// optional sections that follow it
+pub const MAGIC_HEADER_EXTENDED: [u8; 4] = *b"PXC2";
+/// Magic number of files with a version byte right after it. The rest of the
+/// layout matches `MAGIC_HEADER_EXTENDED` files.
+pub const MAGIC_HEADER_VERSIONED: [u8; 4] = *b"PXCV";
+
+/// Version written by the encoder. Files with the older `MAGIC_HEADER` and
+/// `MAGIC_HEADER_EXTENDED` magic numbers have no version byte and are
+/// reported as version 0. They store LZW codes as fixed 16-bit little-endian
+/// values; every versioned file packs them in variable widths.
+///
+/// Version 2 widened the dimensions from `u16` to `u32`. Version 3 added a
+/// CRC32 trailer covering everything after the palette.
+pub const FORMAT_VERSION: u8 = 3;
+
+// The flags byte after the palette lists the optional sections and the
+// pipeline stages that differ from the default palette, RLE-delta, LZW:
+//
+//   bit 0  FLAG_METADATA        metadata section
+//   bit 1  FLAG_HUFFMAN         Huffman table section, payload Huffman coded
+//   bit 2  FLAG_FRAMES          animation frames, see `FLAG_FRAMES`
+//   bit 3  FLAG_FRAME_DELTA     frames stored as diffs, needs `FLAG_FRAMES`
+//   bit 4  FLAG_NO_RLE_DELTA    RLE-delta stage skipped
+//   bit 5  FLAG_TILES           tile section, payload holds deduplicated tiles
+//   bit 6  FLAG_CHANNEL_PLANES  payload holds channel planes, not indices
+//   bit 7  FLAG_SPLIT_ALPHA     alpha section, RGB and alpha indexed apart
+//
+// Frames run the default pipeline only. Tiles, channel planes and split
+// alpha exclude each other. Other combinations are rejected as an unknown
+// pipeline.
+
+/// A metadata section follows the flags byte
+pub const FLAG_METADATA: u8 = 0b01;
+/// The payload is Huffman coded and its code table precedes it
+pub const FLAG_HUFFMAN: u8 = 0b10;
+/// The payload is the first of several animation frames sharing the palette.
+/// Its length precedes it and the remaining frames follow it.
+pub const FLAG_FRAMES: u8 = 0b100;
+/// Each frame after the first stores its indices XORed with those of the
+/// previous frame. Only valid with `FLAG_FRAMES`.
+pub const FLAG_FRAME_DELTA: u8 = 0b1000;
+/// The RLE-delta stage was skipped, so LZW codes the raw palette indices
+pub const FLAG_NO_RLE_DELTA: u8 = 0b1_0000;
+/// The palette indices went through tile deduplication before the later
+/// stages. A tile section after the Huffman table holds the tile width
+/// (`u32`) and tile size (`u8`), big endian.
+pub const FLAG_TILES: u8 = 0b10_0000;
+/// The payload holds the pixels as delta-coded R, G, B and A planes, see
+/// `compression::planes`. The palette is unused and holds a single entry.
+pub const FLAG_CHANNEL_PLANES: u8 = 0b100_0000;
+/// RGB and alpha were indexed separately, see
+/// `compression::palette::split_alpha_palette_compression`. An alpha section
+/// after the tile section holds the alpha count (0 for 256) and values, the
+/// palette alpha is unused and the payload holds the color indices followed
+/// by the alpha indices.
+pub const FLAG_SPLIT_ALPHA: u8 = 0b1000_0000;
 
 #[derive(Debug)]
 pub struct Image {
     pub magic: [u8; 4],
-    pub width: u16,
-    pub height: u16,
+    pub version: u8,
+    pub width: u32,
+    pub height: u32,
     pub palette_size: u8,
     pub palette: Vec<[u8; 4]>,
     pub rgba_data: Vec<u8>,
@@ -12,18 +76,27 @@ pub struct Image {
 
 impl Image {
     pub const MAGIC_SIZE: usize = 4;
-    pub const WIDTH_HEIGHT_SIZE: usize = std::mem::size_of::<u16>();
+    pub const VERSION_SIZE: usize = std::mem::size_of::<u8>();
+    pub const WIDTH_HEIGHT_SIZE: usize = std::mem::size_of::<u32>();
+    /// Size of each dimension in files before version 2
+    pub const LEGACY_WIDTH_HEIGHT_SIZE: usize = std::mem::size_of::<u16>();
+    /// The palette size byte holds 1 to 255 colors, or 0 for a full
+    /// 256-color palette. An image without pixels stores one unused entry.
     pub const PALETTE_SIZE_SIZE: usize = std::mem::size_of::<u8>();
+    pub const CHECKSUM_SIZE: usize = std::mem::size_of::<u32>();
+    /// Tile width and tile size of the `FLAG_TILES` section
+    pub const TILE_SECTION_SIZE: usize = std::mem::size_of::<u32>() + std::mem::size_of::<u8>();
 
     pub fn new(
-        width: u16,
-        height: u16,
+        width: u32,
+        height: u32,
         palette_size: u8,
         palette: Vec<[u8; 4]>,
         rgba_data: Vec<u8>,
     ) -> Self {
         Self {
-            magic: MAGIC_HEADER,
+            magic: MAGIC_HEADER_VERSIONED,
+            version: FORMAT_VERSION,
             width,
             height,
             palette_size,
@@ -31,4 +104,103 @@ impl Image {
             rgba_data,
         }
     }
+
+    /// RGBA of the pixel at (x, y), or `None` outside the image or past the
+    /// end of a short `rgba_data`
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = (y as usize * self.width as usize + x as usize) * 4;
+        let rgba = self.rgba_data.get(index..index + 4)?;
+        Some([rgba[0], rgba[1], rgba[2], rgba[3]])
+    }
+
+    /// RGBA of every pixel in row-major order
+    pub fn pixels(&self) -> impl Iterator<Item = [u8; 4]> + '_ {
+        self.rgba_data
+            .chunks_exact(4)
+            .map(|rgba| [rgba[0], rgba[1], rgba[2], rgba[3]])
+    }
+
+    /// Whether `rgba_data` holds exactly `width`×`height` pixels
+    fn has_complete_data(&self) -> bool {
+        (self.width as usize)
+            .checked_mul(self.height as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            == Some(self.rgba_data.len())
+    }
+
+    /// Encodes the image with [`encode`](super::encode) and writes it to
+    /// `path`. The palette is rebuilt from the pixels.
+    ///
+    /// A `rgba_data` of the wrong length and encoding errors are reported as
+    /// `io::ErrorKind::InvalidInput`.
+    pub fn save_pxc(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        if !self.has_complete_data() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} bytes of RGBA data do not match a {}x{} image",
+                    self.rgba_data.len(),
+                    self.width,
+                    self.height
+                ),
+            ));
+        }
+
+        let encoded_data = super::encode(self.width, self.height, &self.rgba_data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        fs::write(path, encoded_data)
+    }
+
+    /// Writes the image to `path` as an RGBA PNG, whatever its extension.
+    ///
+    /// A `rgba_data` of the wrong length is reported as a dimension mismatch.
+    pub fn save_png(&self, path: impl AsRef<Path>) -> ImageResult<()> {
+        if !self.has_complete_data() {
+            return Err(ImageError::Parameter(ParameterError::from_kind(
+                ParameterErrorKind::DimensionMismatch,
+            )));
+        }
+
+        image::save_buffer_with_format(
+            path,
+            &self.rgba_data,
+            self.width,
+            self.height,
+            ExtendedColorType::Rgba8,
+            ImageFormat::Png,
+        )
+    }
+}
+
+/// Decoded image kept as palette indices rather than RGBA
+#[derive(Debug)]
+pub struct IndexedImage {
+    pub width: u32,
+    pub height: u32,
+    pub palette: Vec<[u8; 4]>,
+    pub indices: Vec<u8>,
+}
+
+impl IndexedImage {
+    /// Palette index of every pixel in row-major order
+    pub fn indexed_pixels(&self) -> &[u8] {
+        &self.indices
+    }
+
+    /// Expands the indices to row-major RGBA. Indices past the end of the
+    /// palette become transparent.
+    pub fn rgba_data(&self) -> Vec<u8> {
+        self.indices
+            .iter()
+            .flat_map(|&index| {
+                self.palette
+                    .get(index as usize)
+                    .copied()
+                    .unwrap_or_default()
+            })
+            .collect()
+    }
 }
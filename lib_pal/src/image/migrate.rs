@@ -0,0 +1,60 @@
+use log::info;
+use thiserror::Error;
+
+use super::decoder::{decode_header, DecodeError};
+use super::encoder::{
+    encode_frames_with_metadata, encode_with_options, EncodingError, FrameOptions,
+};
+use crate::compression::CompressionOptions;
+
+#[derive(Error, Debug)]
+pub enum ReEncodeError {
+    #[error("Failed to decode file: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("Failed to encode file: {0}")]
+    Encode(#[from] EncodingError),
+}
+
+/// Upgrades an encoded image of any supported version to the current format.
+///
+/// The pixels, animation frames, metadata and optional pipeline stages carry
+/// over unchanged; the header, section layout and checksum are rewritten as
+/// `encode` would.
+pub fn re_encode(old: &[u8]) -> Result<Vec<u8>, ReEncodeError> {
+    let header = decode_header(old)?;
+    info!(
+        "Re-encoding a version {} image to the current format",
+        header.version
+    );
+    // Decode the payload once, a still image being a single frame
+    let frames = header.decode_frames_rgba(old)?;
+
+    if !header.frames.is_empty() {
+        let pixels: Vec<&[u8]> = frames.iter().map(Vec::as_slice).collect();
+        return Ok(encode_frames_with_metadata(
+            header.width,
+            header.height,
+            &pixels,
+            &header.metadata,
+            FrameOptions {
+                delta: header.frame_delta,
+            },
+        )?);
+    }
+
+    let options = CompressionOptions {
+        huffman: header.huffman.is_some(),
+        skip_rle_delta: !header.rle_delta,
+        tiles: header.tiles,
+        channel_planes: header.channel_planes,
+        split_alpha: header.alphas.is_some(),
+        ..Default::default()
+    };
+    Ok(encode_with_options(
+        header.width,
+        header.height,
+        &frames[0],
+        &header.metadata,
+        options,
+    )?)
+}
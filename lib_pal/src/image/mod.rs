@@ -1,6 +1,16 @@
 pub mod decoder;
+pub mod diff;
 pub mod encoder;
 pub mod format;
+pub mod migrate;
 
-pub use decoder::decode;
-pub use encoder::encode;
+pub use decoder::{
+    decode, decode_frames, decode_from, decode_indexed, decode_lenient, decode_metadata,
+    decode_tiles,
+};
+pub use diff::ImageDiff;
+pub use encoder::{
+    encode, encode_frames, encode_frames_with_options, encode_gray, encode_indexed, encode_rgb,
+    encode_to, encode_with_metadata, encode_with_options, FrameOptions,
+};
+pub use migrate::re_encode;
@@ -1,14 +1,39 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod batch;
 pub mod compression;
 pub mod constants;
+#[cfg(feature = "std")]
 pub mod image;
 
+/// `alloc` items that the std prelude would otherwise provide
+#[cfg(not(feature = "std"))]
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+}
+
+#[cfg(feature = "std")]
 use log::*;
+#[cfg(feature = "std")]
 use std::fs::File;
+#[cfg(feature = "std")]
 use std::io::Write;
 
-pub use crate::image::format::Image;
-pub use crate::image::{decode, encode};
+#[cfg(feature = "std")]
+pub use crate::image::format::{Image, IndexedImage};
+#[cfg(feature = "std")]
+pub use crate::image::{
+    decode, decode_frames, decode_from, decode_indexed, decode_lenient, decode_metadata,
+    decode_tiles, encode, encode_frames, encode_frames_with_options, encode_gray, encode_indexed,
+    encode_rgb, encode_to, encode_with_metadata, encode_with_options, re_encode,
+};
 
+#[cfg(feature = "std")]
 pub fn init_logging() {
     let target = Box::new(File::create("log.txt").expect("Can't create file"));
 
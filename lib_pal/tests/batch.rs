@@ -0,0 +1,45 @@
+mod common;
+
+use std::fs;
+
+use common::{GRADIENT, RANDOM_RGB};
+use lib_pxc::batch::{batch_reencode, BatchError};
+use lib_pxc::encode;
+
+#[test]
+fn test_batch_reencode_reports_every_file() {
+    let dir = std::env::temp_dir().join(format!("pxc-batch-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut paths = Vec::new();
+    for (i, (width, height, data)) in [(4, 4, &RANDOM_RGB[..]), (16, 16, &GRADIENT[..])]
+        .into_iter()
+        .cycle()
+        .take(4)
+        .enumerate()
+    {
+        let path = dir.join(format!("sprite_{}.pxc", i));
+        fs::write(&path, encode(width, height, data).unwrap()).unwrap();
+        paths.push(path);
+    }
+
+    let corrupt = dir.join("corrupt.pxc");
+    fs::write(&corrupt, b"not a pxc file").unwrap();
+    paths.insert(2, corrupt);
+
+    let results = batch_reencode(&paths, 3);
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(results.len(), paths.len());
+    for (path, result) in paths.iter().zip(&results) {
+        if path.ends_with("corrupt.pxc") {
+            assert!(matches!(result, Err(BatchError::Decode(_))));
+            continue;
+        }
+
+        let result = result.as_ref().unwrap();
+        assert_eq!(&result.path, path);
+        assert!(result.original_size > 0);
+        assert!(result.reencoded_size > 0);
+    }
+}
@@ -1,3 +1,8 @@
+#![allow(dead_code)]
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+
 pub const GRADIENT: [u8; 1024] = gradient();
 
 pub const RANDOM_RGB: [u8; 4 * 4 * 4] = [
@@ -21,8 +26,8 @@ const fn gradient() -> [u8; 1024] {
     data
 }
 
-pub const REAL_IMAGE_WIDTH: u16 = 33;
-pub const REAL_IMAGE_HEIGHT: u16 = 19;
+pub const REAL_IMAGE_WIDTH: u32 = 33;
+pub const REAL_IMAGE_HEIGHT: u32 = 19;
 pub const REAL_IMAGE_PALETTE_SIZE: usize = 27;
 pub const REAL_IMAGE: [u8; 4096] = [
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -162,3 +167,38 @@ pub const REAL_IMAGE: [u8; 4096] = [
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
 ];
+
+/// A random RGBA image of up to 64x64 pixels, see `random_image`
+#[derive(Debug, Clone)]
+pub struct RandomImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Random images with up to 256 colors, drawn in runs of one color, some
+/// longer than 255 pixels to cross the RLE run limit. The runs repeat until
+/// they cover the image.
+pub fn random_image() -> impl Strategy<Value = RandomImage> {
+    (0..=64u32, 0..=64u32, vec(any::<[u8; 4]>(), 1..=256))
+        .prop_flat_map(|(width, height, palette)| {
+            let run_len = prop_oneof![4 => Just(1), 3 => 1..=16usize, 1 => 200..400usize];
+            let runs = vec((0..palette.len(), run_len), 1..=32);
+            (Just(width), Just(height), Just(palette), runs)
+        })
+        .prop_map(|(width, height, palette, runs)| {
+            let len = (width * height) as usize;
+            let pixels = runs
+                .iter()
+                .cycle()
+                .flat_map(|&(color, run_len)| std::iter::repeat_n(palette[color], run_len))
+                .take(len)
+                .flatten()
+                .collect();
+            RandomImage {
+                width,
+                height,
+                pixels,
+            }
+        })
+}
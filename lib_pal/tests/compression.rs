@@ -1,7 +1,17 @@
 mod common;
 
-use common::{GRADIENT, RANDOM_RGB, REAL_IMAGE};
-use lib_pxc::compression::{compress, decompress};
+use common::{random_image, GRADIENT, RANDOM_RGB, REAL_IMAGE};
+use lib_pxc::compression::palette::{
+    color_histogram, palette_compression, palette_compression_quantized, palette_decompression,
+    sort_by_luminance, to_indexed16, PaletteCompressionError,
+};
+use lib_pxc::compression::rle_delta::rle_delta_compression;
+use lib_pxc::compression::tiles::TileOptions;
+use lib_pxc::compression::{
+    compress, compress_best, compress_with_options, compress_with_stats, decompress,
+    CompressionOptions, CompressionResult, DecompressionError,
+};
+use proptest::prelude::*;
 
 #[test]
 fn test_comp_decomp_rgb() {
@@ -18,10 +28,68 @@ fn test_comp_decomp_rgb() {
     assert_eq!(decompressed, &RANDOM_RGB);
 }
 
+#[test]
+fn test_compression_result_bytes_roundtrip() {
+    for huffman in [false, true] {
+        let options = CompressionOptions {
+            huffman,
+            ..Default::default()
+        };
+        let compressed = compress_with_options(&RANDOM_RGB, options).unwrap();
+
+        let restored = CompressionResult::from_bytes(&compressed.to_bytes()).unwrap();
+        assert_eq!(restored.palette, compressed.palette);
+        assert_eq!(restored.data, compressed.data);
+        assert_eq!(restored.huffman, compressed.huffman);
+        assert_eq!(decompress(restored).unwrap(), RANDOM_RGB);
+    }
+}
+
+#[test]
+fn test_compression_result_from_truncated_bytes() {
+    let compressed = compress(&RANDOM_RGB).unwrap();
+    let bytes = compressed.to_bytes();
+
+    // Cut inside the palette, and right before the method tag
+    let palette_end = 2 + compressed.palette.len() * 4;
+    for len in [0, 1, 5, palette_end] {
+        assert!(matches!(
+            CompressionResult::from_bytes(&bytes[..len]),
+            Err(DecompressionError::InvalidSerializedResult)
+        ));
+    }
+}
+
+#[test]
+fn test_comp_decomp_empty() {
+    let (compressed, stats) = compress_with_stats(&[], CompressionOptions::default()).unwrap();
+    assert!(compressed.palette.is_empty());
+    assert!(compressed.data.is_empty());
+    assert_eq!(stats.input_len, 0);
+
+    assert!(decompress(compressed).unwrap().is_empty());
+}
+
+#[test]
+fn test_comp_decomp_single_color_is_tiny() {
+    // 256x256 pixels of one color
+    let rgba_data = [12, 34, 56, 255].repeat(256 * 256);
+
+    let compressed = compress(&rgba_data).unwrap();
+    assert_eq!(compressed.palette, vec![[12, 34, 56, 255]]);
+    assert!(
+        compressed.data.len() < 64,
+        "{} bytes",
+        compressed.data.len()
+    );
+
+    assert_eq!(decompress(compressed).unwrap(), rgba_data);
+}
+
 #[test]
 fn test_comp_decomp_repeating_color() {
     // Create test image with single color
-    let rgba_data = vec![255, 0, 0, 255].repeat(16); // 4x4 red image
+    let rgba_data = [255, 0, 0, 255].repeat(16); // 4x4 red image
 
     // Compress
     let compressed = compress(&rgba_data).unwrap();
@@ -51,3 +119,333 @@ fn test_comp_decomp_real_image() {
     let decompressed = decompress(compressed).unwrap();
     assert_eq!(decompressed, &REAL_IMAGE);
 }
+
+#[test]
+fn test_comp_decomp_huffman_gradients() {
+    let without = compress(&GRADIENT).unwrap();
+    let with = compress_with_options(
+        &GRADIENT,
+        CompressionOptions {
+            huffman: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+
+    assert!(without.huffman.is_none());
+    assert!(with.huffman.is_some());
+    // Huffman coding never needs more bits than the plain bytes it replaces
+    assert!(with.data.len() <= without.data.len());
+
+    let decompressed = decompress(with).unwrap();
+    assert_eq!(decompressed, &GRADIENT);
+}
+
+#[test]
+fn test_palette_quantized_gradient_beyond_256_colors() {
+    // Extend the 256-level gray gradient with 44 reddish shades
+    let mut data = GRADIENT.to_vec();
+    for i in 0..44u8 {
+        data.extend_from_slice(&[255, i * 5, 0, 255]);
+    }
+    assert!(palette_compression(&data).is_err());
+
+    let compressed = palette_compression_quantized(&data, 256).unwrap();
+    assert!(compressed.palette.len() <= 256);
+    assert_eq!(compressed.indices.len(), 300);
+
+    let decompressed = palette_decompression(&compressed).unwrap();
+    let max_error = data
+        .iter()
+        .zip(&decompressed)
+        .map(|(&a, &b)| a.abs_diff(b))
+        .max()
+        .unwrap();
+    assert!(max_error <= 4, "per-channel error {max_error} too large");
+}
+
+#[test]
+fn test_luminance_sorted_palette_rle_size() {
+    // A swatch row introduces the gray levels out of order, then every row is
+    // a left-to-right ramp through them
+    let mut data = Vec::new();
+    for i in 0..16u8 {
+        let level = (i * 7 % 16) * 16;
+        data.extend_from_slice(&[level, level, level, 255]);
+    }
+    for _ in 1..16 {
+        for level in 0..16u8 {
+            data.extend_from_slice(&[level * 16, level * 16, level * 16, 255]);
+        }
+    }
+
+    let unsorted = palette_compression(&data).unwrap();
+    let unsorted_rle = rle_delta_compression(&unsorted.indices).unwrap();
+
+    let sorted = sort_by_luminance(palette_compression(&data).unwrap());
+    let sorted_rle = rle_delta_compression(&sorted.indices).unwrap();
+
+    assert!(
+        sorted_rle.len() < unsorted_rle.len(),
+        "sorted {} >= unsorted {}",
+        sorted_rle.len(),
+        unsorted_rle.len()
+    );
+
+    // Sorting isn't a win on every image: the delta encoder rewards repeated
+    // deltas rather than small ones, and REAL_IMAGE comes out slightly larger
+    let unsorted = palette_compression(&REAL_IMAGE).unwrap();
+    let unsorted_rle = rle_delta_compression(&unsorted.indices).unwrap();
+    let sorted = sort_by_luminance(palette_compression(&REAL_IMAGE).unwrap());
+    let sorted_rle = rle_delta_compression(&sorted.indices).unwrap();
+    assert_eq!((sorted_rle.len(), unsorted_rle.len()), (321, 315));
+
+    let options = CompressionOptions {
+        sort_palette: true,
+        ..Default::default()
+    };
+    let compressed = compress_with_options(&REAL_IMAGE, options).unwrap();
+    assert_eq!(decompress(compressed).unwrap(), &REAL_IMAGE);
+}
+
+#[test]
+fn test_compression_stats_gradient() {
+    let (result, stats) = compress_with_stats(&GRADIENT, CompressionOptions::default()).unwrap();
+
+    assert_eq!(stats.input_len, GRADIENT.len());
+    assert_eq!(stats.palette_size, 256);
+    // One index per RGBA pixel
+    assert_eq!(stats.palette_indices_len, GRADIENT.len() / 4);
+    // The ramp is a single run of +1 deltas
+    assert!(stats.rle_delta_len < stats.palette_indices_len);
+    assert_eq!(stats.lzw_len, result.data.len());
+    assert_eq!(stats.huffman_len, None);
+
+    let huffman = CompressionOptions {
+        huffman: true,
+        ..Default::default()
+    };
+    let (result, stats) = compress_with_stats(&REAL_IMAGE, huffman).unwrap();
+    assert_eq!(stats.huffman_len, Some(result.data.len()));
+    assert!(stats.lzw_len < stats.rle_delta_len);
+}
+
+#[test]
+fn test_compress_best_never_larger() {
+    for data in [&RANDOM_RGB[..], &GRADIENT[..], &REAL_IMAGE[..]] {
+        let plain = compress(data).unwrap();
+        let best = compress_best(data).unwrap();
+        assert!(best.to_bytes().len() <= plain.to_bytes().len());
+
+        // The method tag survives serialization and picks the inverse
+        let restored = CompressionResult::from_bytes(&best.to_bytes()).unwrap();
+        assert_eq!(restored.method(), best.method());
+        assert_eq!(decompress(restored).unwrap(), data);
+    }
+}
+
+#[test]
+fn test_compression_result_unknown_method() {
+    let mut bytes = compress(&GRADIENT).unwrap().to_bytes();
+    let palette_len = u16::from_be_bytes([bytes[0], bytes[1]]) as usize;
+    bytes[2 + palette_len * 4] = 0b10_0000;
+    assert!(matches!(
+        CompressionResult::from_bytes(&bytes),
+        Err(DecompressionError::InvalidSerializedResult)
+    ));
+}
+
+#[test]
+fn test_channel_planes_beat_palette_on_gradient() {
+    let palette = compress(&GRADIENT).unwrap();
+    let options = CompressionOptions {
+        channel_planes: true,
+        ..Default::default()
+    };
+    let (planes, stats) = compress_with_stats(&GRADIENT, options).unwrap();
+    assert_eq!(stats.palette_size, 0);
+    assert_eq!(stats.palette_indices_len, GRADIENT.len());
+
+    // The 256 gradient colors cost 1 KiB of palette, the planes are a few
+    // runs of constant deltas
+    let (planes_len, palette_len) = (planes.to_bytes().len(), palette.to_bytes().len());
+    assert!(
+        planes_len * 4 < palette_len,
+        "planes {planes_len} vs palette {palette_len}"
+    );
+
+    let restored = CompressionResult::from_bytes(&planes.to_bytes()).unwrap();
+    assert!(restored.channel_planes);
+    assert!(restored.palette.is_empty());
+    assert_eq!(decompress(restored).unwrap(), &GRADIENT);
+
+    // Every other stage combination still roundtrips
+    for data in [&RANDOM_RGB[..], &REAL_IMAGE[..], &[]] {
+        for (skip_rle_delta, huffman) in [(false, true), (true, false), (true, true)] {
+            let options = CompressionOptions {
+                channel_planes: true,
+                skip_rle_delta,
+                huffman,
+                ..Default::default()
+            };
+            let compressed = compress_with_options(data, options).unwrap();
+            assert_eq!(decompress(compressed).unwrap(), data);
+        }
+    }
+}
+
+#[test]
+fn test_split_alpha_shares_palette_slots() {
+    // Two colors, each at 16 alpha levels like anti-aliased edges
+    let pixels: Vec<u8> = (0..256)
+        .flat_map(|i| {
+            let [r, g, b] = if i % 32 < 16 {
+                [250, 75, 98]
+            } else {
+                [20, 40, 60]
+            };
+            [r, g, b, (i % 16) as u8 * 17]
+        })
+        .collect();
+    assert_eq!(compress(&pixels).unwrap().palette.len(), 32);
+
+    let options = CompressionOptions {
+        split_alpha: true,
+        ..Default::default()
+    };
+    let (split, stats) = compress_with_stats(&pixels, options).unwrap();
+    assert_eq!(stats.palette_size, 2);
+    assert_eq!(stats.palette_indices_len, 2 * 256);
+
+    let restored = CompressionResult::from_bytes(&split.to_bytes()).unwrap();
+    assert_eq!(restored.palette.len(), 2);
+    assert_eq!(restored.alphas.as_ref().map(Vec::len), Some(16));
+    assert_eq!(decompress(restored).unwrap(), pixels);
+
+    for (skip_rle_delta, huffman) in [(false, true), (true, false), (true, true)] {
+        let options = CompressionOptions {
+            split_alpha: true,
+            skip_rle_delta,
+            huffman,
+            ..Default::default()
+        };
+        let compressed = compress_with_options(&pixels, options).unwrap();
+        let restored = CompressionResult::from_bytes(&compressed.to_bytes()).unwrap();
+        assert_eq!(decompress(restored).unwrap(), pixels);
+    }
+}
+
+#[test]
+fn test_tiles_shrink_repeated_tilemap() {
+    // A noisy 32x32 tile of four colors, repeated 2x2 into a 64x64 image
+    let colors = [
+        [0, 0, 0, 255],
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+    ];
+    let mut state = 0x2545_f491u32;
+    let tile: Vec<[u8; 4]> = (0..32 * 32)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            colors[(state >> 16) as usize % colors.len()]
+        })
+        .collect();
+    let data: Vec<u8> = (0..64 * 64)
+        .flat_map(|i| tile[(i / 64 % 32) * 32 + i % 32])
+        .collect();
+
+    let plain = compress(&data).unwrap();
+    let options = CompressionOptions {
+        tiles: Some(TileOptions {
+            width: 64,
+            tile_size: 32,
+        }),
+        ..Default::default()
+    };
+    let (tiled, stats) = compress_with_stats(&data, options).unwrap();
+    // One tile in the dictionary and a four-entry map
+    assert_eq!(stats.tiles_len, Some(13 + 32 * 32 + 4));
+    assert!(
+        tiled.data.len() * 2 < plain.data.len(),
+        "tiled {} vs plain {}",
+        tiled.data.len(),
+        plain.data.len()
+    );
+
+    let restored = CompressionResult::from_bytes(&tiled.to_bytes()).unwrap();
+    assert!(restored.tiled);
+    assert_eq!(decompress(restored).unwrap(), data);
+}
+
+#[test]
+fn test_color_histogram_rgb() {
+    let histogram = color_histogram(&RANDOM_RGB).unwrap();
+
+    let total: usize = histogram.iter().map(|&(_, count)| count).sum();
+    assert_eq!(total, RANDOM_RGB.len() / 4);
+    assert_eq!(
+        histogram.len(),
+        palette_compression(&RANDOM_RGB).unwrap().palette.len()
+    );
+    // Most frequent first
+    assert!(histogram.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    // Red covers 6 of the 16 pixels, green and blue 5 each
+    assert_eq!(histogram[0], ([255, 0, 0, 255], 6));
+
+    // No 256-color limit, unlike the palette stage
+    let many: Vec<u8> = (0..300u32)
+        .flat_map(|i| [i as u8, (i >> 8) as u8, 0, 255])
+        .collect();
+    assert_eq!(color_histogram(&many).unwrap().len(), 300);
+    assert!(color_histogram(&RANDOM_RGB[..5]).is_err());
+}
+
+#[test]
+fn test_to_indexed16_wide_palette() {
+    // 300 unique colors, each used twice
+    let colors: Vec<[u8; 4]> = (0..300u32)
+        .map(|i| [i as u8, (i >> 8) as u8, 7, 255])
+        .collect();
+    let pixels: Vec<u8> = colors.iter().chain(&colors).flatten().copied().collect();
+
+    let (palette, indices) = to_indexed16(&pixels).unwrap();
+    assert_eq!(palette, colors);
+    assert_eq!(indices.len(), 600);
+    assert_eq!(indices[299], 299);
+    assert_eq!(indices[300..], indices[..300]);
+
+    // The byte-indexed path still stops at 256
+    assert!(matches!(
+        palette_compression(&pixels),
+        Err(PaletteCompressionError::PaletteOverflow(257))
+    ));
+
+    let (palette, indices) = to_indexed16(&RANDOM_RGB).unwrap();
+    let narrow = palette_compression(&RANDOM_RGB).unwrap();
+    assert_eq!(palette, narrow.palette);
+    assert!(indices
+        .iter()
+        .zip(&narrow.indices)
+        .all(|(&wide, &index)| wide == index as u16));
+    assert!(to_indexed16(&RANDOM_RGB[..3]).is_err());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(200))]
+
+    #[test]
+    fn test_random_images_roundtrip(image in random_image()) {
+        for (name, result) in [
+            ("compress", compress(&image.pixels)),
+            ("compress_best", compress_best(&image.pixels)),
+        ] {
+            let compressed =
+                result.map_err(|err| TestCaseError::fail(format!("{name}: {err:?}")))?;
+            let restored = CompressionResult::from_bytes(&compressed.to_bytes())
+                .and_then(decompress)
+                .map_err(|err| TestCaseError::fail(format!("{name}: {err:?}")))?;
+            prop_assert_eq!(&restored, &image.pixels, "{}", name);
+        }
+    }
+}
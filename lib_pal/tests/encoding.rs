@@ -1,9 +1,84 @@
 mod common;
 
 use common::{
-    GRADIENT, RANDOM_RGB, REAL_IMAGE, REAL_IMAGE_HEIGHT, REAL_IMAGE_PALETTE_SIZE, REAL_IMAGE_WIDTH,
+    random_image, GRADIENT, RANDOM_RGB, REAL_IMAGE, REAL_IMAGE_HEIGHT, REAL_IMAGE_PALETTE_SIZE,
+    REAL_IMAGE_WIDTH,
 };
-use lib_pxc::{decode, encode};
+use lib_pxc::compression::tiles::TileOptions;
+use lib_pxc::compression::CompressionOptions;
+use lib_pxc::image::decoder::DecodeError;
+use lib_pxc::image::encoder::EncodingError;
+use lib_pxc::image::format::{
+    FLAG_CHANNEL_PLANES, FLAG_FRAMES, FLAG_FRAME_DELTA, FLAG_HUFFMAN, FLAG_METADATA,
+    FLAG_NO_RLE_DELTA, FLAG_SPLIT_ALPHA, FLAG_TILES, FORMAT_VERSION,
+};
+use lib_pxc::image::FrameOptions;
+use lib_pxc::Image;
+use lib_pxc::{
+    decode, decode_frames, decode_from, decode_indexed, decode_lenient, decode_metadata,
+    decode_tiles, encode, encode_frames, encode_frames_with_options, encode_gray, encode_indexed,
+    encode_rgb, encode_to, encode_with_metadata, encode_with_options, re_encode,
+};
+use proptest::prelude::*;
+use std::io::Cursor;
+
+/// Appends a valid checksum trailer to a hand-edited file body, so tests can
+/// reach the checks that run after it
+fn seal(body: &[u8], palette_end: usize) -> Vec<u8> {
+    let mut sealed = body.to_vec();
+    sealed.extend_from_slice(&crc32fast::hash(&body[palette_end..]).to_be_bytes());
+    sealed
+}
+
+#[test]
+fn test_encode_decode_empty_image() {
+    let encoded = encode(0, 0, &[]).unwrap();
+
+    let decoded = decode(&encoded).unwrap();
+    assert_eq!((decoded.width, decoded.height), (0, 0));
+    assert!(decoded.rgba_data.is_empty());
+}
+
+#[test]
+fn test_encode_rgb_decodes_opaque() {
+    #[rustfmt::skip]
+    let rgb = [
+        255, 0, 0,   0, 255, 0,
+        0, 0, 255,   10, 20, 30,
+    ];
+
+    let decoded = decode(&encode_rgb(2, 2, &rgb).unwrap()).unwrap();
+    #[rustfmt::skip]
+    let expected = [
+        255, 0, 0, 255,   0, 255, 0, 255,
+        0, 0, 255, 255,   10, 20, 30, 255,
+    ];
+    assert_eq!(decoded.rgba_data, expected);
+
+    assert!(matches!(
+        encode_rgb(2, 2, &rgb[..11]),
+        Err(EncodingError::InvalidRgbDataLength(11))
+    ));
+}
+
+#[test]
+fn test_encode_gray_ramp() {
+    // 16 levels, each repeated across a 16x4 ramp
+    let gray: Vec<u8> = (0..64).map(|i| (i % 16) as u8 * 17).collect();
+
+    let encoded = encode_gray(16, 4, &gray).unwrap();
+    let indexed = decode_indexed(&encoded).unwrap();
+    assert_eq!(indexed.palette.len(), 16);
+    assert_eq!(indexed.palette[0], [0, 0, 0, 255]);
+    assert_eq!(indexed.palette[15], [255, 255, 255, 255]);
+
+    let decoded = decode(&encoded).unwrap();
+    let expected: Vec<u8> = gray.iter().flat_map(|&l| [l, l, l, 255]).collect();
+    assert_eq!(decoded.rgba_data, expected);
+
+    let empty = decode(&encode_gray(0, 0, &[]).unwrap()).unwrap();
+    assert!(empty.rgba_data.is_empty());
+}
 
 #[test]
 fn test_encode_decode_rgb() {
@@ -18,6 +93,32 @@ fn test_encode_decode_rgb() {
     assert_eq!(decoded.palette.len(), 3);
 }
 
+#[test]
+fn test_decoded_image_get_pixel() {
+    let decoded = decode(&encode(4, 4, &RANDOM_RGB).unwrap()).unwrap();
+
+    for y in 0..4 {
+        for x in 0..4 {
+            let index = (y * 4 + x) as usize * 4;
+            assert_eq!(
+                decoded.get_pixel(x, y),
+                Some([
+                    RANDOM_RGB[index],
+                    RANDOM_RGB[index + 1],
+                    RANDOM_RGB[index + 2],
+                    RANDOM_RGB[index + 3]
+                ])
+            );
+        }
+    }
+    assert_eq!(decoded.get_pixel(4, 0), None);
+    assert_eq!(decoded.get_pixel(0, 4), None);
+
+    let pixels: Vec<[u8; 4]> = decoded.pixels().collect();
+    assert_eq!(pixels.len(), 16);
+    assert_eq!(pixels.concat(), RANDOM_RGB);
+}
+
 #[test]
 fn test_encode_decode_real_image() {
     let encoded = encode(REAL_IMAGE_WIDTH, REAL_IMAGE_HEIGHT, &REAL_IMAGE).unwrap();
@@ -33,10 +134,10 @@ fn test_encode_decode_real_image() {
 
 #[test]
 fn test_encode_decode_repeating_color() {
-    const WIDTH: u16 = 4;
-    const HEIGHT: u16 = 4;
+    const WIDTH: u32 = 4;
+    const HEIGHT: u32 = 4;
 
-    let data = vec![255, 0, 0, 255].repeat((WIDTH * HEIGHT).try_into().unwrap()); // 4x4 red image
+    let data = [255, 0, 0, 255].repeat((WIDTH * HEIGHT) as usize); // 4x4 red image
 
     let encoded = encode(4, 4, &data).unwrap();
 
@@ -51,8 +152,8 @@ fn test_encode_decode_repeating_color() {
 
 #[test]
 fn test_encode_decode_gradients() {
-    const WIDTH: u16 = 16;
-    const HEIGHT: u16 = 16;
+    const WIDTH: u32 = 16;
+    const HEIGHT: u32 = 16;
 
     let encoded = encode(WIDTH, HEIGHT, &GRADIENT);
 
@@ -79,3 +180,757 @@ fn test_encode_decode_gradients() {
     assert_eq!(decoded.height, HEIGHT);
     assert_eq!(decoded.palette.len(), 256);
 }
+
+#[test]
+fn test_encode_indexed_preserves_palette_order() {
+    let encoded = encode(REAL_IMAGE_WIDTH, REAL_IMAGE_HEIGHT, &REAL_IMAGE).unwrap();
+    let indexed = decode_indexed(&encoded).unwrap();
+
+    // Reverse the palette and remap indices so the order differs from what
+    // palette compression would produce on its own
+    let last = indexed.palette.len() - 1;
+    let palette: Vec<[u8; 4]> = indexed.palette.iter().rev().copied().collect();
+    let indices: Vec<u8> = indexed
+        .indices
+        .iter()
+        .map(|&index| (last - index as usize) as u8)
+        .collect();
+
+    let reencoded = encode_indexed(indexed.width, indexed.height, &palette, &indices).unwrap();
+    let reindexed = decode_indexed(&reencoded).unwrap();
+    assert_eq!(reindexed.palette, palette);
+    assert_eq!(reindexed.indices, indices);
+
+    let decoded = decode(&reencoded).unwrap();
+    assert_eq!(decoded.rgba_data, &REAL_IMAGE);
+}
+
+#[test]
+fn test_decode_indexed_pixels() {
+    let encoded = encode(4, 4, &RANDOM_RGB).unwrap();
+    let indexed = decode_indexed(&encoded).unwrap();
+
+    let pixels = indexed.indexed_pixels();
+    assert_eq!(pixels.len(), (indexed.width * indexed.height) as usize);
+    assert!(pixels
+        .iter()
+        .all(|&index| (index as usize) < indexed.palette.len()));
+
+    assert_eq!(indexed.rgba_data(), RANDOM_RGB);
+}
+
+#[test]
+fn test_encode_indexed_invalid_index() {
+    let result = encode_indexed(2, 1, &[[255, 0, 0, 255]], &[0, 1]);
+    assert!(matches!(
+        result,
+        Err(EncodingError::InvalidPaletteIndex(1, 1))
+    ));
+}
+
+#[test]
+fn test_decode_tiles_quadrants() {
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const GREEN: [u8; 4] = [0, 255, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+    let mut data = Vec::new();
+    for y in 0..4 {
+        for x in 0..4 {
+            let color = match (x < 2, y < 2) {
+                (true, true) => RED,
+                (false, true) => GREEN,
+                (true, false) => BLUE,
+                (false, false) => WHITE,
+            };
+            data.extend_from_slice(&color);
+        }
+    }
+
+    let encoded = encode(4, 4, &data).unwrap();
+    let (width, height, tiles) = decode_tiles(&encoded, 2).unwrap();
+
+    assert_eq!((width, height), (4, 4));
+    assert_eq!(tiles.len(), 4);
+    for (tile, color) in tiles.iter().zip([RED, GREEN, BLUE, WHITE]) {
+        assert_eq!(tile, &color.repeat(4));
+    }
+}
+
+#[test]
+fn test_decode_tiles_pads_edges() {
+    let data = [255, 0, 0, 255].repeat(9); // 3x3 red image
+    let encoded = encode(3, 3, &data).unwrap();
+
+    let (width, height, tiles) = decode_tiles(&encoded, 2).unwrap();
+    assert_eq!((width, height), (3, 3));
+    assert_eq!(tiles.len(), 4);
+
+    // The bottom-right tile only holds a single pixel of the image
+    let mut expected = vec![0; 16];
+    expected[..4].copy_from_slice(&[255, 0, 0, 255]);
+    assert_eq!(tiles[3], expected);
+
+    assert!(matches!(
+        decode_tiles(&encoded, 0),
+        Err(DecodeError::InvalidTileSize)
+    ));
+    // Too large to allocate, rather than overflowing the tile length
+    assert!(matches!(
+        decode_tiles(&encoded, u32::MAX),
+        Err(DecodeError::InvalidTileSize)
+    ));
+}
+
+#[test]
+fn test_palette_size_zero_means_256() {
+    // A file cut right after a size byte of 0 is missing its 256 colors,
+    // it is not an empty palette
+    const PALETTE_SIZE_OFFSET: usize = 13;
+    let mut encoded = encode(1, 1, &[1, 2, 3, 255]).unwrap();
+    encoded.truncate(PALETTE_SIZE_OFFSET + 1);
+    encoded[PALETTE_SIZE_OFFSET] = 0;
+    assert!(matches!(
+        decode(&encoded),
+        Err(DecodeError::UnexpectedEofPaletteColor(0))
+    ));
+}
+
+#[test]
+fn test_decode_understated_palette_size() {
+    let palette = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]];
+    let mut encoded = encode_indexed(2, 2, &palette, &[0, 1, 2, 2]).unwrap();
+
+    // Claim only two palette colors and drop the third from the file
+    const PALETTE_SIZE_OFFSET: usize = 13;
+    encoded[PALETTE_SIZE_OFFSET] = 2;
+    let third_color = PALETTE_SIZE_OFFSET + 1 + 2 * 4;
+    encoded.drain(third_color..third_color + 4);
+
+    let expected = |result| {
+        matches!(
+            result,
+            Err(DecodeError::PaletteSizeInconsistent {
+                declared: 2,
+                max_index: 2
+            })
+        )
+    };
+    assert!(expected(decode(&encoded).map(|_| ())));
+    assert!(expected(decode_indexed(&encoded).map(|_| ())));
+}
+
+#[test]
+fn test_encode_decode_metadata() {
+    let metadata = vec![
+        ("author".to_string(), "pallight".to_string()),
+        ("empty".to_string(), String::new()),
+    ];
+    let encoded = encode_with_metadata(4, 4, &RANDOM_RGB, &metadata).unwrap();
+
+    assert_eq!(decode_metadata(&encoded).unwrap(), metadata);
+    let decoded = decode(&encoded).unwrap();
+    assert_eq!(decoded.rgba_data, &RANDOM_RGB);
+
+    // Without metadata the output matches `encode`
+    let plain = encode(4, 4, &RANDOM_RGB).unwrap();
+    assert_eq!(encode_with_metadata(4, 4, &RANDOM_RGB, &[]).unwrap(), plain);
+    assert!(decode_metadata(&plain).unwrap().is_empty());
+}
+
+#[test]
+fn test_decode_truncated_metadata() {
+    let metadata = vec![("key".to_string(), "value".to_string())];
+    let encoded = encode_with_metadata(4, 4, &RANDOM_RGB, &metadata).unwrap();
+
+    // Cut the file inside the metadata value
+    let header_and_palette = 4 + 1 + 4 + 4 + 1 + 3 * 4;
+    let truncated = seal(
+        &encoded[..header_and_palette + 2 + 2 + 3 + 2 + 2],
+        header_and_palette,
+    );
+    assert!(matches!(
+        decode_metadata(&truncated),
+        Err(DecodeError::InvalidMetadata)
+    ));
+}
+
+#[test]
+fn test_encode_decode_huffman() {
+    let huffman = CompressionOptions {
+        huffman: true,
+        ..Default::default()
+    };
+    let encoded = encode_with_options(
+        REAL_IMAGE_WIDTH,
+        REAL_IMAGE_HEIGHT,
+        &REAL_IMAGE,
+        &[],
+        huffman,
+    )
+    .unwrap();
+
+    // Decode from a fresh buffer so nothing but the bytes carries the table
+    let bytes = encoded.clone();
+    let decoded = decode(&bytes).unwrap();
+    assert_eq!(decoded.rgba_data, &REAL_IMAGE);
+
+    let metadata = vec![("key".to_string(), "value".to_string())];
+    let encoded = encode_with_options(4, 4, &RANDOM_RGB, &metadata, huffman).unwrap();
+    assert_eq!(decode_metadata(&encoded).unwrap(), metadata);
+    assert_eq!(decode(&encoded).unwrap().rgba_data, &RANDOM_RGB);
+}
+
+#[test]
+fn test_decode_invalid_huffman_table() {
+    let huffman = CompressionOptions {
+        huffman: true,
+        ..Default::default()
+    };
+    let encoded = encode_with_options(4, 4, &RANDOM_RGB, &[], huffman).unwrap();
+
+    // magic, version, dimensions, palette size, 3 colors
+    let palette_end = 4 + 1 + 4 + 4 + 1 + 3 * 4;
+    // flags, padding, symbol count
+    let first_symbol = palette_end + 1 + 1 + 2;
+    let body = &encoded[..encoded.len() - 4];
+
+    let mut zero_length = body.to_vec();
+    zero_length[first_symbol + 1] = 0;
+    assert!(matches!(
+        decode(&seal(&zero_length, palette_end)),
+        Err(DecodeError::InvalidHuffmanTable)
+    ));
+
+    let truncated = seal(&body[..first_symbol + 1], palette_end);
+    assert!(matches!(
+        decode(&truncated),
+        Err(DecodeError::InvalidHuffmanTable)
+    ));
+
+    // Three 1-bit codes can't all be distinct
+    let mut overfull = body[..first_symbol - 2].to_vec();
+    overfull.extend_from_slice(&[0, 3, 0, 1, 1, 1, 2, 1]);
+    assert!(matches!(
+        decode(&seal(&overfull, palette_end)),
+        Err(DecodeError::InvalidHuffmanTable)
+    ));
+}
+
+/// `REAL_IMAGE` at 32x32 as written by the encoder from before the version
+/// byte: `PXC1` magic, u16 dimensions and fixed 16-bit LZW codes
+const LEGACY_REAL_IMAGE: &[u8] = include_bytes!("fixtures/legacy_pxc1.pxc");
+
+#[test]
+fn test_decode_version() {
+    let encoded = encode(4, 4, &RANDOM_RGB).unwrap();
+    let decoded = decode(&encoded).unwrap();
+    assert_eq!(decoded.version, FORMAT_VERSION);
+    assert_eq!(decoded.rgba_data, &RANDOM_RGB);
+
+    // Files from before the version byte decode as version 0
+    let decoded = decode(LEGACY_REAL_IMAGE).unwrap();
+    assert_eq!(decoded.version, 0);
+    assert_eq!((decoded.width, decoded.height), (32, 32));
+    assert_eq!(decoded.rgba_data, &REAL_IMAGE);
+
+    // Even when damaged
+    let truncated = &LEGACY_REAL_IMAGE[..LEGACY_REAL_IMAGE.len() - 1];
+    assert!(decode(truncated).is_err());
+    let decoded = decode_lenient(truncated).unwrap();
+    assert_eq!(decoded.version, 0);
+    assert!(decoded
+        .rgba_data
+        .starts_with(&REAL_IMAGE[..REAL_IMAGE.len() / 2]));
+}
+
+#[test]
+fn test_re_encode_upgrades_legacy_file() {
+    let upgraded = re_encode(LEGACY_REAL_IMAGE).unwrap();
+    assert_eq!(upgraded, encode(32, 32, &REAL_IMAGE).unwrap());
+
+    let decoded = decode(&upgraded).unwrap();
+    assert_eq!(decoded.version, FORMAT_VERSION);
+    assert_eq!((decoded.width, decoded.height), (32, 32));
+    assert_eq!(decoded.rgba_data, &REAL_IMAGE);
+}
+
+#[test]
+fn test_re_encode_keeps_metadata_and_huffman() {
+    let metadata = vec![("author".to_string(), "pal".to_string())];
+    let options = CompressionOptions {
+        huffman: true,
+        ..Default::default()
+    };
+    let encoded = encode_with_options(4, 4, &RANDOM_RGB, &metadata, options).unwrap();
+
+    let reencoded = re_encode(&encoded).unwrap();
+    assert_eq!(decode_metadata(&reencoded).unwrap(), metadata);
+    assert_eq!(decode(&reencoded).unwrap().rgba_data, &RANDOM_RGB);
+    // Tied frequencies can pick different Huffman codes, but every optimal
+    // code has the same length, so only dropping the stage changes the size
+    assert_eq!(reencoded.len(), encoded.len());
+
+    assert!(re_encode(b"not an image").is_err());
+}
+
+#[test]
+fn test_decode_unsupported_version() {
+    const VERSION_OFFSET: usize = 4;
+
+    for version in [0, FORMAT_VERSION + 1] {
+        let mut encoded = encode(4, 4, &RANDOM_RGB).unwrap();
+        encoded[VERSION_OFFSET] = version;
+        assert!(matches!(
+            decode(&encoded),
+            Err(DecodeError::UnsupportedVersion(v)) if v == version
+        ));
+    }
+}
+
+#[test]
+fn test_encode_decode_wide_image() {
+    // Wider than a u16 can hold; a single color keeps the payload tiny
+    const WIDTH: u32 = 70_000;
+    let data = [0, 128, 255, 255].repeat(WIDTH as usize);
+
+    let encoded = encode(WIDTH, 1, &data).unwrap();
+    let decoded = decode(&encoded).unwrap();
+    assert_eq!((decoded.width, decoded.height), (WIDTH, 1));
+    assert_eq!(decoded.rgba_data, data);
+}
+
+#[test]
+fn test_decode_checksum_mismatch() {
+    let encoded = encode(REAL_IMAGE_WIDTH, REAL_IMAGE_HEIGHT, &REAL_IMAGE).unwrap();
+    assert_eq!(decode(&encoded).unwrap().rgba_data, &REAL_IMAGE);
+
+    // Flip a bit in the payload, just before the checksum trailer
+    let mut corrupted = encoded.clone();
+    let payload_byte = corrupted.len() - 4 - 8;
+    corrupted[payload_byte] ^= 0x10;
+    assert!(matches!(
+        decode(&corrupted),
+        Err(DecodeError::ChecksumMismatch { expected, found }) if expected != found
+    ));
+}
+
+#[test]
+fn test_decode_lenient_truncated_payload() {
+    // 32x32 pixels of 16 noisy colors, so the payload is long enough to cut
+    let mut state = 0x1234_5678u32;
+    let data: Vec<u8> = (0..32 * 32)
+        .flat_map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let shade = (state >> 16) as u8 % 16 * 16;
+            [shade, 255 - shade, shade / 2, 255]
+        })
+        .collect();
+    let encoded = encode(32, 32, &data).unwrap();
+    assert_eq!(decode_lenient(&encoded).unwrap().rgba_data, data);
+
+    // Magic, version, dimensions, palette size, 16 colors and the flags
+    let payload_start = 4 + 1 + 8 + 1 + 16 * 4 + 1;
+    let payload_len = encoded.len() - 4 - payload_start;
+    let truncated = &encoded[..payload_start + payload_len / 2];
+    assert!(decode(truncated).is_err());
+
+    let image = decode_lenient(truncated).unwrap();
+    assert_eq!((image.width, image.height), (32, 32));
+    assert_eq!(image.rgba_data.len(), data.len());
+    // A prefix of the pixels survives and the rest is transparent
+    let recovered = image
+        .rgba_data
+        .chunks_exact(4)
+        .zip(data.chunks_exact(4))
+        .take_while(|(decoded, original)| decoded == original)
+        .count();
+    assert!(recovered > 0 && recovered < 32 * 32, "{recovered} pixels");
+    assert!(image.rgba_data[recovered * 4..]
+        .iter()
+        .all(|&byte| byte == 0));
+
+    // The palette itself must be intact
+    assert!(matches!(
+        decode_lenient(&encoded[..20]),
+        Err(DecodeError::UnexpectedEofPaletteColor(1))
+    ));
+}
+
+#[test]
+fn test_decode_lenient_rejects_oversized_dimensions() {
+    let encoded = encode(4, 4, &RANDOM_RGB).unwrap();
+    let mut truncated = encoded[..encoded.len() - 6].to_vec();
+    // Width and height of u32::MAX, whose RGBA length overflows
+    truncated[5..13].fill(0xFF);
+    assert!(matches!(
+        decode_lenient(&truncated),
+        Err(DecodeError::DimensionParsingFailed)
+    ));
+
+    // Still far more pixels than a few bytes of payload could fill
+    truncated[5..13].copy_from_slice(&[0, 0, 0x40, 0, 0, 0, 0x40, 0]);
+    assert!(matches!(
+        decode_lenient(&truncated),
+        Err(DecodeError::DimensionParsingFailed)
+    ));
+}
+
+#[test]
+fn test_encode_to_decode_from_cursor() {
+    let mut cursor = Cursor::new(Vec::new());
+    encode_to(
+        &mut cursor,
+        REAL_IMAGE_WIDTH,
+        REAL_IMAGE_HEIGHT,
+        &REAL_IMAGE,
+    )
+    .unwrap();
+    assert_eq!(
+        cursor.get_ref(),
+        &encode(REAL_IMAGE_WIDTH, REAL_IMAGE_HEIGHT, &REAL_IMAGE).unwrap()
+    );
+
+    cursor.set_position(0);
+    let decoded = decode_from(&mut cursor).unwrap();
+    assert_eq!(
+        (decoded.width, decoded.height),
+        (REAL_IMAGE_WIDTH, REAL_IMAGE_HEIGHT)
+    );
+    assert_eq!(decoded.rgba_data, &REAL_IMAGE);
+
+    for bad in [&b"PX"[..], &b"PNG\x0d rest of a png file"[..]] {
+        assert!(matches!(
+            decode_from(&mut Cursor::new(bad)),
+            Err(DecodeError::InvalidHeader)
+        ));
+    }
+}
+
+/// 4x4 sprite with a pixel walking along the diagonal on a transparent
+/// background
+fn walking_sprite(step: usize) -> Vec<u8> {
+    (0..16)
+        .flat_map(|i| {
+            if i == step * 5 {
+                [255, 128, 0, 255]
+            } else {
+                [0, 0, 0, 0]
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_encode_decode_frames() {
+    let frames: Vec<Vec<u8>> = (0..3).map(walking_sprite).collect();
+    let refs: Vec<&[u8]> = frames.iter().map(Vec::as_slice).collect();
+    let encoded = encode_frames(4, 4, &refs).unwrap();
+
+    let decoded = decode_frames(&encoded).unwrap();
+    assert_eq!(decoded.len(), 3);
+    for (frame, expected) in decoded.iter().zip(&frames) {
+        assert_eq!((frame.width, frame.height), (4, 4));
+        assert_eq!(frame.palette.len(), 2);
+        assert_eq!(&frame.rgba_data, expected);
+    }
+
+    // A still image decoder sees the first frame
+    assert_eq!(decode(&encoded).unwrap().rgba_data, frames[0]);
+    // and re-encoding keeps every frame
+    let upgraded = decode_frames(&re_encode(&encoded).unwrap()).unwrap();
+    assert_eq!(upgraded.len(), 3);
+    assert_eq!(upgraded[2].rgba_data, frames[2]);
+}
+
+#[test]
+fn test_encode_frames_rejects_mismatched_frames() {
+    assert!(matches!(
+        encode_frames(4, 4, &[]),
+        Err(EncodingError::NoFrames)
+    ));
+
+    let first = walking_sprite(0);
+    assert!(matches!(
+        encode_frames(4, 4, &[&first, &first[..8]]),
+        Err(EncodingError::InvalidFrameLength {
+            frame: 1,
+            expected: 64,
+            found: 8,
+        })
+    ));
+}
+
+#[test]
+fn test_decode_frames_still_image() {
+    let encoded = encode(16, 16, &GRADIENT).unwrap();
+    let frames = decode_frames(&encoded).unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].rgba_data, GRADIENT);
+}
+
+#[test]
+fn test_encode_decode_delta_frames() {
+    // A noisy 32x32 frame, then the same frame with one pixel changed
+    const COLORS: [[u8; 4]; 4] = [
+        [0, 0, 0, 255],
+        [255, 0, 0, 255],
+        [0, 255, 0, 255],
+        [0, 0, 255, 255],
+    ];
+    let mut seed = 1u32;
+    let first: Vec<u8> = (0..32 * 32)
+        .flat_map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            COLORS[(seed >> 16) as usize % COLORS.len()]
+        })
+        .collect();
+    let mut second = first.clone();
+    second[4 * 100..4 * 101].copy_from_slice(&[0, 0, 255, 255]);
+    second[4 * 101..4 * 102].copy_from_slice(&[255, 0, 0, 255]);
+
+    let delta = FrameOptions { delta: true };
+    let keyframe = encode_frames_with_options(32, 32, &[&first], delta).unwrap();
+    let full = encode_frames(32, 32, &[&first, &second]).unwrap();
+    let encoded = encode_frames_with_options(32, 32, &[&first, &second], delta).unwrap();
+
+    let decoded = decode_frames(&encoded).unwrap();
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0].rgba_data, first);
+    assert_eq!(decoded[1].rgba_data, second);
+
+    // Bytes added by the second frame
+    let full_frame = full.len() - keyframe.len();
+    let delta_frame = encoded.len() - keyframe.len();
+    assert!(
+        delta_frame * 4 < full_frame,
+        "delta frame {delta_frame} bytes, full frame {full_frame} bytes"
+    );
+
+    let upgraded = decode_frames(&re_encode(&encoded).unwrap()).unwrap();
+    assert_eq!(upgraded[1].rgba_data, second);
+    assert_eq!(re_encode(&encoded).unwrap().len(), encoded.len());
+}
+
+/// Offset of the flags byte in an encoded `RANDOM_RGB`: magic, version,
+/// dimensions, palette size and 3 colors
+const RANDOM_RGB_FLAGS: usize = 4 + 1 + 4 + 4 + 1 + 3 * 4;
+
+#[test]
+fn test_pipeline_flag_combinations() {
+    let metadata = [("key".to_string(), "value".to_string())];
+    for huffman in [false, true] {
+        for skip_rle_delta in [false, true] {
+            for metadata in [&[][..], &metadata[..]] {
+                let options = CompressionOptions {
+                    huffman,
+                    skip_rle_delta,
+                    ..Default::default()
+                };
+                let encoded = encode_with_options(4, 4, &RANDOM_RGB, metadata, options).unwrap();
+
+                let mut expected = 0;
+                if !metadata.is_empty() {
+                    expected |= FLAG_METADATA;
+                }
+                if huffman {
+                    expected |= FLAG_HUFFMAN;
+                }
+                if skip_rle_delta {
+                    expected |= FLAG_NO_RLE_DELTA;
+                }
+                assert_eq!(encoded[RANDOM_RGB_FLAGS], expected);
+
+                assert_eq!(decode(&encoded).unwrap().rgba_data, &RANDOM_RGB);
+                assert_eq!(decode_metadata(&encoded).unwrap(), metadata);
+                let upgraded = re_encode(&encoded).unwrap();
+                assert_eq!(upgraded[RANDOM_RGB_FLAGS], expected);
+            }
+        }
+    }
+
+    let frames = [&RANDOM_RGB[..], &RANDOM_RGB[..]];
+    for (delta, expected) in [(false, FLAG_FRAMES), (true, FLAG_FRAMES | FLAG_FRAME_DELTA)] {
+        let encoded = encode_frames_with_options(4, 4, &frames, FrameOptions { delta }).unwrap();
+        assert_eq!(encoded[RANDOM_RGB_FLAGS], expected);
+        assert_eq!(decode_frames(&encoded).unwrap().len(), 2);
+    }
+}
+
+#[test]
+fn test_encode_decode_tiles() {
+    // RANDOM_RGB repeated as a 3x2 tilemap, with a partial column of tiles
+    let (width, height) = (10, 8);
+    let mut pixels = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            let offset = ((y % 4) * 4 + x % 4) * 4;
+            pixels.extend_from_slice(&RANDOM_RGB[offset..offset + 4]);
+        }
+    }
+    let options = CompressionOptions {
+        tiles: Some(TileOptions {
+            width: width as u32,
+            tile_size: 4,
+        }),
+        ..Default::default()
+    };
+    let encoded = encode_with_options(width as u32, height as u32, &pixels, &[], options).unwrap();
+    assert_eq!(encoded[RANDOM_RGB_FLAGS], FLAG_TILES);
+
+    let decoded = decode(&encoded).unwrap();
+    assert_eq!((decoded.width, decoded.height), (10, 8));
+    assert_eq!(decoded.rgba_data, pixels);
+    assert_eq!(decode_indexed(&encoded).unwrap().rgba_data(), pixels);
+    assert_eq!(decode_lenient(&encoded).unwrap().rgba_data, pixels);
+
+    let upgraded = re_encode(&encoded).unwrap();
+    assert_eq!(upgraded, encoded);
+
+    // A tile section cut short
+    let truncated = seal(&encoded[..RANDOM_RGB_FLAGS + 3], RANDOM_RGB_FLAGS);
+    assert!(matches!(
+        decode(&truncated),
+        Err(DecodeError::InvalidTileSection)
+    ));
+}
+
+#[test]
+fn test_encode_decode_channel_planes() {
+    let options = CompressionOptions {
+        channel_planes: true,
+        ..Default::default()
+    };
+    let encoded = encode_with_options(16, 16, &GRADIENT, &[], options).unwrap();
+    // magic, version, dimensions, palette size and the single unused color
+    let flags = 4 + 1 + 4 + 4 + 1 + 4;
+    assert_eq!(encoded[flags], FLAG_CHANNEL_PLANES);
+
+    assert_eq!(decode(&encoded).unwrap().rgba_data, &GRADIENT);
+    assert_eq!(decode_lenient(&encoded).unwrap().rgba_data, &GRADIENT);
+    let frames = decode_frames(&encoded).unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].rgba_data, &GRADIENT);
+    assert!(matches!(
+        decode_indexed(&encoded),
+        Err(DecodeError::NotIndexed)
+    ));
+
+    assert_eq!(re_encode(&encoded).unwrap(), encoded);
+}
+
+#[test]
+fn test_encode_decode_split_alpha() {
+    // One color at 8 alpha levels, twice over
+    let pixels: Vec<u8> = (0..16)
+        .flat_map(|i| [250, 75, 98, (i % 8) as u8 * 32 + 31])
+        .collect();
+    let options = CompressionOptions {
+        split_alpha: true,
+        ..Default::default()
+    };
+    let encoded = encode_with_options(4, 4, &pixels, &[], options).unwrap();
+    // magic, version, dimensions, palette size and the single RGB color
+    let flags = 4 + 1 + 4 + 4 + 1 + 4;
+    assert_eq!(encoded[flags], FLAG_SPLIT_ALPHA);
+    // The alpha section holds the 8 levels
+    assert_eq!(encoded[flags + 1], 8);
+    assert!(encoded.len() < encode(4, 4, &pixels).unwrap().len());
+
+    assert_eq!(decode(&encoded).unwrap().rgba_data, pixels);
+    assert_eq!(decode_lenient(&encoded).unwrap().rgba_data, pixels);
+    assert_eq!(decode_frames(&encoded).unwrap()[0].rgba_data, pixels);
+    assert!(matches!(
+        decode_indexed(&encoded),
+        Err(DecodeError::NotIndexed)
+    ));
+    assert_eq!(re_encode(&encoded).unwrap(), encoded);
+
+    // An alpha section cut short
+    let truncated = seal(&encoded[..flags + 4], flags);
+    assert!(matches!(
+        decode(&truncated),
+        Err(DecodeError::InvalidAlphaSection)
+    ));
+}
+
+#[test]
+fn test_decode_unknown_pipeline() {
+    let encoded = encode(4, 4, &RANDOM_RGB).unwrap();
+    let body = &encoded[..encoded.len() - 4];
+
+    for flags in [
+        FLAG_FRAME_DELTA,
+        FLAG_FRAMES | FLAG_HUFFMAN,
+        FLAG_FRAMES | FLAG_NO_RLE_DELTA,
+        FLAG_FRAMES | FLAG_TILES,
+        FLAG_FRAMES | FLAG_CHANNEL_PLANES,
+        FLAG_FRAMES | FLAG_SPLIT_ALPHA,
+        FLAG_TILES | FLAG_CHANNEL_PLANES,
+        FLAG_CHANNEL_PLANES | FLAG_SPLIT_ALPHA,
+    ] {
+        let mut edited = body.to_vec();
+        edited[RANDOM_RGB_FLAGS] = flags;
+        assert!(
+            matches!(
+                decode(&seal(&edited, RANDOM_RGB_FLAGS)),
+                Err(DecodeError::UnknownPipeline(found)) if found == flags
+            ),
+            "flags {flags:#010b}"
+        );
+    }
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("pxc-encoding-{}-{}", std::process::id(), name))
+}
+
+#[test]
+fn test_image_save_pxc_and_png() {
+    let image = decode(&encode(16, 16, &GRADIENT).unwrap()).unwrap();
+
+    let pxc_path = temp_path("save.pxc");
+    image.save_pxc(&pxc_path).unwrap();
+    let saved = std::fs::read(&pxc_path).unwrap();
+    std::fs::remove_file(&pxc_path).unwrap();
+    assert_eq!(decode(&saved).unwrap().rgba_data, &GRADIENT);
+
+    let png_path = temp_path("save.png");
+    image.save_png(&png_path).unwrap();
+    let png = image::open(&png_path).unwrap().to_rgba8();
+    std::fs::remove_file(&png_path).unwrap();
+    assert_eq!(png.dimensions(), (16, 16));
+    assert_eq!(png.as_raw(), &GRADIENT);
+}
+
+#[test]
+fn test_image_save_mismatched_data() {
+    let image = Image::new(4, 4, 1, vec![[0; 4]], vec![0; 12]);
+
+    let pxc_path = temp_path("short.pxc");
+    let err = image.save_pxc(&pxc_path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(!pxc_path.exists());
+
+    let png_path = temp_path("short.png");
+    assert!(matches!(
+        image.save_png(&png_path),
+        Err(image::ImageError::Parameter(_))
+    ));
+    assert!(!png_path.exists());
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(100))]
+
+    #[test]
+    fn test_random_images_encode_decode(image in random_image()) {
+        let encoded = encode(image.width, image.height, &image.pixels)
+            .map_err(|err| TestCaseError::fail(format!("encode: {err:?}")))?;
+        let decoded =
+            decode(&encoded).map_err(|err| TestCaseError::fail(format!("decode: {err:?}")))?;
+        prop_assert_eq!((decoded.width, decoded.height), (image.width, image.height));
+        prop_assert_eq!(decoded.rgba_data, image.pixels);
+    }
+}